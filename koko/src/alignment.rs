@@ -0,0 +1,213 @@
+use kokoros::tts::koko::WordAlignment;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// File formats of `--alignment-out`, chosen by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentFormat {
+    /// Praat TextGrid with a words and a phones tier
+    TextGrid,
+    Json,
+}
+
+impl AlignmentFormat {
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("textgrid") => Ok(AlignmentFormat::TextGrid),
+            Some("json") => Ok(AlignmentFormat::Json),
+            _ => Err(format!(
+                "unsupported alignment file {}, expected a .TextGrid or .json extension",
+                path
+            )),
+        }
+    }
+}
+
+/// An interval of a TextGrid tier; empty `text` marks silence
+struct Interval<'a> {
+    start: f32,
+    end: f32,
+    text: &'a str,
+}
+
+/// Lay `spans` end to end over `0..duration` as Praat requires: gaps become
+/// empty intervals and overlaps (e.g. from cross-faded chunks) are cut at the
+/// start of the later span
+fn tile<'a>(spans: impl Iterator<Item = (f32, f32, &'a str)>, duration: f32) -> Vec<Interval<'a>> {
+    let mut intervals: Vec<Interval> = Vec::new();
+    let mut cursor = 0.0;
+    for (start, end, text) in spans {
+        let start = start.max(cursor);
+        let end = end.min(duration);
+        if end <= start {
+            continue;
+        }
+        if start > cursor {
+            intervals.push(Interval {
+                start: cursor,
+                end: start,
+                text: "",
+            });
+        }
+        intervals.push(Interval { start, end, text });
+        cursor = end;
+    }
+    if cursor < duration || intervals.is_empty() {
+        intervals.push(Interval {
+            start: cursor,
+            end: duration,
+            text: "",
+        });
+    }
+    intervals
+}
+
+/// Punctuation entries stand for pauses, which the tiers leave empty
+fn is_pause(word: &WordAlignment) -> bool {
+    word.phonemes.is_empty()
+}
+
+/// Render word and phone tiers in the long TextGrid text format
+pub fn text_grid(words: &[WordAlignment], duration: f32) -> String {
+    let word_tier = tile(
+        words
+            .iter()
+            .filter(|w| !is_pause(w))
+            .map(|w| (w.start_sec, w.end_sec, w.word.as_str())),
+        duration,
+    );
+    let phone_tier = tile(
+        words
+            .iter()
+            .flat_map(|w| &w.phonemes)
+            .map(|p| (p.start_sec, p.end_sec, p.phoneme.as_str())),
+        duration,
+    );
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\n\
+         xmin = 0\nxmax = {}\ntiers? <exists>\nsize = 2\nitem []:\n",
+        duration
+    );
+    for (i, (name, intervals)) in [("words", word_tier), ("phones", phone_tier)]
+        .into_iter()
+        .enumerate()
+    {
+        let _ = write!(
+            out,
+            "    item [{}]:\n        class = \"IntervalTier\"\n        name = \"{}\"\n        \
+             xmin = 0\n        xmax = {}\n        intervals: size = {}\n",
+            i + 1,
+            name,
+            duration,
+            intervals.len()
+        );
+        for (j, interval) in intervals.iter().enumerate() {
+            let _ = write!(
+                out,
+                "        intervals [{}]:\n            xmin = {}\n            xmax = {}\n            \
+                 text = \"{}\"\n",
+                j + 1,
+                interval.start,
+                interval.end,
+                interval.text.replace('"', "\"\"")
+            );
+        }
+    }
+    out
+}
+
+pub fn alignment_json(words: &[WordAlignment], duration: f32) -> serde_json::Value {
+    let words: Vec<_> = words
+        .iter()
+        .filter(|w| !is_pause(w))
+        .map(|w| {
+            let phonemes: Vec<_> = w
+                .phonemes
+                .iter()
+                .map(|p| json!({"phoneme": p.phoneme, "start_sec": p.start_sec, "end_sec": p.end_sec}))
+                .collect();
+            json!({
+                "word": w.word,
+                "start_sec": w.start_sec,
+                "end_sec": w.end_sec,
+                "phonemes": phonemes,
+            })
+        })
+        .collect();
+    json!({ "duration_sec": duration, "words": words })
+}
+
+/// Write the word and phoneme timings of `duration` seconds of audio to `path`,
+/// in the format given by its extension
+pub fn write_alignment(
+    path: &str,
+    words: &[WordAlignment],
+    duration: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = match AlignmentFormat::from_path(path)? {
+        AlignmentFormat::TextGrid => text_grid(words, duration),
+        AlignmentFormat::Json => serde_json::to_string_pretty(&alignment_json(words, duration))?,
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kokoros::tts::koko::PhonemeAlignment;
+
+    fn phoneme(phoneme: &str, start_sec: f32, end_sec: f32) -> PhonemeAlignment {
+        PhonemeAlignment {
+            phoneme: phoneme.to_string(),
+            start_sec,
+            end_sec,
+        }
+    }
+
+    #[test]
+    fn test_text_grid() {
+        let words = vec![
+            WordAlignment {
+                word: "hi".to_string(),
+                start_sec: 0.25,
+                end_sec: 0.75,
+                phonemes: vec![phoneme("h", 0.25, 0.5), phoneme("aɪ", 0.5, 0.75)],
+            },
+            WordAlignment {
+                word: ".".to_string(),
+                start_sec: 0.75,
+                end_sec: 1.0,
+                phonemes: Vec::new(),
+            },
+        ];
+        let grid = text_grid(&words, 1.0);
+
+        assert!(grid.starts_with("File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n"));
+        // Leading silence, the word, then the pause left empty
+        assert!(grid.contains(
+            "name = \"words\"\n        xmin = 0\n        xmax = 1\n        intervals: size = 3\n"
+        ));
+        assert!(grid.contains("xmin = 0.25\n            xmax = 0.75\n            text = \"hi\"\n"));
+        assert!(grid.contains("name = \"phones\""));
+        assert!(grid.contains("xmin = 0.5\n            xmax = 0.75\n            text = \"aɪ\"\n"));
+
+        let json = alignment_json(&words, 1.0);
+        assert_eq!(json["words"].as_array().unwrap().len(), 1);
+        assert_eq!(json["words"][0]["phonemes"][1]["phoneme"], "aɪ");
+
+        assert_eq!(
+            AlignmentFormat::from_path("out/a.TextGrid"),
+            Ok(AlignmentFormat::TextGrid)
+        );
+        assert!(AlignmentFormat::from_path("a.tsv").is_err());
+    }
+}
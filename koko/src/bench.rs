@@ -0,0 +1,187 @@
+use kokoros::tts::koko::TTSKoko;
+use std::time::{Duration, Instant};
+
+/// Settings of a `bench` run
+pub struct BenchOpts<'a> {
+    pub lan: &'a str,
+    pub style: &'a str,
+    pub speed: f32,
+    pub sample_rate: u32,
+    pub runs: usize,
+    pub warmup: usize,
+    /// Don't report progress after each run
+    pub quiet: bool,
+}
+
+/// Aggregated results over every utterance of every measured run
+pub struct BenchReport {
+    pub runs: usize,
+    pub utterances: usize,
+    pub mean_rtf: f64,
+    pub median_rtf: f64,
+    pub p95_latency_ms: f64,
+    /// Seconds of audio produced per second of wall time
+    pub throughput: f64,
+    pub chars_per_sec: f64,
+    pub total_audio_sec: f64,
+    pub total_wall_sec: f64,
+}
+
+/// Synthesis time against audio produced, for `--no-write` runs that skip
+/// encoding and file output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub utterances: usize,
+    pub audio_sec: f64,
+    pub synth_sec: f64,
+}
+
+impl RenderStats {
+    /// Stats of one utterance of `samples` synthesized in `elapsed`
+    pub fn new(samples: usize, sample_rate: u32, elapsed: Duration) -> Self {
+        Self {
+            utterances: 1,
+            audio_sec: samples as f64 / sample_rate as f64,
+            synth_sec: elapsed.as_secs_f64(),
+        }
+    }
+
+    /// Real-time factor, seconds of synthesis per second of audio
+    pub fn rtf(&self) -> f64 {
+        if self.audio_sec > 0.0 {
+            self.synth_sec / self.audio_sec
+        } else {
+            0.0
+        }
+    }
+
+    pub fn add(&mut self, other: RenderStats) {
+        self.utterances += other.utterances;
+        self.audio_sec += other.audio_sec;
+        self.synth_sec += other.synth_sec;
+    }
+}
+
+impl std::fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2}s of audio in {:.2}s of synthesis, RTF {:.3}",
+            self.audio_sec,
+            self.synth_sec,
+            self.rtf()
+        )
+    }
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    // Nearest-rank percentile
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Synthesize every line of `corpus` `warmup + runs` times and time it.
+/// Warmup runs are not included in the report.
+pub fn run_bench(
+    tts: &TTSKoko,
+    corpus: &[String],
+    opts: &BenchOpts,
+) -> Result<BenchReport, Box<dyn std::error::Error>> {
+    if corpus.is_empty() {
+        return Err("benchmark corpus has no non-empty lines".into());
+    }
+    if opts.runs == 0 {
+        return Err("--runs must be at least 1".into());
+    }
+
+    let sample_rate = opts.sample_rate as f64;
+    let mut rtfs = Vec::new();
+    let mut latencies_ms = Vec::new();
+    let mut total_audio_sec = 0.0;
+    let mut total_wall_sec = 0.0;
+    let mut total_chars = 0;
+
+    for run in 0..opts.warmup + opts.runs {
+        let measured = run >= opts.warmup;
+        for line in corpus {
+            let start = Instant::now();
+            let audio = tts.tts_raw_audio(
+                line, opts.lan, opts.style, opts.speed, None, None, None, None,
+            )?;
+            let elapsed = start.elapsed().as_secs_f64();
+            if !measured {
+                continue;
+            }
+
+            let audio_sec = audio.len() as f64 / sample_rate;
+            if audio_sec > 0.0 {
+                rtfs.push(elapsed / audio_sec);
+            }
+            latencies_ms.push(elapsed * 1000.0);
+            total_audio_sec += audio_sec;
+            total_wall_sec += elapsed;
+            total_chars += line.chars().count();
+        }
+        if opts.quiet {
+            continue;
+        }
+        if measured {
+            eprintln!("Run {}/{} done", run - opts.warmup + 1, opts.runs);
+        } else {
+            eprintln!("Warmup {}/{} done", run + 1, opts.warmup);
+        }
+    }
+
+    rtfs.sort_by(|a, b| a.total_cmp(b));
+    latencies_ms.sort_by(|a, b| a.total_cmp(b));
+
+    let mean_rtf = if rtfs.is_empty() {
+        0.0
+    } else {
+        rtfs.iter().sum::<f64>() / rtfs.len() as f64
+    };
+
+    Ok(BenchReport {
+        runs: opts.runs,
+        utterances: latencies_ms.len(),
+        mean_rtf,
+        median_rtf: percentile(&rtfs, 50.0),
+        p95_latency_ms: percentile(&latencies_ms, 95.0),
+        throughput: total_audio_sec / total_wall_sec.max(f64::EPSILON),
+        chars_per_sec: total_chars as f64 / total_wall_sec.max(f64::EPSILON),
+        total_audio_sec,
+        total_wall_sec,
+    })
+}
+
+impl BenchReport {
+    pub fn print_table(&self) {
+        println!("{:<24} {:>12}", "metric", "value");
+        println!("{:<24} {:>12}", "runs", self.runs);
+        println!("{:<24} {:>12}", "utterances", self.utterances);
+        println!("{:<24} {:>12.3}", "mean RTF", self.mean_rtf);
+        println!("{:<24} {:>12.3}", "median RTF", self.median_rtf);
+        println!("{:<24} {:>12.1}", "p95 latency (ms)", self.p95_latency_ms);
+        println!("{:<24} {:>12.2}", "throughput (audio s/s)", self.throughput);
+        println!("{:<24} {:>12.1}", "chars/s", self.chars_per_sec);
+        println!("{:<24} {:>12.1}", "total audio (s)", self.total_audio_sec);
+        println!("{:<24} {:>12.1}", "total wall (s)", self.total_wall_sec);
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "runs": self.runs,
+            "utterances": self.utterances,
+            "mean_rtf": self.mean_rtf,
+            "median_rtf": self.median_rtf,
+            "p95_latency_ms": self.p95_latency_ms,
+            "throughput_audio_sec_per_sec": self.throughput,
+            "chars_per_sec": self.chars_per_sec,
+            "total_audio_sec": self.total_audio_sec,
+            "total_wall_sec": self.total_wall_sec,
+        })
+    }
+}
@@ -0,0 +1,18 @@
+/// Text on the system clipboard for `--clipboard`, `None` when it holds no
+/// text or only whitespace
+#[cfg(feature = "clipboard")]
+pub fn read_clipboard() -> Result<Option<String>, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("failed to open the clipboard: {}", e))?;
+    match clipboard.get_text() {
+        Ok(text) if text.trim().is_empty() => Ok(None),
+        Ok(text) => Ok(Some(text)),
+        Err(arboard::Error::ContentNotAvailable) => Ok(None),
+        Err(e) => Err(format!("failed to read the clipboard: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn read_clipboard() -> Result<Option<String>, String> {
+    Err("--clipboard needs koko built with the `clipboard` feature".to_string())
+}
@@ -0,0 +1,109 @@
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use serde_json::{Map, Value, json};
+
+/// The settings of `matches` after defaults, environment variables and
+/// presets are applied, as JSON: every option of `cmd` and of the chosen
+/// subcommand with its value and where the value came from (`default`,
+/// `env`, `preset` or `command line`). Options named in `from_preset` were
+/// filled in from the preset.
+pub fn resolved_config(cmd: &Command, matches: &ArgMatches, from_preset: &[String]) -> Value {
+    let mut config = json!({ "options": options(cmd, matches, from_preset) });
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        if let Some(sub) = cmd.find_subcommand(name) {
+            config["mode"] = json!({
+                "name": name,
+                "options": options(sub, sub_matches, &[]),
+            });
+        }
+    }
+    config
+}
+
+fn options(cmd: &Command, matches: &ArgMatches, from_preset: &[String]) -> Map<String, Value> {
+    let mut options = Map::new();
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches!(arg.get_action(), ArgAction::Help | ArgAction::Version) {
+            continue;
+        }
+        let name = arg.get_long().unwrap_or(id);
+        let values: Vec<String> = matches
+            .get_raw(id)
+            .map(|raw| raw.map(|v| v.to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        let value = if matches!(arg.get_action(), ArgAction::Count) {
+            json!(matches.get_count(id))
+        } else if !arg.get_action().takes_values() {
+            // Switches hold "true" or "false"
+            Value::Bool(values.first().is_some_and(|v| v == "true"))
+        } else if matches!(arg.get_action(), ArgAction::Append) || values.len() > 1 {
+            json!(values)
+        } else {
+            values.into_iter().next().map_or(Value::Null, Value::String)
+        };
+        let source = match matches.value_source(id) {
+            Some(ValueSource::CommandLine) if from_preset.iter().any(|p| p == name) => "preset",
+            Some(ValueSource::CommandLine) => "command line",
+            Some(ValueSource::EnvVariable) => "env",
+            Some(ValueSource::DefaultValue) => "default",
+            _ => "unset",
+        };
+        options.insert(
+            name.to_string(),
+            json!({ "value": value, "source": source }),
+        );
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    #[test]
+    fn test_resolved_config() {
+        let cmd = Command::new("koko")
+            .arg(Arg::new("style").long("style").default_value("af_heart"))
+            .arg(Arg::new("speed").long("speed").default_value("1.0"))
+            .arg(Arg::new("lan").long("lan"))
+            .arg(
+                Arg::new("limiter")
+                    .long("limiter")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                Command::new("text")
+                    .arg(Arg::new("output").long("output").default_value("out.wav")),
+            );
+        let matches = cmd.clone().get_matches_from([
+            "koko",
+            "--style",
+            "bf_emma",
+            "--limiter",
+            "--speed",
+            "0.9",
+            "text",
+        ]);
+
+        let config = resolved_config(&cmd, &matches, &["speed".to_string()]);
+        let options = &config["options"];
+        assert_eq!(
+            options["style"],
+            json!({"value": "bf_emma", "source": "command line"})
+        );
+        assert_eq!(
+            options["speed"],
+            json!({"value": "0.9", "source": "preset"})
+        );
+        assert_eq!(options["lan"], json!({"value": null, "source": "unset"}));
+        assert_eq!(options["limiter"]["value"], json!(true));
+        assert!(options.get("help").is_none());
+        assert_eq!(config["mode"]["name"], "text");
+        assert_eq!(
+            config["mode"]["options"]["output"],
+            json!({"value": "out.wav", "source": "default"})
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use kokoros::utils::audio::to_pcm16;
+use kokoros::utils::mp3::pcm_to_mp3;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Formats `--also` writes next to the WAV file, encoded from the same audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraFormat {
+    Mp3,
+    /// Headerless signed 16-bit little-endian mono samples, like the server's `pcm`
+    Pcm,
+}
+
+impl FromStr for ExtraFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mp3" => Ok(ExtraFormat::Mp3),
+            "pcm" => Ok(ExtraFormat::Pcm),
+            "opus" => {
+                Err("opus encoding is not available in this build, expected mp3 or pcm".into())
+            }
+            other => Err(format!("unknown format '{}', expected mp3 or pcm", other)),
+        }
+    }
+}
+
+impl ExtraFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExtraFormat::Mp3 => "mp3",
+            ExtraFormat::Pcm => "pcm",
+        }
+    }
+
+    /// Encode mono `audio`
+    pub fn encode(self, audio: &[f32], sample_rate: u32) -> std::io::Result<Vec<u8>> {
+        match self {
+            ExtraFormat::Mp3 => pcm_to_mp3(audio, sample_rate),
+            ExtraFormat::Pcm => Ok(to_pcm16(audio)),
+        }
+    }
+}
+
+/// `save_path` with the extension of `format`, e.g. `master.mp3` for `master.wav`
+pub fn sibling_path(save_path: &str, format: ExtraFormat) -> PathBuf {
+    Path::new(save_path).with_extension(format.extension())
+}
+
+/// Write `audio` in each of `formats` next to `save_path`, returning the
+/// paths written
+pub fn write_extra_formats(
+    save_path: &str,
+    audio: &[f32],
+    sample_rate: u32,
+    formats: &[ExtraFormat],
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::with_capacity(formats.len());
+    for &format in formats {
+        let path = sibling_path(save_path, format);
+        let data = format
+            .encode(audio, sample_rate)
+            .map_err(|e| format!("failed to encode {}: {}", path.display(), e))?;
+        std::fs::write(&path, data)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_formats() {
+        assert_eq!(" MP3".parse(), Ok(ExtraFormat::Mp3));
+        assert!(
+            "opus"
+                .parse::<ExtraFormat>()
+                .unwrap_err()
+                .contains("not available")
+        );
+        assert!("ogg".parse::<ExtraFormat>().is_err());
+
+        assert_eq!(
+            sibling_path("out/master.wav", ExtraFormat::Mp3),
+            Path::new("out/master.mp3")
+        );
+        assert_eq!(
+            sibling_path("master", ExtraFormat::Pcm),
+            Path::new("master.pcm")
+        );
+
+        let pcm = ExtraFormat::Pcm.encode(&[0.0, 1.0, -2.0], 24000).unwrap();
+        assert_eq!(pcm, [0x00, 0x00, 0xff, 0x7f, 0x00, 0x80]);
+    }
+}
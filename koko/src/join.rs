@@ -0,0 +1,148 @@
+use crate::wav::{WavOptions, write_frames};
+use kokoros::utils::wav::{SUPPORTED_BITS_PER_SAMPLE, WavAudio, read_wav};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Concatenate the WAV files `inputs` into `output` with `gap_ms` of silence
+/// between them. Every input must have the format of the first, which the
+/// output keeps. Returns the duration written in seconds.
+pub fn join_wavs(inputs: &[String], output: &str, gap_ms: u32) -> Result<f32, String> {
+    let (first_path, rest) = inputs
+        .split_first()
+        .ok_or_else(|| "join needs at least one input".to_string())?;
+    let first = read_wav(first_path)?;
+    if first.float != (first.bits_per_sample == 32)
+        || !SUPPORTED_BITS_PER_SAMPLE.contains(&first.bits_per_sample)
+    {
+        return Err(format!(
+            "{} is {}, expected 16 or 24-bit PCM or 32-bit float",
+            first_path,
+            first.describe_format()
+        ));
+    }
+
+    let mut clips = vec![first];
+    for path in rest {
+        let clip = read_wav(path)?;
+        check_same_format(&clips[0], first_path, &clip, path)?;
+        clips.push(clip);
+    }
+
+    let first = &clips[0];
+    let frames = join_clips(&clips, gap_ms);
+    let opts = WavOptions {
+        channels: first.channels,
+        bits_per_sample: first.bits_per_sample,
+        ..Default::default()
+    };
+    let mut w = BufWriter::new(
+        File::create(output).map_err(|e| format!("failed to create {}: {}", output, e))?,
+    );
+    write_frames(&mut w, &frames, first.channels, first.sample_rate, &opts)
+        .and_then(|_| w.flush())
+        .map_err(|e| format!("failed to write {}: {}", output, e))?;
+
+    Ok(frames.len() as f32 / first.channels.max(1) as f32 / first.sample_rate as f32)
+}
+
+fn check_same_format(
+    first: &WavAudio,
+    first_path: &str,
+    clip: &WavAudio,
+    path: &str,
+) -> Result<(), String> {
+    let same = clip.channels == first.channels
+        && clip.sample_rate == first.sample_rate
+        && clip.bits_per_sample == first.bits_per_sample
+        && clip.float == first.float;
+    if same {
+        Ok(())
+    } else {
+        Err(format!(
+            "cannot join {} ({}) to {} ({}): every input needs the same sample rate, channels and sample format",
+            path,
+            clip.describe_format(),
+            first_path,
+            first.describe_format()
+        ))
+    }
+}
+
+/// Interleaved samples of `clips`, which share one format, with `gap_ms` of
+/// silence between consecutive clips
+fn join_clips(clips: &[WavAudio], gap_ms: u32) -> Vec<f32> {
+    let Some(first) = clips.first() else {
+        return Vec::new();
+    };
+    let gap = (first.sample_rate as u64 * gap_ms as u64 / 1000) as usize * first.channels as usize;
+    let total =
+        clips.iter().map(|c| c.samples.len()).sum::<usize>() + gap * clips.len().saturating_sub(1);
+
+    let mut joined = Vec::with_capacity(total);
+    for (i, clip) in clips.iter().enumerate() {
+        if i > 0 {
+            joined.resize(joined.len() + gap, 0.0);
+        }
+        joined.extend_from_slice(&clip.samples);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(samples: Vec<f32>, channels: u16, sample_rate: u32) -> WavAudio {
+        WavAudio {
+            samples,
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            float: true,
+        }
+    }
+
+    #[test]
+    fn test_join_wavs() {
+        let dir = std::env::temp_dir().join(format!("kokoros-join-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let write = |name: &str, audio: &WavAudio| {
+            let opts = WavOptions {
+                channels: audio.channels,
+                bits_per_sample: audio.bits_per_sample,
+                ..Default::default()
+            };
+            let mut bytes = Vec::new();
+            write_frames(
+                &mut bytes,
+                &audio.samples,
+                audio.channels,
+                audio.sample_rate,
+                &opts,
+            )
+            .unwrap();
+            std::fs::write(path(name), bytes).unwrap();
+        };
+
+        write("a.wav", &clip(vec![0.25, -0.25, 0.5, -0.5], 2, 1000));
+        write("b.wav", &clip(vec![1.0, -1.0], 2, 1000));
+        write("mono.wav", &clip(vec![0.1, 0.2], 1, 1000));
+
+        let secs = join_wavs(&[path("a.wav"), path("b.wav")], &path("out.wav"), 2).unwrap();
+        assert!((secs - 0.005).abs() < 1e-6);
+        let joined = read_wav(&path("out.wav")).unwrap();
+        assert_eq!(
+            joined.samples,
+            [0.25, -0.25, 0.5, -0.5, 0.0, 0.0, 0.0, 0.0, 1.0, -1.0]
+        );
+        assert_eq!((joined.channels, joined.sample_rate), (2, 1000));
+
+        let mismatch = join_wavs(&[path("a.wav"), path("mono.wav")], &path("bad.wav"), 0);
+        let message = mismatch.unwrap_err();
+        assert!(message.contains("1 channel,"), "{}", message);
+        assert!(!dir.join("bad.wav").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
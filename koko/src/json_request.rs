@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+/// A text job given as JSON with `--json-request`, in the shape of the
+/// server's speech requests plus the output settings of text mode. Fields
+/// left out keep the value of the corresponding command line option
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonRequest {
+    /// Text to generate speech for
+    pub input: String,
+    /// Voice or voice blend, like `--style`
+    pub voice: Option<String>,
+    /// Rate of speech, like `--speed`
+    pub speed: Option<f32>,
+    /// eSpeak language, like `--lan`
+    pub lang_code: Option<String>,
+    pub initial_silence: Option<usize>,
+    /// Path of the WAV file, like `text --output`
+    pub output: Option<String>,
+    pub mono: Option<bool>,
+    pub low_memory: Option<bool>,
+}
+
+impl JsonRequest {
+    /// Parse `arg`, either the JSON itself or `@` followed by the path of a
+    /// file holding it
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let (json, source) = match arg.strip_prefix('@') {
+            Some(path) => (
+                std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {}", path, e))?,
+                path,
+            ),
+            None => (arg.to_string(), "--json-request"),
+        };
+        let request: JsonRequest = serde_json::from_str(kokoros::utils::fileio::strip_bom(&json))
+            .map_err(|e| format!("{}: {}", source, e))?;
+
+        if request.input.trim().is_empty() {
+            return Err(format!("{}: input is empty", source));
+        }
+        match request.speed {
+            Some(speed) if !(speed > 0.0 && speed.is_finite()) => {
+                return Err(format!(
+                    "{}: invalid speed {}, expected a positive number",
+                    source, speed
+                ));
+            }
+            _ => {}
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_request() {
+        let request =
+            JsonRequest::parse(r#"{"input": "Hello", "voice": "af_sky", "speed": 1.2}"#).unwrap();
+        assert_eq!(request.input, "Hello");
+        assert_eq!(request.voice.as_deref(), Some("af_sky"));
+        assert_eq!(request.speed, Some(1.2));
+        assert_eq!(request.output, None);
+
+        let unknown = JsonRequest::parse(r#"{"input": "Hello", "voise": "af_sky"}"#).unwrap_err();
+        assert!(unknown.contains("unknown field `voise`"), "{}", unknown);
+        assert!(JsonRequest::parse(r#"{"input": "Hello", "speed": 0}"#).is_err());
+        assert!(JsonRequest::parse(r#"{"voice": "af_sky"}"#).is_err());
+        assert!(JsonRequest::parse("@/nonexistent/request.json").is_err());
+    }
+}
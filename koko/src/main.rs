@@ -1,16 +1,63 @@
-use clap::{Parser, Subcommand};
+mod alignment;
+mod bench;
+mod clipboard;
+mod config;
+mod encode;
+mod join;
+mod json_request;
+mod manifest;
+mod preset;
+#[cfg(feature = "playback")]
+mod repl;
+mod wav;
+
+use alignment::{AlignmentFormat, write_alignment};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use encode::{ExtraFormat, sibling_path, write_extra_formats};
+use indicatif::{ProgressBar, ProgressStyle};
+use json_request::JsonRequest;
 use kokoros::{
-    tts::koko::{TTSKoko, TTSOpts},
-    utils::wav::{WavHeader, write_audio_chunk},
+    onn::ort_base::{OrtBase, SessionMemory, execution_provider},
+    onn::ort_koko::{ModelPrecision, OrtKoko},
+    tts::duration::DurationProfile,
+    tts::koko::{
+        InitConfig, NanPolicy, TTSKoko, TextPhonemizer, TimestampGranularity, WordAlignment,
+        parse_style, set_espeak_data_path,
+    },
+    tts::lang::{FALLBACK_LANGUAGE, MIN_CONFIDENCE, detect_language, style_for_language},
+    tts::lexicon::Lexicon,
+    tts::markdown::strip_markdown,
+    tts::normalize::{
+        DEFAULT_SYMBOL_TABLE, DigitReading, EmojiPolicy, normalize_digits, normalize_symbols,
+        spell_out,
+    },
+    tts::tokenize::UnknownPhonemePolicy,
+    tts::vocab::{VOCAB, load_vocab_file, missing_phonemes},
+    tts::voices,
+    utils::audio::{
+        AudioStats, DEFAULT_LIMITER_THRESHOLD, DeEsser, ResampleQuality, batch_gain, db_to_gain,
+        ms_to_samples, resample_with, soft_limit, split_on_silence,
+    },
+    utils::fileio::{
+        TextEncoding, default_cache_dir, fetch_cached_model, is_url, read_text_file, strip_bom,
+    },
+    utils::wav::{SUPPORTED_BITS_PER_SAMPLE, WavHeader, read_wav_mono},
 };
+use manifest::Manifest;
+use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::{
     fs::{self},
-    io::Write,
-    path::Path,
+    io::{IsTerminal, Write},
+    path::{Component, Path, PathBuf},
 };
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing_subscriber::fmt::time::FormatTime;
+use wav::{
+    Bext, MAX_CHANNELS, SplitWavWriter, StereoMode, StreamWavWriter, WavOptions, has_output,
+    write_wav_file,
+};
 
 /// Custom Unix timestamp formatter for tracing logs
 struct UnixTimestampFormatter;
@@ -25,18 +72,52 @@ impl FormatTime for UnixTimestampFormatter {
     }
 }
 
+/// Sample text synthesized by `text --demo`
+const DEMO_TEXT: &str = "Hello, This is Kokoro, your remarkable AI TTS. It's a TTS model with merely 82 million parameters yet delivers incredible audio quality.
+This is one of the top notch Rust based inference models, and I'm sure you'll love it. If you do, please give us a star. Thank you very much.
+As the night falls, I wish you all a peaceful and restful sleep. May your dreams be filled with joy and happiness. Good night, and sweet dreams!";
+
+/// Text mode for `text` with the defaults of its other options, for jobs
+/// given without a mode on the command line. Parsed like `text -o PATH -- TEXT`
+/// so the defaults come from the clap attributes
+fn default_text_mode(text: String, save_path: Option<String>) -> Result<Mode, clap::Error> {
+    let mut args = vec!["koko".to_string(), "text".to_string()];
+    if let Some(save_path) = save_path {
+        args.extend(["--output".to_string(), save_path]);
+    }
+    args.extend(["--".to_string(), text]);
+    let matches =
+        Mode::augment_subcommands(clap::Command::new("koko")).try_get_matches_from(args)?;
+    Mode::from_arg_matches(&matches)
+}
+
+/// The text of text mode, failing on empty input so scripts don't silently
+/// synthesize nothing
+fn text_mode_input(
+    text: &Option<String>,
+    demo: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if demo {
+        return Ok(DEMO_TEXT.to_string());
+    }
+    match text {
+        Some(text) if !text.trim().is_empty() => Ok(text.clone()),
+        _ => Err("no text to synthesize (pass --demo for the sample text)".into()),
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Mode {
     /// Generate speech for a string of text
     #[command(alias = "t", long_flag_alias = "text", short_flag_alias = 't')]
     Text {
-        /// Text to generate speech for
-        #[arg(
-            default_value = "Hello, This is Kokoro, your remarkable AI TTS. It's a TTS model with merely 82 million parameters yet delivers incredible audio quality.
-                This is one of the top notch Rust based inference models, and I'm sure you'll love it. If you do, please give us a star. Thank you very much.
-                As the night falls, I wish you all a peaceful and restful sleep. May your dreams be filled with joy and happiness. Good night, and sweet dreams!"
-        )]
-        text: String,
+        /// Text to generate speech for, required unless --demo or the
+        /// top-level --clipboard is given
+        text: Option<String>,
+
+        /// Synthesize the built-in sample text instead of TEXT
+        #[arg(long = "demo", conflicts_with = "text")]
+        demo: bool,
 
         /// Path to output the WAV file to on the filesystem
         #[arg(
@@ -46,6 +127,42 @@ enum Mode {
             default_value = "tmp/output.wav"
         )]
         save_path: String,
+
+        /// Also write the model's intermediate acoustic features (e.g. the mel
+        /// spectrogram) as a 2-D .npy array. Needs a model exported with them
+        /// as an extra output
+        #[arg(long = "dump-features", value_name = "PATH")]
+        dump_features: Option<String>,
+
+        /// Also write word and phoneme timings from the model's predicted
+        /// durations, as a Praat .TextGrid or .json file. Needs a timestamped model
+        #[arg(long = "alignment-out", value_name = "PATH")]
+        alignment_out: Option<String>,
+
+        /// Write one WAV file per spoken segment instead of a single file,
+        /// cutting the audio at pauses found by an energy-based detector.
+        /// Segments are numbered from OUTPUT_PATH, e.g. tmp/output_001.wav
+        #[arg(long = "split-on-silence", conflicts_with = "alignment_out")]
+        split_on_silence: bool,
+
+        /// Level at or below which a 10 ms frame counts as silence, in dBFS
+        #[arg(
+            long = "silence-threshold-db",
+            value_name = "DB",
+            default_value_t = -40.0,
+            allow_negative_numbers = true,
+            requires = "split_on_silence"
+        )]
+        silence_threshold_db: f32,
+
+        /// Shortest pause that starts a new segment, in milliseconds
+        #[arg(
+            long = "min-silence-ms",
+            value_name = "MS",
+            default_value_t = 300,
+            requires = "split_on_silence"
+        )]
+        min_silence_ms: u32,
     },
 
     /// Read from a file path and generate a speech file for each line
@@ -62,11 +179,122 @@ enum Mode {
             default_value = "tmp/output_{line}.wav"
         )]
         save_path_format: String,
+
+        /// Also write the phonemes of each line to a text file in this
+        /// directory, at the path of its WAV file (`DIR/tmp/output_3.txt` for
+        /// `tmp/output_3.wav`), for auditing pronunciations across a corpus
+        #[arg(long = "dump-phonemes-dir", value_name = "DIR")]
+        dump_phonemes_dir: Option<String>,
+
+        /// Synthesize every line before writing any, then apply one gain to
+        /// all of them that brings their overall loudness to
+        /// --normalize-batch-target-db, so the clips of a project play at
+        /// consistent levels while keeping their differences. Holds all the
+        /// audio in memory
+        #[arg(long = "normalize-batch", default_value_t = false)]
+        normalize_batch: bool,
+
+        /// Overall RMS level of --normalize-batch in dBFS. The gain is lowered
+        /// when the loudest clip would otherwise peak above -1 dBFS
+        #[arg(
+            long = "normalize-batch-target-db",
+            value_name = "DB",
+            default_value_t = -20.0,
+            allow_negative_numbers = true,
+            requires = "normalize_batch"
+        )]
+        normalize_batch_target_db: f32,
+
+        /// Leave out lines whose output file already exists and isn't empty,
+        /// to resume an interrupted run. With --manifest they are recorded as
+        /// skipped. Can't be combined with --normalize-batch, whose gain
+        /// depends on every line
+        #[arg(
+            long = "skip-existing",
+            default_value_t = false,
+            conflicts_with = "normalize_batch"
+        )]
+        skip_existing: bool,
     },
 
     /// Continuously read from stdin to generate speech, outputting to stdout, for each line
     #[command(aliases = ["stdio", "stdin", "-"], long_flag_aliases = ["stdio", "stdin"])]
-    Stream,
+    Stream {
+        /// Read lines from this named pipe instead of stdin, reopening it
+        /// whenever the writer closes it so Kokoros keeps serving
+        #[arg(long = "input-fifo", value_name = "PATH")]
+        input_fifo: Option<String>,
+
+        /// Write the WAV stream to this file instead of stdout. The header
+        /// sizes of regular files are kept up to date after every line
+        #[arg(short = 'o', long = "output", value_name = "OUTPUT_PATH")]
+        output: Option<String>,
+
+        /// Synthesize up to N chunks of a line ahead of the output, in a
+        /// bounded queue, and write the first N together, so a slow chunk
+        /// doesn't stutter the playback. Adds a little latency before each
+        /// line; 0 writes every line once it is synthesized
+        #[arg(long = "stream-lookahead", value_name = "N", default_value_t = 0)]
+        stream_lookahead: usize,
+    },
+
+    /// Interactively synthesize typed lines and play them on the default audio device
+    #[cfg(feature = "playback")]
+    Repl,
+
+    /// Benchmark synthesis of a text corpus with the current settings
+    Bench {
+        /// Corpus to synthesize, one utterance per line
+        #[arg(long = "text-file", value_name = "PATH")]
+        text_file: String,
+
+        /// Number of measured passes over the corpus
+        #[arg(long = "runs", default_value_t = 5)]
+        runs: usize,
+
+        /// Number of unmeasured passes run first to warm up the session
+        #[arg(long = "warmup", default_value_t = 1)]
+        warmup: usize,
+
+        /// Also write the results as JSON to this path (`-` for stdout)
+        #[arg(long = "json", value_name = "PATH")]
+        json: Option<String>,
+    },
+
+    /// Print the input and output tensors of an ONNX model, to diagnose
+    /// incompatible custom models
+    InspectModel {
+        /// Model to inspect, defaults to the --model path
+        model: Option<String>,
+
+        /// Text to phonemize in --lan for the phoneme coverage check, instead
+        /// of the built-in sample of the language
+        #[arg(long = "probe-file", value_name = "PATH")]
+        probe_file: Option<String>,
+    },
+
+    /// Check a voices file before deploying it: embedding shapes against the
+    /// --model style input, NaN values and duplicates. Runs no inference
+    ValidateVoices {
+        /// Voices file to check, defaults to the --data path
+        voices: Option<String>,
+    },
+
+    /// Concatenate existing WAV files, which must share one sample rate,
+    /// channel count and sample format
+    Join {
+        /// WAV files to join, in order
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<String>,
+
+        /// Path of the joined WAV file
+        #[arg(short = 'o', long = "output", value_name = "OUTPUT_PATH")]
+        output: String,
+
+        /// Silence between consecutive files, in milliseconds
+        #[arg(long = "gap-ms", value_name = "MS", default_value_t = 0)]
+        gap_ms: u32,
+    },
 
     /// Start an OpenAI-compatible HTTP server
     #[command(name = "openai", alias = "oai", long_flag_aliases = ["oai", "openai"])]
@@ -78,6 +306,72 @@ enum Mode {
         /// Port to expose the HTTP server on
         #[arg(long, default_value_t = 3000)]
         port: u16,
+
+        /// Reject requests whose input is longer than this many characters (HTTP 413)
+        #[arg(long = "max-input-chars", default_value_t = kokoros_openai::DEFAULT_MAX_INPUT_CHARS)]
+        max_input_chars: usize,
+
+        /// Additional models selectable through the request `model` field,
+        /// as `name=path` pairs separated by commas (e.g. `v019=kokoro-v0_19.onnx`)
+        #[arg(
+            long = "models",
+            value_name = "NAME=PATH",
+            value_delimiter = ',',
+            value_parser = parse_named_model
+        )]
+        models: Vec<(String, String)>,
+
+        /// Cache non-streaming responses in this directory and answer repeated
+        /// requests from it without synthesis
+        #[arg(long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<String>,
+
+        /// Size cap of --cache-dir in megabytes; least recently used entries
+        /// are removed beyond it
+        #[arg(long = "cache-max-mb", value_name = "MB", default_value_t = 512)]
+        cache_max_mb: u64,
+
+        /// Also cache a WAV copy of every response and answer requests for
+        /// the same speech in another format by re-encoding it, without
+        /// synthesis
+        #[arg(
+            long = "cache-transcode",
+            default_value_t = false,
+            requires = "cache_dir"
+        )]
+        cache_transcode: bool,
+
+        /// Resampler for the `sample_rate` of streamed responses, e.g. `fast`
+        /// for low latency. Defaults to --resample-quality
+        #[arg(long = "stream-resample-quality", value_name = "QUALITY")]
+        stream_resample_quality: Option<ResampleQuality>,
+
+        /// Synthesize at most this many speech requests at once, bounding
+        /// memory use under load; unlimited by default
+        #[arg(long = "max-concurrent", value_name = "N")]
+        max_concurrent: Option<std::num::NonZeroUsize>,
+
+        /// What to do with requests beyond --max-concurrent: `queue` waits for
+        /// a free slot, `reject` answers with HTTP 503
+        #[arg(
+            long = "overflow",
+            value_name = "POLICY",
+            default_value = "queue",
+            requires = "max_concurrent"
+        )]
+        overflow: kokoros_openai::OverflowPolicy,
+
+        /// JSON file mapping API keys to the default voice and speed of
+        /// requests that omit them, e.g. `{"sk-a": {"voice": "af_nicole", "speed": 1.1}}`.
+        /// The key is read from the `Authorization: Bearer` header
+        #[arg(long = "key-defaults", value_name = "KEYFILE")]
+        key_defaults: Option<String>,
+
+        /// Keep the blended style tables of this many distinct voice blends
+        /// per instance, dropping the least recently used beyond it (0
+        /// disables). Hit rates are reported on `/metrics`
+        #[arg(long = "blend-cache-size", value_name = "N", default_value_t = 64)]
+        blend_cache_size: usize,
     },
 }
 
@@ -96,7 +390,32 @@ struct Cli {
     )]
     lan: String,
 
-    /// Path to the Kokoro v1.0 ONNX model on the filesystem
+    /// eSpeak NG data directory (`espeak-ng-data` or its parent), for layouts
+    /// where auto-detection fails
+    #[arg(
+        long = "espeak-data-path",
+        value_name = "DIR",
+        env = "ESPEAK_DATA_PATH"
+    )]
+    espeak_data_path: Option<String>,
+
+    /// eSpeak NG voice variant used when phonemizing, e.g. f3, m2 or whisper.
+    /// Only changes eSpeak's phonemes, not the neural voice chosen by --style
+    #[arg(long = "espeak-variant", value_name = "VARIANT", value_parser = parse_espeak_variant)]
+    espeak_variant: Option<String>,
+
+    /// Detect the language of each input and pick a matching eSpeak language
+    /// and default voice (falls back to en-us when detection is not confident)
+    #[arg(long = "auto-lang", default_value_t = false)]
+    auto_lang: bool,
+
+    /// Detect the language of every sentence and switch eSpeak language and
+    /// voice accordingly (short sentences keep the previous sentence's language)
+    #[arg(long = "per-sentence-lang", default_value_t = false)]
+    per_sentence_lang: bool,
+
+    /// Path to the Kokoro v1.0 ONNX model on the filesystem, or an http(s)
+    /// URL downloaded once into ~/.cache/kokoros/models
     #[arg(
         short = 'm',
         long = "model",
@@ -105,6 +424,44 @@ struct Cli {
     )]
     model_path: String,
 
+    /// Verify the model file against this hex SHA-256 before loading it,
+    /// failing on a mismatch (e.g. a truncated download). -v logs the
+    /// computed hash either way
+    #[arg(long = "model-sha256", value_name = "HASH", value_parser = parse_sha256)]
+    model_sha256: Option<String>,
+
+    /// Precision of the ONNX model: auto, fp32 or int8. Integer outputs of
+    /// quantized models are dequantized to f32 unless fp32 is requested
+    #[arg(
+        long = "model-precision",
+        value_name = "PRECISION",
+        default_value = "auto"
+    )]
+    model_precision: ModelPrecision,
+
+    /// Recreate the session on the CPU when the GPU execution provider fails
+    /// to create or run it (e.g. an unsupported op), logging a warning,
+    /// instead of failing synthesis
+    #[arg(long = "provider-fallback", default_value_t = false)]
+    provider_fallback: bool,
+
+    /// Memory arena of ONNX Runtime in megabytes: caps the CUDA arena, while 0
+    /// disables the CPU arena (which can't be capped). By default the arena
+    /// grows as needed and is never returned
+    #[arg(long = "arena-mb", value_name = "MB")]
+    arena_mb: Option<usize>,
+
+    /// ONNX Runtime's memory pattern optimization, which plans allocations
+    /// from earlier runs. Disabling it lowers peak memory for varying input
+    /// lengths at some speed cost
+    #[arg(
+        long = "memory-pattern",
+        value_name = "BOOL",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    memory_pattern: bool,
+
     /// Path to the voices data file on the filesystem
     #[arg(
         short = 'd',
@@ -114,6 +471,20 @@ struct Cli {
     )]
     data_path: String,
 
+    /// More voices files to load after --data, comma separated, e.g. community
+    /// voice packs. Every voice must have the embedding shape of the --data voices
+    #[arg(long = "voices", value_name = "FILES", value_delimiter = ',')]
+    voice_packs: Vec<String>,
+
+    /// Let a voice of a later voices file replace one of the same name, in
+    /// the order --data then --voices, instead of failing on the duplicate
+    #[arg(
+        long = "voices-override-order",
+        default_value_t = false,
+        requires = "voice_packs"
+    )]
+    voices_override_order: bool,
+
     /// Which single voice to use or voices to combine to serve as the style of speech
     #[arg(
         short = 's',
@@ -125,21 +496,266 @@ struct Cli {
     )]
     style: String,
 
+    /// Voice to blend, repeatable; pair each with a `--weight` (e.g. `--voice af_sky
+    /// --weight 0.4 --voice af_nicole --weight 0.6`). Overrides --style
+    #[arg(
+        long = "voice",
+        value_name = "NAME",
+        action = clap::ArgAction::Append,
+        conflicts_with = "style"
+    )]
+    voices: Vec<String>,
+
+    /// Weight of the corresponding `--voice`, repeatable. Without any weights
+    /// the voices are blended equally
+    #[arg(
+        long = "weight",
+        value_name = "WEIGHT",
+        action = clap::ArgAction::Append,
+        requires = "voices"
+    )]
+    weights: Vec<f32>,
+
     /// Rate of speech, as a coefficient of the default
     /// (i.e. 0.0 to 1.0 is slower than default,
     /// whereas 1.0 and beyond is faster than default)
+    /// or as a percentage (`120%` is the same as `1.2`)
     #[arg(
         short = 'p',
         long = "speed",
         value_name = "SPEED",
-        default_value_t = 1.0
+        default_value = "1.0",
+        value_parser = parse_speed
     )]
     speed: f32,
 
-    /// Output audio in mono (as opposed to stereo)
-    #[arg(long = "mono", default_value_t = false)]
+    /// Energy/expressiveness from 0.0 (flat) to 1.0 (lively), for model
+    /// variants with an `expressiveness` or `energy` input. Ignored with a
+    /// warning by models without one
+    #[arg(long = "expressiveness", value_name = "LEVEL", value_parser = parse_expressiveness)]
+    expressiveness: Option<f32>,
+
+    /// Speak spans enclosed in MARKER, e.g. `*` for `This is *really*
+    /// important`, slower and a little louder. Escape the marker with `\`
+    #[arg(long = "emphasis-marker", value_name = "MARKER", value_parser = parse_emphasis_marker)]
+    emphasis_marker: Option<String>,
+
+    /// Shorten pauses within a chunk longer than MS milliseconds, such as
+    /// overly long ones at commas, to MS, for tighter pacing without a higher
+    /// --speed. Can't be combined with --timestamps or --alignment-out
+    #[arg(
+        long = "max-internal-pause-ms",
+        value_name = "MS",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    max_internal_pause_ms: Option<u32>,
+
+    /// Per phoneme class duration multipliers, inline (`vowel=1.5,consonant=1.0`)
+    /// or a file with one `class=factor` pair per line. Factors of at least
+    /// 1.25 add one IPA length mark per 0.5 above 1.0, factors of at most 0.8
+    /// drop the length marks
+    #[arg(
+        long = "duration-profile",
+        value_name = "PROFILE",
+        value_parser = DurationProfile::load
+    )]
+    duration_profile: Option<DurationProfile>,
+
+    /// Cross-fade adjacent chunks of long inputs over this many milliseconds
+    /// when joining them (0 keeps the plain concatenation)
+    #[arg(long = "chunk-crossfade-ms", value_name = "MS", default_value_t = 0)]
+    chunk_crossfade_ms: u32,
+
+    /// Trim each chunk's natural edge silence when joining them and insert a
+    /// uniform pause chosen by its final punctuation, for even pacing
+    #[arg(long = "smart-gaps", default_value_t = false)]
+    smart_gaps: bool,
+
+    /// Use the same style vector for every chunk instead of one picked by the
+    /// chunk's length, so the timbre doesn't drift between joined chunks.
+    /// Defaults to true in text and file mode, which join the chunks of each
+    /// input into one file, and to false otherwise
+    #[arg(
+        long = "consistent-voice",
+        value_name = "BOOL",
+        action = clap::ArgAction::Set
+    )]
+    consistent_voice: Option<bool>,
+
+    /// End sentences that lack terminal punctuation with a period, which
+    /// keeps fragments like UI strings from being clipped. Existing `?` and
+    /// `!` are kept either way
+    #[arg(
+        long = "auto-punctuate",
+        value_name = "BOOL",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    auto_punctuate: bool,
+
+    /// Apply a soft (tanh) limiter instead of hard clipping loud output
+    #[arg(long = "limiter", default_value_t = false)]
+    limiter: bool,
+
+    /// Level above which the limiter starts to saturate, as a fraction of full scale
+    #[arg(
+        long = "limiter-threshold",
+        value_name = "LEVEL",
+        default_value_t = DEFAULT_LIMITER_THRESHOLD,
+        requires = "limiter"
+    )]
+    limiter_threshold: f32,
+
+    /// Turn down harsh sibilance of some voices and blends: the band above
+    /// --de-ess-freq is compressed while it is louder than
+    /// --de-ess-threshold-db. Runs before --limiter
+    #[arg(long = "de-ess", default_value_t = false)]
+    de_ess: bool,
+
+    /// Lower edge of the band the de-esser works on, in Hz
+    #[arg(
+        long = "de-ess-freq",
+        value_name = "HZ",
+        default_value_t = DeEsser::default().freq_hz,
+        requires = "de_ess"
+    )]
+    de_ess_freq: f32,
+
+    /// Level of the sibilance band above which the de-esser turns it down, in dBFS
+    #[arg(
+        long = "de-ess-threshold-db",
+        value_name = "DB",
+        default_value_t = DeEsser::default().threshold_db,
+        allow_negative_numbers = true,
+        requires = "de_ess"
+    )]
+    de_ess_threshold_db: f32,
+
+    /// Stop synthesizing once the output reaches this many seconds, cutting it
+    /// there with a short fade-out. Applies to every mode, including the server
+    #[arg(long = "max-duration-sec", value_name = "SECONDS", value_parser = parse_max_duration)]
+    max_duration_sec: Option<f32>,
+
+    /// Keep every synthesized chunk under about this many seconds of audio
+    /// (estimated from its phonemes), besides the token limit, so streamed
+    /// chunks arrive at a steadier pace
+    #[arg(long = "max-chunk-seconds", value_name = "SECONDS", value_parser = parse_max_duration)]
+    max_chunk_seconds: Option<f32>,
+
+    /// Write text and file mode output to disk chunk by chunk instead of
+    /// holding the whole audio in memory, for hours-long inputs on small
+    /// machines. Can't be combined with --timestamps
+    #[arg(long = "low-memory", default_value_t = false)]
+    low_memory: bool,
+
+    /// Split text and file mode output over files of at most this many
+    /// megabytes (1 MB = 1,000,000 bytes), named `out_part1.wav`,
+    /// `out_part2.wav` and so on, for platforms with file size limits. New
+    /// parts start between chunks where possible. Writes chunk by chunk like
+    /// --low-memory
+    #[arg(
+        long = "split-size-mb",
+        value_name = "MB",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    split_size_mb: Option<u64>,
+
+    /// Mix this WAV file under the speech in text and file mode, looped or cut
+    /// to the speech length, e.g. a music bed for a podcast intro
+    #[arg(long = "bg-audio", value_name = "PATH")]
+    bg_audio: Option<String>,
+
+    /// Gain of the --bg-audio track in dB
+    #[arg(
+        long = "bg-gain-db",
+        value_name = "DB",
+        default_value_t = -18.0,
+        allow_negative_numbers = true,
+        requires = "bg_audio"
+    )]
+    bg_gain_db: f32,
+
+    /// Resampler for sample rate conversion (the --bg-audio track and server
+    /// `sample_rate` responses): `fast` interpolates linearly, `medium` and
+    /// `high` use a windowed sinc with more taps for cleaner audio at more CPU
+    #[arg(
+        long = "resample-quality",
+        value_name = "QUALITY",
+        default_value = "high"
+    )]
+    resample_quality: ResampleQuality,
+
+    /// What to do when the model outputs NaN/Inf samples: zero (replace with
+    /// silence), retry (run the chunk once more) or error
+    #[arg(long = "on-nan", value_name = "POLICY", default_value = "zero")]
+    on_nan: NanPolicy,
+
+    /// What to do with phonemes missing from the model vocab: skip them,
+    /// map them to the nearest known phoneme, or error. Unmapped phonemes are
+    /// logged. Defaults to skip, or to error with --vocab-file
+    #[arg(long = "on-unknown-phoneme", value_name = "POLICY")]
+    on_unknown_phoneme: Option<UnknownPhonemePolicy>,
+
+    /// JSON phoneme-to-id vocab overriding the built-in one, for converted
+    /// Kokoro variants (a `{"phoneme": id}` object or a config.json with `vocab`)
+    #[arg(long = "vocab-file", value_name = "PATH")]
+    vocab_file: Option<String>,
+
+    /// TSV of `word<TAB>pronunciation` entries replacing the eSpeak
+    /// pronunciation of matching words and phrases (case-insensitive, whole
+    /// word). Pronunciations in slashes are IPA, others are respellings
+    #[arg(long = "lexicon", value_name = "PATH")]
+    lexicon: Option<String>,
+
+    /// Output audio in mono (as opposed to stereo), same as --channels 1
+    #[arg(long = "mono", default_value_t = false, conflicts_with = "channels")]
     mono: bool,
 
+    /// Number of output channels, each a copy of the mono signal, e.g. 4 for a
+    /// four-speaker installation. Defaults to 2 (stereo)
+    #[arg(long = "channels", value_name = "N", value_parser = parse_channels)]
+    channels: Option<u16>,
+
+    /// Average all channels into one when writing WAV files, for a mono
+    /// deliverable. Unlike --mono, which synthesizes a single channel, this
+    /// applies to the final channel layout at write time
+    #[arg(long = "downmix", default_value_t = false)]
+    downmix: bool,
+
+    /// How the left and right channels are made from the mono signal: `dup`
+    /// copies it, `pan` places it with --pan, `decorrelate` delays the right
+    /// channel by 1 ms for a wider image
+    #[arg(long = "stereo-mode", value_name = "MODE", default_value = "dup")]
+    stereo_mode: StereoMode,
+
+    /// Position for --stereo-mode pan, from -1.0 (left) to 1.0 (right)
+    #[arg(long = "pan", value_name = "POS", default_value_t = 0.0, value_parser = parse_pan)]
+    pan: f32,
+
+    /// Pad the WAV `data` chunk with silence to a multiple of N bytes, for
+    /// players that require block-aligned data. N must be a multiple of the
+    /// frame size (channels x bytes per sample, e.g. 8 for 32-bit stereo)
+    #[arg(long = "wav-align", value_name = "N")]
+    wav_align: Option<u32>,
+
+    /// Write Broadcast Wave (BWF) files with a `bext` chunk carrying the
+    /// origination date and time and --bwf-description
+    #[arg(long = "bwf", default_value_t = false)]
+    bwf: bool,
+
+    /// Description stored in the BWF `bext` chunk (up to 256 ASCII characters)
+    #[arg(
+        long = "bwf-description",
+        value_name = "TEXT",
+        default_value = "",
+        requires = "bwf"
+    )]
+    bwf_description: String,
+
+    /// Bits per WAV sample: 16 or 24 for integer PCM, 32 for float
+    #[arg(long = "wav-bits", value_name = "BITS", default_value_t = 32, value_parser = parse_wav_bits)]
+    wav_bits: u16,
+
     /// Initial silence duration in tokens
     #[arg(long = "initial-silence", value_name = "INITIAL_SILENCE")]
     initial_silence: Option<usize>,
@@ -148,12 +764,357 @@ struct Cli {
     #[arg(long = "timestamps", default_value_t = false, global = true)]
     timestamps: bool,
 
+    /// Timing detail of --timestamps and --alignment-out: `word` times words
+    /// only, `phoneme` also their phonemes, `frame` rounds all timings to
+    /// the model's 25 ms frames
+    #[arg(
+        long = "timestamp-granularity",
+        value_name = "GRANULARITY",
+        default_value = "phoneme",
+        global = true
+    )]
+    timestamp_granularity: TimestampGranularity,
+
+    /// Print the peak level, RMS level and number of clipped samples of each
+    /// output file, to spot clipped or silent audio
+    #[arg(long = "stats", default_value_t = false, global = true)]
+    stats: bool,
+
+    /// Also encode each output into these formats (comma-separated, mp3 or
+    /// pcm), written next to the WAV file with the format's extension, from
+    /// the same synthesis
+    #[arg(
+        long = "also",
+        value_name = "FORMATS",
+        value_delimiter = ',',
+        global = true
+    )]
+    also: Vec<ExtraFormat>,
+
+    /// Speak the voice name before the text of each output, in that voice,
+    /// e.g. for labelling clips of blind listening tests
+    #[arg(long = "announce-voice", default_value_t = false, global = true)]
+    announce_voice: bool,
+
+    /// Create missing parent directories of output paths
+    #[arg(
+        long = "mkdir",
+        value_name = "BOOL",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    mkdir: bool,
+
+    /// Only print errors and requested output: no progress bar, status
+    /// messages or logs below the error level
+    #[arg(long = "quiet", short = 'q', default_value_t = false, global = true)]
+    quiet: bool,
+
+    /// Log more detail: -v for debug logs of Kokoros, -vv for trace logs and
+    /// debug logs of dependencies. Overrides RUST_LOG
+    #[arg(
+        long = "verbose",
+        short = 'v',
+        action = clap::ArgAction::Count,
+        global = true,
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Phonemize and validate the input, estimate the audio duration and print
+    /// what would be produced, without running inference or writing files
+    #[arg(long = "dry-run", default_value_t = false)]
+    dry_run: bool,
+
+    /// Print the phonemes of the input and exit, loading only the phonemizer
+    /// (no model or voices), for quickly iterating on pronunciations with
+    /// --lexicon or --espeak-variant
+    #[arg(
+        long = "phonemes-only",
+        default_value_t = false,
+        conflicts_with = "dry_run"
+    )]
+    phonemes_only: bool,
+
+    /// Synthesize text and file jobs but discard the audio instead of
+    /// encoding and writing it, and print the synthesis time of each job, to
+    /// profile inference alone
+    #[arg(
+        long = "no-write",
+        default_value_t = false,
+        conflicts_with_all = ["dry_run", "phonemes_only"]
+    )]
+    no_write: bool,
+
     /// Number of TTS instances for parallel processing
     #[arg(long = "instances", value_name = "INSTANCES", default_value_t = 2)]
     instances: usize,
 
+    /// Encoding of input text files: auto (UTF-8, falling back to Latin-1),
+    /// utf-8 (strict, reports the offending byte offset) or latin-1
+    #[arg(
+        long = "input-encoding",
+        value_name = "ENCODING",
+        default_value = "auto"
+    )]
+    input_encoding: TextEncoding,
+
+    /// In file mode, write a JSON manifest with the output path, duration,
+    /// voice and error (if any) of every line; failed lines no longer stop the run
+    #[arg(long = "manifest", value_name = "PATH")]
+    manifest: Option<String>,
+
+    /// Remove markdown markup (emphasis, headings, links, lists, tables) from
+    /// the input before synthesis; fenced code blocks are skipped
+    #[arg(long = "strip-markdown", default_value_t = false)]
+    strip_markdown: bool,
+
+    /// With --strip-markdown, read the contents of fenced code blocks instead of skipping them
+    #[arg(
+        long = "keep-code-blocks",
+        default_value_t = false,
+        requires = "strip_markdown"
+    )]
+    keep_code_blocks: bool,
+
+    /// How to handle emoji and symbols such as ✓, → and ©: `speak` replaces
+    /// them with their names, `strip` removes them, `keep` leaves them as is
+    #[arg(long = "emoji", value_name = "POLICY", default_value = "keep")]
+    emoji: EmojiPolicy,
+
+    /// How to read numbers: `grouped` as numbers, `individual` digit by
+    /// digit, `auto` digit by digit only for phone numbers and codes such as
+    /// 5551234, 555-1234 or 007
+    #[arg(long = "digits", value_name = "MODE", default_value = "grouped")]
+    digits: DigitReading,
+
+    /// Read the input character by character, with short gaps between
+    /// letters and digits, e.g. for spelling names or confirmation codes
+    #[arg(long = "spell", default_value_t = false)]
+    spell: bool,
+
+    /// Print the eSpeak language codes accepted by --lan and exit
+    #[arg(long = "list-languages", default_value_t = false)]
+    list_languages: bool,
+
+    /// Print the voices in the --data file with the indices --style accepts
+    /// in place of names, and exit
+    #[arg(long = "list-voices", default_value_t = false)]
+    list_voices: bool,
+
+    /// Run a text job described by JSON (or `@file.json`) instead of a mode:
+    /// `{"input": "...", "voice": "af_sky", "speed": 1.0, "lang_code": "en-us",
+    /// "initial_silence": 0, "output": "tmp/output.wav", "mono": false,
+    /// "low_memory": false}`. Only `input` is required; the rest default to
+    /// the matching options, and unknown fields are an error
+    #[arg(long = "json-request", value_name = "JSON")]
+    json_request: Option<String>,
+
+    /// Read the text on the system clipboard aloud. On its own it plays the
+    /// audio when built with the `playback` feature, and otherwise writes it
+    /// like `text`; with `text` it takes the place of TEXT, e.g. `--clipboard
+    /// text -o clip.wav`. Needs the `clipboard` feature
+    #[arg(
+        long = "clipboard",
+        default_value_t = false,
+        conflicts_with = "json_request"
+    )]
+    clipboard: bool,
+
+    /// Apply the options of this named preset from --presets-file. Options
+    /// given on the command line override the preset's
+    #[arg(long = "preset", value_name = "NAME")]
+    preset: Option<String>,
+
+    /// JSON file of named presets, each mapping long option names to values:
+    /// `{"audiobook": {"style": "bf_emma", "speed": 0.9, "lan": "en-gb", "limiter": true}}`.
+    /// Defaults to ~/.config/kokoros/presets.json
+    #[arg(long = "presets-file", value_name = "PATH", env = "KOKOROS_PRESETS")]
+    presets_file: Option<String>,
+
+    /// Print the settings in effect after defaults, environment variables and
+    /// presets are applied, with where each value came from, as JSON and exit
+    #[arg(long = "print-config", default_value_t = false)]
+    print_config: bool,
+
+    /// Required unless --list-languages, --list-voices or --json-request is given
     #[command(subcommand)]
-    mode: Mode,
+    mode: Option<Mode>,
+}
+
+/// Clean-up passes applied to input text before synthesis
+#[derive(Debug, Clone, Copy)]
+struct Preprocess {
+    strip_markdown: bool,
+    keep_code_blocks: bool,
+    emoji: EmojiPolicy,
+    digits: DigitReading,
+    spell: bool,
+}
+
+impl Preprocess {
+    fn apply(&self, text: &str) -> String {
+        let text = if self.strip_markdown {
+            strip_markdown(text, self.keep_code_blocks)
+        } else {
+            text.to_string()
+        };
+        let text = normalize_symbols(&text, self.emoji, DEFAULT_SYMBOL_TABLE);
+        let text = normalize_digits(&text, self.digits);
+        if self.spell { spell_out(&text) } else { text }
+    }
+}
+
+/// Build a style string from `--voice`/`--weight` pairs, using the literal
+/// `name:weight` blend syntax so weights need no encoding
+fn style_from_voice_weights(voices: &[String], weights: &[f32]) -> Result<String, String> {
+    let weights: Vec<f32> = if weights.is_empty() {
+        vec![1.0 / voices.len() as f32; voices.len()]
+    } else if weights.len() == voices.len() {
+        weights.to_vec()
+    } else {
+        return Err(format!(
+            "got {} --voice but {} --weight arguments, pass one weight per voice",
+            voices.len(),
+            weights.len()
+        ));
+    };
+
+    if let Some(bad) = voices.iter().find(|v| v.contains(['+', '.', ':'])) {
+        return Err(format!("--voice takes a single voice name, got '{}'", bad));
+    }
+
+    Ok(voices
+        .iter()
+        .zip(&weights)
+        .map(|(voice, weight)| format!("{}:{}", voice, weight))
+        .collect::<Vec<_>>()
+        .join("+"))
+}
+
+/// Accepted range of `--speed`, as a multiplier
+const SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.1..=5.0;
+
+fn parse_channels(s: &str) -> Result<u16, String> {
+    match s.parse::<u16>() {
+        Ok(channels) if (1..=MAX_CHANNELS).contains(&channels) => Ok(channels),
+        _ => Err(format!(
+            "invalid channel count '{}', expected 1 to {}",
+            s, MAX_CHANNELS
+        )),
+    }
+}
+
+fn parse_emphasis_marker(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() {
+        Err("the emphasis marker cannot be empty".to_string())
+    } else {
+        Ok(s.to_string())
+    }
+}
+
+fn parse_expressiveness(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(level) if (0.0..=1.0).contains(&level) => Ok(level),
+        _ => Err(format!(
+            "invalid expressiveness '{}', expected 0.0 to 1.0",
+            s
+        )),
+    }
+}
+
+fn parse_pan(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(pan) if (-1.0..=1.0).contains(&pan) => Ok(pan),
+        _ => Err(format!("invalid pan '{}', expected -1.0 to 1.0", s)),
+    }
+}
+
+fn parse_espeak_variant(s: &str) -> Result<String, String> {
+    // Accept the `+f3` spelling of eSpeak voice names as well
+    let variant = s.trim().trim_start_matches('+');
+    if !variant.is_empty()
+        && variant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(variant.to_string())
+    } else {
+        Err(format!(
+            "invalid eSpeak variant '{}', expected a name such as f3, m2 or whisper",
+            s
+        ))
+    }
+}
+
+fn parse_sha256(s: &str) -> Result<String, String> {
+    let hash = s.trim().to_lowercase();
+    if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(hash)
+    } else {
+        Err(format!(
+            "invalid sha256 '{}', expected 64 hexadecimal characters",
+            s
+        ))
+    }
+}
+
+fn parse_max_duration(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+        _ => Err(format!(
+            "invalid duration '{}', expected a positive number of seconds",
+            s
+        )),
+    }
+}
+
+fn parse_wav_bits(s: &str) -> Result<u16, String> {
+    match s.parse::<u16>() {
+        Ok(bits) if SUPPORTED_BITS_PER_SAMPLE.contains(&bits) => Ok(bits),
+        _ => Err(format!(
+            "unsupported WAV bit depth '{}', expected one of {:?}",
+            s, SUPPORTED_BITS_PER_SAMPLE
+        )),
+    }
+}
+
+/// Parse `--speed` given as a multiplier (`1.2`) or a percentage (`120%`)
+fn parse_speed(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let speed = match s.strip_suffix('%') {
+        Some(percent) => {
+            percent
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("invalid speed percentage '{}'", s))?
+                / 100.0
+        }
+        None => s
+            .parse::<f32>()
+            .map_err(|_| format!("invalid speed '{}', expected e.g. 1.2 or 120%", s))?,
+    };
+    if !SPEED_RANGE.contains(&speed) {
+        return Err(format!(
+            "speed {} is out of range ({} to {}, i.e. {}% to {}%)",
+            speed,
+            SPEED_RANGE.start(),
+            SPEED_RANGE.end(),
+            SPEED_RANGE.start() * 100.0,
+            SPEED_RANGE.end() * 100.0
+        ));
+    }
+    Ok(speed)
+}
+
+/// Parse a `name=path` entry of `--models`
+fn parse_named_model(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.trim().is_empty() && !path.trim().is_empty() => {
+            Ok((name.trim().to_string(), path.trim().to_string()))
+        }
+        _ => Err(format!("expected name=path, got '{}'", s)),
+    }
 }
 
 fn derive_tsv_path_from_wav(path: &str) -> String {
@@ -171,64 +1132,127 @@ fn derive_tsv_path_from_wav(path: &str) -> String {
     format!("{path}.tsv")
 }
 
-fn write_wav_file(
-    path: &str,
-    samples: &[f32],
-    sample_rate: u32,
-    mono: bool,
-) -> std::io::Result<()> {
-    use std::fs::File;
-    use std::io::Write;
-
-    let channels: u16 = if mono { 1 } else { 2 };
-    let bits_per_sample: u16 = 32; // f32
-    let bytes_per_sample: u32 = (bits_per_sample as u32) / 8;
-    let block_align: u16 = channels * bits_per_sample / 8;
-    let byte_rate: u32 = sample_rate * (block_align as u32);
-
-    // Data size in bytes
-    let num_frames: usize = samples.len();
-    let total_samples_to_write: usize = if mono { num_frames } else { num_frames * 2 };
-    let data_size: u32 = (total_samples_to_write as u32) * bytes_per_sample;
-    let riff_chunk_size: u32 = 36 + data_size; // 4 + (8+16) + (8+data)
-
-    let mut f = File::create(path)?;
-
-    // RIFF header
-    f.write_all(b"RIFF")?;
-    f.write_all(&riff_chunk_size.to_le_bytes())?;
-    f.write_all(b"WAVE")?;
-
-    // fmt chunk
-    f.write_all(b"fmt ")?;
-    f.write_all(&(16u32).to_le_bytes())?; // PCM fmt chunk size
-    f.write_all(&(3u16).to_le_bytes())?; // IEEE float = 3
-    f.write_all(&channels.to_le_bytes())?;
-    f.write_all(&sample_rate.to_le_bytes())?;
-    f.write_all(&byte_rate.to_le_bytes())?;
-    f.write_all(&block_align.to_le_bytes())?;
-    f.write_all(&bits_per_sample.to_le_bytes())?;
-
-    // data chunk
-    f.write_all(b"data")?;
-    f.write_all(&data_size.to_le_bytes())?;
-
-    // write samples
-    if mono {
-        for &s in samples {
-            f.write_all(&s.to_le_bytes())?;
+/// Make sure `path` is a named pipe, since reopening a regular file on EOF
+/// would replay it forever
+fn check_fifo(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        let metadata =
+            std::fs::metadata(path).map_err(|e| format!("failed to open FIFO {}: {}", path, e))?;
+        if metadata.file_type().is_fifo() {
+            Ok(())
+        } else {
+            Err(format!("{} is not a FIFO, create one with `mkfifo {}`", path, path).into())
         }
-    } else {
-        for &s in samples {
-            f.write_all(&s.to_le_bytes())?; // left
-            f.write_all(&s.to_le_bytes())?; // right (duplicate for simple stereo)
+    }
+    #[cfg(not(unix))]
+    {
+        Err(format!("--input-fifo is only supported on Unix ({})", path).into())
+    }
+}
+
+/// Resolve an `--output` path. A directory (an existing one, or any path with
+/// a trailing slash) gets `file_name` inside it; anything else that exists
+/// must be a writable file.
+fn resolve_output_path(path: &str, file_name: &str) -> Result<String, String> {
+    let as_path = Path::new(path);
+    if as_path.is_dir() || path.ends_with('/') || path.ends_with(std::path::MAIN_SEPARATOR) {
+        return Ok(as_path.join(file_name).to_string_lossy().into_owned());
+    }
+    match fs::metadata(as_path) {
+        Ok(metadata) if !metadata.is_file() => Err(format!(
+            "output path {} is neither a file nor a directory",
+            path
+        )),
+        Ok(metadata) if metadata.permissions().readonly() => {
+            Err(format!("output file {} is not writable", path))
         }
+        _ => Ok(path.to_string()),
     }
+}
 
-    Ok(())
+/// Output path of input line `line` (0-based) in file mode: `{line}` in any
+/// component of `format` is replaced by the line number, and the components
+/// are joined with the platform's separator
+fn line_output_path(format: &str, line: usize) -> String {
+    Path::new(format)
+        .components()
+        .map(|c| {
+            c.as_os_str()
+                .to_string_lossy()
+                .replace("{line}", &line.to_string())
+        })
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .into_owned()
 }
 
-fn write_tsv(path: &str, alignments: &[(String, f32, f32)]) -> std::io::Result<()> {
+/// Make sure the directory `path` will be written into exists, creating it
+/// when `--mkdir` is on
+fn prepare_output_path(path: &str, mkdir: bool) -> std::io::Result<()> {
+    let Some(parent) = Path::new(path).parent() else {
+        return Ok(());
+    };
+    if parent.as_os_str().is_empty() || parent.is_dir() {
+        return Ok(());
+    }
+    if mkdir {
+        fs::create_dir_all(parent)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "output directory {} does not exist (pass --mkdir true to create it)",
+                parent.display()
+            ),
+        ))
+    }
+}
+
+/// Resolve the language and style to use for `text`, honouring `--auto-lang`
+fn resolve_language(text: &str, lan: &str, style: &str, auto_lang: bool) -> (String, String) {
+    if !auto_lang {
+        return (lan.to_string(), style.to_string());
+    }
+
+    let lan = match detect_language(text) {
+        Some(detected) if detected.confidence >= MIN_CONFIDENCE => {
+            tracing::info!(
+                "Detected language '{}' (confidence {:.2})",
+                detected.espeak_code,
+                detected.confidence
+            );
+            detected.espeak_code
+        }
+        detected => {
+            tracing::warn!(
+                "Could not confidently detect the input language (confidence {:.2}), falling back to {}",
+                detected.map(|d| d.confidence).unwrap_or(0.0),
+                FALLBACK_LANGUAGE
+            );
+            FALLBACK_LANGUAGE
+        }
+    };
+
+    (lan.to_string(), style_for_language(style, lan))
+}
+
+/// `text` preceded by the spoken name of `style` for `--announce-voice`.
+/// Underscores are read as spaces and the voices of a blend are listed, e.g.
+/// "af sky and af nicole. Hello"
+fn announce_voice_text(text: &str, style: &str) -> String {
+    let names: Vec<String> = match parse_style(style) {
+        Ok(parts) => parts
+            .into_iter()
+            .map(|(name, _)| name.replace('_', " "))
+            .collect(),
+        Err(_) => vec![style.replace('_', " ")],
+    };
+    format!("{}. {}", names.join(" and "), text)
+}
+
+fn write_tsv(path: &str, alignments: &[(String, f32, f32)]) -> std::io::Result<()> {
     use std::fs::File;
     use std::io::Write;
     let mut f = File::create(path)?;
@@ -241,143 +1265,1569 @@ fn write_tsv(path: &str, alignments: &[(String, f32, f32)]) -> std::io::Result<(
     Ok(())
 }
 
+/// What `synthesize_to_file` wrote, for printing
+fn saved_message(
+    save_path: &str,
+    wav_paths: &[String],
+    timestamps: bool,
+    also: &[ExtraFormat],
+) -> String {
+    let message: Vec<String> = wav_paths
+        .iter()
+        .map(|path| format!("Audio saved to {}", path))
+        .collect();
+    let mut message = message.join("\n");
+    for &format in also {
+        message.push_str(&format!(
+            "\nAudio saved to {}",
+            sibling_path(save_path, format).display()
+        ));
+    }
+    if timestamps {
+        message.push_str(&format!(
+            "\nTimestamps saved to {}",
+            derive_tsv_path_from_wav(save_path)
+        ));
+    }
+    message
+}
+
+/// Progress of file mode on stderr, hidden when stderr is not a terminal or with --quiet
+fn file_progress_bar(lines: usize, quiet: bool) -> ProgressBar {
+    if quiet || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(lines as u64);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{bar:40} {pos}/{len} lines ({percent}%) elapsed {elapsed_precise}, ETA {eta}",
+        )
+        .expect("valid progress template"),
+    );
+    bar
+}
+
+/// Synthesize `text` into a WAV file at `save_path`, mixed over `background`
+/// if given, plus a TSV sidecar with word timestamps when `timestamps` is set
+/// and word and phoneme timings at `alignment_out` when given. The audio is
+/// also encoded into the `also` formats next to the WAV file. Returns the
+/// audio duration in seconds, the level statistics of the audio and the WAV
+/// files written, several with `--split-size-mb`. Reporting the written
+/// paths is left to the caller, see `saved_message`.
+fn synthesize_to_file(
+    tts: &TTSKoko,
+    text: &str,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    initial_silence: Option<usize>,
+    save_path: &str,
+    wav_options: &WavOptions,
+    background: Option<&Background>,
+    timestamps: bool,
+    alignment_out: Option<&str>,
+    granularity: TimestampGranularity,
+    low_memory: bool,
+    also: &[ExtraFormat],
+) -> Result<(f32, AudioStats, Vec<String>), Box<dyn std::error::Error>> {
+    let sample_rate = tts.sample_rate();
+    if low_memory || wav_options.split_bytes.is_some() {
+        return synthesize_streaming_to_file(
+            tts,
+            text,
+            lan,
+            style,
+            speed,
+            initial_silence,
+            save_path,
+            wav_options,
+            background,
+        );
+    }
+
+    let (audio, words) = synthesize_clip(
+        tts,
+        text,
+        lan,
+        style,
+        speed,
+        initial_silence,
+        timestamps || alignment_out.is_some(),
+        alignment_out.is_some(),
+        granularity,
+    )?;
+    write_clip(
+        save_path,
+        audio,
+        &words,
+        sample_rate,
+        wav_options,
+        background,
+        timestamps,
+        alignment_out,
+        also,
+    )
+}
+
+/// Synthesize `text` into memory, with the word timings when `timed` is set
+/// (phoneme timings too with `phonemes` set)
+fn synthesize_clip(
+    tts: &TTSKoko,
+    text: &str,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    initial_silence: Option<usize>,
+    timed: bool,
+    phonemes: bool,
+    granularity: TimestampGranularity,
+) -> Result<(Vec<f32>, Vec<WordAlignment>), Box<dyn std::error::Error>> {
+    if timed {
+        // The TSV has no phoneme column, so phoneme timings would go unused
+        let granularity = match granularity {
+            TimestampGranularity::Phoneme if !phonemes => TimestampGranularity::Word,
+            other => other,
+        };
+        let Some((audio, words)) = tts.tts_timestamped_raw_audio(
+            text,
+            lan,
+            style,
+            speed,
+            initial_silence,
+            None,
+            None,
+            None,
+            granularity,
+        )?
+        else {
+            return Err("no audio produced".into());
+        };
+        Ok((audio, words))
+    } else {
+        let audio =
+            tts.tts_raw_audio(text, lan, style, speed, initial_silence, None, None, None)?;
+        Ok((audio, Vec::new()))
+    }
+}
+
+/// Mix `background` into the synthesized `audio` and write it to `save_path`
+/// with the sidecar files asked for, returning the speech duration, the
+/// level stats and the paths written
+fn write_clip(
+    save_path: &str,
+    mut audio: Vec<f32>,
+    words: &[WordAlignment],
+    sample_rate: u32,
+    wav_options: &WavOptions,
+    background: Option<&Background>,
+    timestamps: bool,
+    alignment_out: Option<&str>,
+    also: &[ExtraFormat],
+) -> Result<(f32, AudioStats, Vec<String>), Box<dyn std::error::Error>> {
+    let duration = audio.len() as f32 / sample_rate as f32;
+
+    if let Some(background) = background {
+        background.mix(&mut audio, 0);
+    }
+    write_wav_file(save_path, &audio, sample_rate, wav_options)?;
+    write_extra_formats(save_path, &audio, sample_rate, also)?;
+    if timestamps {
+        let rows: Vec<(String, f32, f32)> = words
+            .iter()
+            .map(|w| (w.word.clone(), w.start_sec, w.end_sec))
+            .collect();
+        write_tsv(&derive_tsv_path_from_wav(save_path), &rows)?;
+    }
+    if let Some(alignment_path) = alignment_out {
+        write_alignment(alignment_path, words, duration)?;
+    }
+
+    Ok((
+        duration,
+        AudioStats::measure(&audio),
+        vec![save_path.to_string()],
+    ))
+}
+
+/// `synthesize_to_file` for `--low-memory` and `--split-size-mb`: each chunk
+/// is mixed and written as soon as it is synthesized, so memory use doesn't
+/// grow with the text
+fn synthesize_streaming_to_file(
+    tts: &TTSKoko,
+    text: &str,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    initial_silence: Option<usize>,
+    save_path: &str,
+    wav_options: &WavOptions,
+    background: Option<&Background>,
+) -> Result<(f32, AudioStats, Vec<String>), Box<dyn std::error::Error>> {
+    let sample_rate = tts.sample_rate();
+    let mut writer = SplitWavWriter::create(save_path, sample_rate, wav_options)?;
+    let mut stats = AudioStats::default();
+    let mut len = 0;
+
+    tts.tts_raw_audio_streaming(
+        text,
+        lan,
+        style,
+        speed,
+        initial_silence,
+        None,
+        None,
+        None,
+        |mut chunk| {
+            if let Some(background) = background {
+                // Continue the looped track where the previous chunk left it
+                background.mix(&mut chunk, len);
+            }
+            writer.write(&chunk)?;
+            stats = stats.combine(len, AudioStats::measure(&chunk), chunk.len());
+            len += chunk.len();
+            Ok(())
+        },
+    )?;
+    let paths = writer.finish()?;
+
+    Ok((len as f32 / sample_rate as f32, stats, paths))
+}
+
+/// Pause detection settings of `--split-on-silence`
+struct SilenceSplit {
+    /// Linear RMS level at or below which a frame is silent
+    threshold: f32,
+    min_silence_ms: u32,
+}
+
+/// Length of the frames `--split-on-silence` measures, in milliseconds
+const SILENCE_FRAME_MS: u32 = 10;
+
+/// `save_path` with a 1-based segment number before its extension,
+/// e.g. `tmp/output.wav` becomes `tmp/output_001.wav`
+fn segment_path(save_path: &str, segment: usize) -> String {
+    let p = Path::new(save_path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let name = match p.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{stem}_{segment:03}.{extension}"),
+        None => format!("{stem}_{segment:03}"),
+    };
+    p.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// Synthesize `text` and write each segment between pauses to its own WAV
+/// file, see `segment_path`. Returns the written paths with their levels
+fn synthesize_split(
+    tts: &TTSKoko,
+    text: &str,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    initial_silence: Option<usize>,
+    save_path: &str,
+    wav_options: &WavOptions,
+    background: Option<&Background>,
+    split: &SilenceSplit,
+) -> Result<Vec<(String, AudioStats)>, Box<dyn std::error::Error>> {
+    let sample_rate = tts.sample_rate();
+    let mut audio =
+        tts.tts_raw_audio(text, lan, style, speed, initial_silence, None, None, None)?;
+    // Pauses are found on the speech alone, a music bed would hide them
+    let segments = split_on_silence(
+        &audio,
+        split.threshold,
+        ms_to_samples(split.min_silence_ms, sample_rate),
+        ms_to_samples(SILENCE_FRAME_MS, sample_rate),
+    );
+    if segments.is_empty() {
+        return Err("no speech found in the synthesized audio".into());
+    }
+    if let Some(background) = background {
+        background.mix(&mut audio, 0);
+    }
+
+    let mut written = Vec::with_capacity(segments.len());
+    for (i, range) in segments.into_iter().enumerate() {
+        let path = segment_path(save_path, i + 1);
+        let segment = &audio[range];
+        write_wav_file(&path, segment, sample_rate, wav_options)?;
+        written.push((path, AudioStats::measure(segment)));
+    }
+    Ok(written)
+}
+
+/// A background track mixed under text and file mode output, see `--bg-audio`
+struct Background {
+    /// Mono samples at the model's sample rate
+    samples: Vec<f32>,
+    /// Linear gain applied to the track
+    gain: f32,
+    /// `--limiter` threshold, applied after mixing in place of the limiter of
+    /// the synthesis, which can't see the track
+    limiter_threshold: Option<f32>,
+}
+
+impl Background {
+    fn load(
+        path: &str,
+        gain_db: f32,
+        sample_rate: u32,
+        quality: ResampleQuality,
+        limiter_threshold: Option<f32>,
+    ) -> Result<Self, String> {
+        let (samples, rate) = read_wav_mono(path)?;
+        if samples.is_empty() {
+            return Err(format!("background track {} is empty", path));
+        }
+        Ok(Self {
+            samples: resample_with(&samples, rate, sample_rate, quality),
+            gain: db_to_gain(gain_db),
+            limiter_threshold,
+        })
+    }
+
+    /// Mix the track into `speech`, which starts `offset` samples into the
+    /// output, then limit the mix
+    fn mix(&self, speech: &mut [f32], offset: usize) {
+        let track = self
+            .samples
+            .iter()
+            .cycle()
+            .skip(offset % self.samples.len());
+        for (s, b) in speech.iter_mut().zip(track) {
+            *s += b * self.gain;
+        }
+        if let Some(threshold) = self.limiter_threshold {
+            soft_limit(speech, threshold);
+        }
+    }
+}
+
+/// Download `model_path` into the model cache if it is a URL, returning the
+/// local path to load
+async fn fetch_model_if_url(model_path: String) -> Result<String, Box<dyn std::error::Error>> {
+    if !is_url(&model_path) {
+        return Ok(model_path);
+    }
+    fetch_cached_model(&model_path, &default_cache_dir().join("models")).await
+}
+
+/// Phonemes of `probe` in `lan`, with those the vocab of `cfg` has no id
+/// for and how often they occur
+fn phoneme_coverage(
+    cfg: &InitConfig,
+    lan: &str,
+    probe: &str,
+) -> Result<(String, Vec<(char, usize)>), Box<dyn std::error::Error>> {
+    let phonemes = TextPhonemizer::new(cfg)?.phonemize(probe, lan)?;
+    let vocab = cfg.vocab.as_deref().unwrap_or(&*VOCAB);
+    let missing = missing_phonemes(&phonemes, vocab);
+    Ok((phonemes, missing))
+}
+
+/// Load `model_path` in an ORT session and print its inputs and outputs,
+/// then the phonemes eSpeak produces for `probe` in `lan` that the vocab of
+/// `cfg` can't represent
+fn inspect_model(
+    model_path: &str,
+    precision: ModelPrecision,
+    cfg: &InitConfig,
+    lan: &str,
+    probe: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let model = OrtKoko::new_with_precision(model_path.to_string(), precision)?;
+    let Some((inputs, outputs)) = model.io_info() else {
+        return Err(format!("failed to load {}", model_path).into());
+    };
+
+    println!("Model: {}", model_path);
+    println!("Inputs:");
+    for input in &inputs {
+        println!("  {}", input);
+    }
+    println!("Outputs:");
+    for output in &outputs {
+        println!("  {}", output);
+    }
+
+    // eSpeak may be missing on machines only inspecting models, which is no reason to fail
+    let (phonemes, missing) = match phoneme_coverage(cfg, lan, probe) {
+        Ok(coverage) => coverage,
+        Err(e) => {
+            println!("Phoneme coverage ({}): skipped, {}", lan, e);
+            return Ok(());
+        }
+    };
+    println!(
+        "Phoneme coverage ({}, {} phonemes probed):",
+        lan,
+        phonemes.chars().count()
+    );
+    if missing.is_empty() {
+        println!("  every phoneme is in the model vocab");
+    } else {
+        let list: Vec<String> = missing
+            .iter()
+            .map(|(c, n)| format!("'{}' U+{:04X} ({}x)", c, *c as u32, n))
+            .collect();
+        println!("  not in the model vocab: {}", list.join(", "));
+        eprintln!(
+            "Warning: {} phoneme(s) eSpeak produces for {} can't be represented by the model, see --on-unknown-phoneme",
+            missing.len(),
+            lan
+        );
+    }
+    Ok(())
+}
+
+/// Print the voices of `voices_path` with any problems found, failing if there
+/// are any. The expected style width comes from the `style` input of the model
+/// at `model_path`, or Kokoro's 256 when the model is missing.
+fn validate_voices_file(
+    voices_path: &str,
+    model_path: &str,
+    precision: ModelPrecision,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let style_dim = if std::path::Path::new(model_path).exists() {
+        let model = OrtKoko::new_with_precision(model_path.to_string(), precision)?;
+        model
+            .io_info()
+            .and_then(|(inputs, _)| inputs.into_iter().find(|i| i.name == "style"))
+            .and_then(|style| style.shape?.last().copied())
+            .filter(|&dim| dim > 0)
+            .map(|dim| dim as usize)
+            .ok_or_else(|| format!("{} has no fixed-size style input", model_path))?
+    } else {
+        eprintln!(
+            "Model {} not found, assuming {}-wide style vectors",
+            model_path,
+            voices::STYLE_DIM
+        );
+        voices::STYLE_DIM
+    };
+
+    let checks = voices::validate_voices(voices_path, style_dim)?;
+    println!("Voices file: {} ({} voices)", voices_path, checks.len());
+    let mut bad = 0;
+    for check in &checks {
+        if check.problems.is_empty() {
+            println!("  ok    {} {:?}", check.name, check.shape);
+        } else {
+            bad += 1;
+            println!(
+                "  FAIL  {} {:?}: {}",
+                check.name,
+                check.shape,
+                check.problems.join("; ")
+            );
+        }
+    }
+    if bad > 0 {
+        return Err(format!("{} of {} voices have problems", bad, checks.len()).into());
+    }
+    Ok(())
+}
+
+/// The preprocessed inputs of a text or file job with the paths they would be
+/// saved to, for the modes that only inspect the input. `flag` names the
+/// option in the error for other modes
+fn text_jobs(
+    mode: &Mode,
+    input_encoding: TextEncoding,
+    preprocess: Preprocess,
+    flag: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let jobs = match mode {
+        Mode::Text {
+            text,
+            demo,
+            save_path,
+            ..
+        } => vec![(
+            preprocess.apply(&text_mode_input(text, *demo)?),
+            save_path.clone(),
+        )],
+        Mode::File {
+            input_path,
+            save_path_format,
+            ..
+        } => preprocess
+            .apply(&read_text_file(input_path, input_encoding)?)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                (
+                    line.trim().to_string(),
+                    line_output_path(save_path_format, i),
+                )
+            })
+            .collect(),
+        _ => return Err(format!("{} is only supported in text and file modes", flag).into()),
+    };
+    Ok(jobs)
+}
+
+/// Write the phonemes of `text` to `dir`, in a text file at the path of the
+/// WAV file `save_path` under `dir`, so outputs of the same name in different
+/// directories don't collide
+fn dump_phonemes(
+    phonemizer: &TextPhonemizer,
+    dir: &str,
+    save_path: &str,
+    text: &str,
+    lan: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let phonemes = phonemizer
+        .phonemize(text, lan)
+        .map_err(|e| format!("eSpeak failed for language '{}': {}", lan, e))?;
+    let name = Path::new(save_path).with_extension("txt");
+    // Roots and `..` would lead out of `dir`
+    let relative: PathBuf = name
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    let path = Path::new(dir).join(relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(&path, format!("{}\n", phonemes))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Print the phonemes of a text or file job, without loading the ONNX model
+/// or the voices
+fn run_phonemes_only(
+    mode: &Mode,
+    init_config: &InitConfig,
+    lan: &str,
+    style: &str,
+    auto_lang: bool,
+    input_encoding: TextEncoding,
+    preprocess: Preprocess,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jobs = text_jobs(mode, input_encoding, preprocess, "--phonemes-only")?;
+    let phonemizer = TextPhonemizer::new(init_config)?;
+    for (text, _) in &jobs {
+        let (lan, _) = resolve_language(text, lan, style, auto_lang);
+        let phonemes = phonemizer
+            .phonemize(text, &lan)
+            .map_err(|e| format!("eSpeak failed for language '{}': {}", lan, e))?;
+        println!("{}", phonemes);
+    }
+    Ok(())
+}
+
+/// Synthesize the text or file jobs of `mode` without writing the audio,
+/// printing the `RenderStats` of each job and their total
+fn run_no_write(
+    tts: &TTSKoko,
+    mode: &Mode,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    initial_silence: Option<usize>,
+    auto_lang: bool,
+    input_encoding: TextEncoding,
+    preprocess: Preprocess,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jobs = text_jobs(mode, input_encoding, preprocess, "--no-write")?;
+    let mut total = bench::RenderStats::default();
+    for (text, save_path) in &jobs {
+        let (lan, style) = resolve_language(text, lan, style, auto_lang);
+        let start = std::time::Instant::now();
+        let audio =
+            tts.tts_raw_audio(text, &lan, &style, speed, initial_silence, None, None, None)?;
+        let stats = bench::RenderStats::new(audio.len(), tts.sample_rate(), start.elapsed());
+        println!("{}: {}", save_path, stats);
+        total.add(stats);
+    }
+    if jobs.len() > 1 {
+        println!("total ({} jobs): {}", total.utterances, total);
+    }
+    Ok(())
+}
+
+/// Validate the inputs of a text or file job without loading the ONNX model.
+/// Returns an error if any input fails validation.
+fn run_dry_run(
+    mode: &Mode,
+    data_path: &str,
+    init_config: InitConfig,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    auto_lang: bool,
+    input_encoding: TextEncoding,
+    preprocess: Preprocess,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jobs = text_jobs(mode, input_encoding, preprocess, "--dry-run")?;
+
+    if !Path::new(data_path).exists() {
+        return Err(format!("voices data file not found: {}", data_path).into());
+    }
+    let tts = TTSKoko::without_model(data_path, init_config)?;
+
+    let mut failures = 0;
+    let mut total_duration = 0.0;
+    for (text, save_path) in &jobs {
+        let (lan, style) = resolve_language(text, lan, style, auto_lang);
+        match tts.dry_run(text, &lan, &style, speed) {
+            Ok(report) => {
+                total_duration += report.estimated_duration_sec;
+                println!(
+                    "{}: {} chunk(s), ~{:.1}s of audio",
+                    save_path,
+                    report.chunks.len(),
+                    report.estimated_duration_sec
+                );
+                for chunk in &report.chunks {
+                    println!(
+                        "  [{} / {}] {} tokens: {}",
+                        chunk.lan, chunk.style_name, chunk.tokens, chunk.phonemes
+                    );
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("{}: validation failed: {}", save_path, e);
+            }
+        }
+    }
+
+    println!(
+        "Dry run: {} of {} input(s) valid, ~{:.1}s of audio in total",
+        jobs.len() - failures,
+        jobs.len(),
+        total_duration
+    );
+
+    if failures > 0 {
+        return Err(format!("{} input(s) failed validation", failures).into());
+    }
+    Ok(())
+}
+
+/// Log filter for `--quiet` and `--verbose`, falling back to RUST_LOG and
+/// then `info` when neither is given
+fn log_filter(quiet: bool, verbose: u8) -> tracing_subscriber::EnvFilter {
+    use tracing_subscriber::EnvFilter;
+    match (quiet, verbose) {
+        (true, _) => EnvFilter::new("error"),
+        (false, 0) => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        (false, 1) => EnvFilter::new("info,kokoros=debug,koko=debug,kokoros_openai=debug"),
+        (false, _) => EnvFilter::new("debug,kokoros=trace,koko=trace,kokoros_openai=trace"),
+    }
+}
+
+/// Parse the command line, expanding `--preset`. Returns the options, their
+/// matches and the long names of the options the preset filled in.
+fn parse_cli() -> Result<(Cli, clap::ArgMatches, Vec<String>), Box<dyn std::error::Error>> {
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let matches = Cli::command().get_matches_from(&args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let Some(name) = &cli.preset else {
+        return Ok((cli, matches, Vec::new()));
+    };
+
+    let path = cli
+        .presets_file
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(preset::default_presets_path);
+    let presets = preset::load_presets(&path)?;
+    let preset_args = preset::preset_args(&Cli::command(), &matches, &presets, name)?;
+    let from_preset = preset_args
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--"))
+        .map(String::from)
+        .collect();
+
+    // Before the subcommand, where the top-level options go
+    let program = args.len().min(1);
+    let mut expanded = args[..program].to_vec();
+    expanded.extend(preset_args.into_iter().map(Into::into));
+    expanded.extend_from_slice(&args[program..]);
+    let matches = Cli::command().get_matches_from(expanded);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    Ok((cli, matches, from_preset))
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing with Unix timestamp format and environment-based log level
+    let (cli, matches, from_preset) = parse_cli()?;
+    if cli.print_config {
+        let mut config = config::resolved_config(&Cli::command(), &matches, &from_preset);
+        config["runtime"] = serde_json::json!({
+            "execution_provider": execution_provider(),
+            "available_threads": std::thread::available_parallelism().map_or(1, |n| n.get()),
+        });
+        println!("{}", serde_json::to_string_pretty(&config)?);
+        return Ok(());
+    }
+
+    // Initialize tracing with Unix timestamp format, logging to stderr so piped audio stays clean
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
         .with_timer(UnixTimestampFormatter)
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
+        .with_env_filter(log_filter(cli.quiet, cli.verbose))
         .init();
 
     let rt = tokio::runtime::Runtime::new()?;
     rt.block_on(async {
         let Cli {
             lan,
+            espeak_data_path,
+            espeak_variant,
+            auto_lang,
+            per_sentence_lang,
             model_path,
+            model_sha256,
+            model_precision,
+            provider_fallback,
+            arena_mb,
+            memory_pattern,
             data_path,
+            voice_packs,
+            voices_override_order,
             style,
+            voices,
+            weights,
             speed,
+            expressiveness,
+            emphasis_marker,
+            max_internal_pause_ms,
+            duration_profile,
+            chunk_crossfade_ms,
+            smart_gaps,
+            consistent_voice,
+            auto_punctuate,
+            limiter,
+            limiter_threshold,
+            de_ess,
+            de_ess_freq,
+            de_ess_threshold_db,
+            max_duration_sec,
+            max_chunk_seconds,
+            low_memory,
+            split_size_mb,
+            bg_audio,
+            bg_gain_db,
+            resample_quality,
+            on_nan,
+            on_unknown_phoneme,
+            vocab_file,
+            lexicon,
             initial_silence,
             mono,
+            channels,
+            downmix,
+            stereo_mode,
+            pan,
+            wav_align,
+            bwf,
+            bwf_description,
+            wav_bits,
             timestamps,
+            timestamp_granularity,
+            stats,
+            announce_voice,
+            also,
+            mkdir,
+            quiet,
+            verbose: _,
+            dry_run,
+            phonemes_only,
+            no_write,
             instances,
+            input_encoding,
+            strip_markdown,
+            keep_code_blocks,
+            emoji,
+            digits,
+            spell,
+            manifest,
+            list_languages,
+            list_voices,
+            json_request,
+            clipboard,
+            preset: _,
+            presets_file: _,
+            print_config: _,
             mode,
-        } = Cli::parse();
+        } = cli;
 
-        let tts = TTSKoko::new(&model_path, &data_path).await;
+        if list_languages {
+            if let Some(espeak_data_path) = &espeak_data_path {
+                set_espeak_data_path(espeak_data_path)?;
+            }
+            for language in TTSKoko::supported_languages() {
+                println!("{}", language);
+            }
+            return Ok(());
+        }
+        if list_voices {
+            let mut names = TTSKoko::list_voices(&data_path)?;
+            for pack in &voice_packs {
+                names.extend(TTSKoko::list_voices(pack)?);
+            }
+            names.sort();
+            names.dedup();
+            for (i, voice) in names.iter().enumerate() {
+                println!("{:>3} {}", i, voice);
+            }
+            return Ok(());
+        }
+        let (mode, lan, style, speed, initial_silence, mono, low_memory) = match json_request {
+            Some(_) if mode.is_some() => {
+                return Err("--json-request replaces the mode, don't pass both".into());
+            }
+            Some(arg) => {
+                let request = JsonRequest::parse(&arg)?;
+                let mode = default_text_mode(request.input, request.output)?;
+                (
+                    Some(mode),
+                    request.lang_code.unwrap_or(lan),
+                    request.voice.unwrap_or(style),
+                    request.speed.unwrap_or(speed),
+                    request.initial_silence.or(initial_silence),
+                    request.mono.unwrap_or(mono),
+                    request.low_memory.unwrap_or(low_memory),
+                )
+            }
+            None => (mode, lan, style, speed, initial_silence, mono, low_memory),
+        };
+        let clipboard_text = match clipboard.then(clipboard::read_clipboard).transpose()? {
+            Some(None) => {
+                eprintln!("The clipboard holds no text, nothing to read");
+                return Ok(());
+            }
+            text => text.flatten(),
+        };
+        // Clipboard text fills in text mode, or is played when there is no mode
+        #[cfg(feature = "playback")]
+        let mut play_clipboard = None;
+        let mode = match (clipboard_text, mode) {
+            (None, mode) => mode,
+            (Some(text), None) => {
+                #[cfg(feature = "playback")]
+                {
+                    play_clipboard = Some(text.clone());
+                }
+                Some(default_text_mode(text, None)?)
+            }
+            (
+                Some(clip),
+                Some(Mode::Text {
+                    text: None,
+                    demo: false,
+                    save_path,
+                    dump_features,
+                    alignment_out,
+                    split_on_silence,
+                    silence_threshold_db,
+                    min_silence_ms,
+                }),
+            ) => Some(Mode::Text {
+                text: Some(clip),
+                demo: false,
+                save_path,
+                dump_features,
+                alignment_out,
+                split_on_silence,
+                silence_threshold_db,
+                min_silence_ms,
+            }),
+            (Some(_), Some(Mode::Text { .. })) => {
+                return Err("--clipboard replaces TEXT, don't pass TEXT or --demo too".into());
+            }
+            (Some(_), Some(_)) => return Err("--clipboard works with text mode only".into()),
+        };
+        let Some(mode) = mode else {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::MissingSubcommand,
+                    "a mode (e.g. `text` or `file`) is required",
+                )
+                .exit();
+        };
+        if low_memory && timestamps {
+            return Err("--timestamps can't be combined with --low-memory".into());
+        }
+        if max_internal_pause_ms.is_some() && timestamps {
+            return Err("--timestamps can't be combined with --max-internal-pause-ms".into());
+        }
+        if low_memory && !also.is_empty() {
+            return Err("--also can't be combined with --low-memory".into());
+        }
+        // Chunk-by-chunk output has no cross-fades or smart gaps
+        if low_memory && (chunk_crossfade_ms > 0 || smart_gaps) {
+            return Err(
+                "--low-memory can't be combined with --chunk-crossfade-ms or --smart-gaps".into(),
+            );
+        }
+        if split_size_mb.is_some() && (timestamps || !also.is_empty()) {
+            return Err("--split-size-mb can't be combined with --timestamps or --also".into());
+        }
+        // Chunk-by-chunk output has no cross-fades or smart gaps
+        if split_size_mb.is_some() && (chunk_crossfade_ms > 0 || smart_gaps) {
+            return Err(
+                "--split-size-mb can't be combined with --chunk-crossfade-ms or --smart-gaps"
+                    .into(),
+            );
+        }
+        // Output paths naming a directory write default file names inside it
+        let mode = match mode {
+            Mode::Text {
+                text,
+                demo,
+                save_path,
+                dump_features,
+                alignment_out,
+                split_on_silence,
+                silence_threshold_db,
+                min_silence_ms,
+            } => {
+                if let Some(path) = &alignment_out {
+                    AlignmentFormat::from_path(path)?;
+                }
+                if max_internal_pause_ms.is_some() && alignment_out.is_some() {
+                    return Err(
+                        "--alignment-out can't be combined with --max-internal-pause-ms".into(),
+                    );
+                }
+                if split_on_silence && timestamps {
+                    return Err("--timestamps can't be combined with --split-on-silence".into());
+                }
+                if split_on_silence && !also.is_empty() {
+                    return Err("--also can't be combined with --split-on-silence".into());
+                }
+                if low_memory && (split_on_silence || alignment_out.is_some()) {
+                    return Err(
+                        "--low-memory can't be combined with --split-on-silence or --alignment-out"
+                            .into(),
+                    );
+                }
+                if split_size_mb.is_some() && (split_on_silence || alignment_out.is_some()) {
+                    return Err(
+                        "--split-size-mb can't be combined with --split-on-silence or --alignment-out"
+                            .into(),
+                    );
+                }
+                Mode::Text {
+                    text,
+                    demo,
+                    save_path: resolve_output_path(&save_path, "output.wav")?,
+                    dump_features,
+                    alignment_out,
+                    split_on_silence,
+                    silence_threshold_db,
+                    min_silence_ms,
+                }
+            }
+            Mode::File {
+                input_path,
+                save_path_format,
+                dump_phonemes_dir,
+                normalize_batch,
+                normalize_batch_target_db,
+                skip_existing,
+            } => {
+                if normalize_batch && (low_memory || split_size_mb.is_some()) {
+                    return Err(
+                        "--normalize-batch can't be combined with --low-memory or --split-size-mb"
+                            .into(),
+                    );
+                }
+                Mode::File {
+                    input_path,
+                    save_path_format: resolve_output_path(&save_path_format, "output_{line}.wav")?,
+                    dump_phonemes_dir,
+                    normalize_batch,
+                    normalize_batch_target_db,
+                    skip_existing,
+                }
+            }
+            Mode::Stream {
+                input_fifo,
+                output,
+                stream_lookahead,
+            } => {
+                // Lookahead chunks are written as they come, without the
+                // cross-fades and smart gaps of the joined line
+                if stream_lookahead > 0 && (chunk_crossfade_ms > 0 || smart_gaps) {
+                    return Err(
+                        "--stream-lookahead can't be combined with --chunk-crossfade-ms or --smart-gaps"
+                            .into(),
+                    );
+                }
+                Mode::Stream {
+                    input_fifo,
+                    output,
+                    stream_lookahead,
+                }
+            }
+            mode => mode,
+        };
+
+        let wav_options = WavOptions {
+            channels: channels.unwrap_or(if mono { 1 } else { 2 }),
+            stereo_mode,
+            pan,
+            align: wav_align,
+            bits_per_sample: wav_bits,
+            bext: bwf.then(|| Bext::new(&bwf_description)),
+            downmix,
+            split_bytes: split_size_mb.map(|mb| mb.saturating_mul(1_000_000)),
+        };
+        // The background track is only mixed in text and file mode, where the
+        // limiter has to run after the mix instead of on the bare speech
+        let limit_after_mix =
+            bg_audio.is_some() && matches!(mode, Mode::Text { .. } | Mode::File { .. });
+
+        let preprocess = Preprocess {
+            strip_markdown,
+            keep_code_blocks,
+            emoji,
+            digits,
+            spell,
+        };
+
+        let style = if voices.is_empty() {
+            style
+        } else {
+            style_from_voice_weights(&voices, &weights)?
+        };
+
+        let vocab = vocab_file
+            .as_deref()
+            .map(load_vocab_file)
+            .transpose()?
+            .map(Arc::new);
+        // A custom vocab must cover every phoneme, so mismatches fail loudly
+        let on_unknown_phoneme = on_unknown_phoneme.unwrap_or(if vocab.is_some() {
+            UnknownPhonemePolicy::Error
+        } else {
+            UnknownPhonemePolicy::Skip
+        });
+
+        if let Some(missing) = voice_packs.iter().find(|p| !Path::new(p).exists()) {
+            return Err(format!("voices file not found: {}", missing).into());
+        }
+
+        let lexicon = lexicon
+            .as_deref()
+            .map(Lexicon::load)
+            .transpose()?
+            .map(Arc::new);
+
+        let mut init_config = InitConfig {
+            per_sentence_lang,
+            model_precision,
+            provider_fallback,
+            session_memory: SessionMemory {
+                arena_mb,
+                memory_pattern,
+            },
+            duration_profile,
+            chunk_crossfade_ms,
+            smart_gaps,
+            // Defaults on where the chunks of a document are joined into one file
+            consistent_voice: consistent_voice
+                .unwrap_or(matches!(mode, Mode::Text { .. } | Mode::File { .. })),
+            auto_punctuate,
+            voice_packs,
+            voice_packs_override: voices_override_order,
+            expressiveness,
+            emphasis_marker,
+            max_internal_pause_ms,
+            espeak_data_path,
+            espeak_variant,
+            limiter_threshold: limiter
+                .then_some(limiter_threshold)
+                .filter(|_| !limit_after_mix),
+            de_ess: de_ess.then_some(DeEsser {
+                freq_hz: de_ess_freq,
+                threshold_db: de_ess_threshold_db,
+            }),
+            on_nan,
+            on_unknown_phoneme,
+            vocab,
+            lexicon,
+            max_duration_secs: max_duration_sec,
+            max_chunk_secs: max_chunk_seconds,
+            model_sha256,
+            ..InitConfig::default()
+        };
+        if let Mode::Join {
+            inputs,
+            output,
+            gap_ms,
+        } = &mode
+        {
+            let secs = join::join_wavs(inputs, output, *gap_ms)?;
+            eprintln!(
+                "Joined {} files into {} ({:.2}s)",
+                inputs.len(),
+                output,
+                secs
+            );
+            return Ok(());
+        }
+        if let Mode::ValidateVoices { voices } = &mode {
+            let model_path = fetch_model_if_url(model_path).await?;
+            return validate_voices_file(
+                voices.as_deref().unwrap_or(&data_path),
+                &model_path,
+                model_precision,
+            );
+        }
+        if let Mode::InspectModel { model, probe_file } = &mode {
+            let model_path = fetch_model_if_url(model.clone().unwrap_or(model_path)).await?;
+            let probe = match probe_file {
+                Some(path) => read_text_file(path, input_encoding)?,
+                None => kokoros::tts::lang::probe_text(&lan).to_string(),
+            };
+            return inspect_model(&model_path, model_precision, &init_config, &lan, &probe);
+        }
+        // A custom vocab may lack phonemes of --lan, better known before synthesis
+        if init_config.vocab.is_some() {
+            let probe = kokoros::tts::lang::probe_text(&lan);
+            match phoneme_coverage(&init_config, &lan, probe) {
+                Ok((_, missing)) if !missing.is_empty() => {
+                    let chars: String = missing.iter().map(|(c, _)| *c).collect();
+                    tracing::warn!(
+                        "--vocab-file has no ids for phonemes eSpeak produces for {}: {}",
+                        lan,
+                        chars
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if phonemes_only {
+            return run_phonemes_only(
+                &mode,
+                &init_config,
+                &lan,
+                &style,
+                auto_lang,
+                input_encoding,
+                preprocess,
+            );
+        }
+        if dry_run {
+            return run_dry_run(
+                &mode,
+                &data_path,
+                init_config,
+                &lan,
+                &style,
+                speed,
+                auto_lang,
+                input_encoding,
+                preprocess,
+            );
+        }
+
+        let model_path = fetch_model_if_url(model_path).await?;
+        let tts = TTSKoko::from_config(&model_path, &data_path, init_config.clone()).await?;
+        // The checksum is of the default model, which is verified now; server
+        // instances would only hash it again and named models must not be checked
+        init_config.model_sha256 = None;
+        if no_write {
+            return run_no_write(
+                &tts,
+                &mode,
+                &lan,
+                &style,
+                speed,
+                initial_silence,
+                auto_lang,
+                input_encoding,
+                preprocess,
+            );
+        }
+
+        #[cfg(feature = "playback")]
+        if let Some(text) = play_clipboard {
+            let text = preprocess.apply(&text);
+            return repl::play_text(&tts, &text, &lan, &style, speed, tts.sample_rate());
+        }
+
+        let background = bg_audio
+            .as_deref()
+            .map(|path| {
+                Background::load(
+                    path,
+                    bg_gain_db,
+                    tts.sample_rate(),
+                    resample_quality,
+                    limiter.then_some(limiter_threshold),
+                )
+            })
+            .transpose()?;
 
         match mode {
             Mode::File {
                 input_path,
                 save_path_format,
+                dump_phonemes_dir,
+                normalize_batch,
+                normalize_batch_target_db,
+                skip_existing,
             } => {
-                let file_content = fs::read_to_string(input_path)?;
+                let file_content = preprocess.apply(&read_text_file(&input_path, input_encoding)?);
+                let phonemizer = match &dump_phonemes_dir {
+                    Some(dir) => {
+                        std::fs::create_dir_all(dir)
+                            .map_err(|e| format!("failed to create {}: {}", dir, e))?;
+                        Some(TextPhonemizer::new(&init_config)?)
+                    }
+                    None => None,
+                };
+                let mut file_manifest = manifest.as_ref().map(|_| Manifest::default());
+                let total_lines = file_content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .count();
+                let progress = file_progress_bar(total_lines, quiet);
+                // Messages go above the bar while it is shown
+                let note = |msg: String| {
+                    if progress.is_hidden() {
+                        eprintln!("{}", msg);
+                    } else {
+                        progress.println(msg);
+                    }
+                };
+                let mut report = |i: usize,
+                                  save_path: &str,
+                                  style: &str,
+                                  lan: &str,
+                                  result: Result<
+                    (f32, AudioStats, Vec<String>),
+                    Box<dyn std::error::Error>,
+                >|
+                 -> Result<(), Box<dyn std::error::Error>> {
+                    match result {
+                        Ok((duration, audio_stats, wav_paths)) => {
+                            if !quiet {
+                                note(saved_message(save_path, &wav_paths, timestamps, &also));
+                            }
+                            if stats {
+                                note(format!("{}: {}", save_path, audio_stats));
+                            }
+                            if let Some(m) = file_manifest.as_mut() {
+                                m.record_ok(i, save_path, style, lan, duration, &audio_stats);
+                            }
+                        }
+                        Err(e) => {
+                            // Without a manifest, only timestamped runs keep going after a failed line
+                            if file_manifest.is_none() && !timestamps {
+                                progress.abandon();
+                                return Err(e);
+                            }
+                            note(format!("Error processing line {}: {}", i + 1, e));
+                            if let Some(m) = file_manifest.as_mut() {
+                                m.record_error(i, save_path, style, lan, &e.to_string());
+                            }
+                        }
+                    }
+                    Ok(())
+                };
+                // With --normalize-batch, the synthesized lines wait here
+                // until the gain of the whole batch is known
+                let mut pending = Vec::new();
+                // Recorded in the manifest once `report` is done with it
+                let mut skipped = Vec::new();
                 for (i, line) in file_content.lines().enumerate() {
                     let stripped_line = line.trim();
                     if stripped_line.is_empty() {
                         continue;
                     }
 
-                    let save_path = save_path_format.replace("{line}", &i.to_string());
-                    if timestamps {
-                        match tts.tts_timestamped_raw_audio(
-                            stripped_line,
+                    let save_path = line_output_path(&save_path_format, i);
+                    let (lan, style) = resolve_language(stripped_line, &lan, &style, auto_lang);
+                    if skip_existing && has_output(&save_path, &wav_options) {
+                        skipped.push((i, save_path, style, lan));
+                        progress.inc(1);
+                        continue;
+                    }
+                    let line_text = if announce_voice {
+                        announce_voice_text(stripped_line, &style)
+                    } else {
+                        stripped_line.to_string()
+                    };
+                    let prepared = prepare_output_path(&save_path, mkdir)
+                        .map_err(Into::into)
+                        .and_then(|_| match (&phonemizer, &dump_phonemes_dir) {
+                            (Some(phonemizer), Some(dir)) => {
+                                dump_phonemes(phonemizer, dir, &save_path, &line_text, &lan)
+                            }
+                            _ => Ok(()),
+                        });
+                    if normalize_batch {
+                        let clip = prepared.and_then(|_| {
+                            synthesize_clip(
+                                &tts,
+                                &line_text,
+                                &lan,
+                                &style,
+                                speed,
+                                initial_silence,
+                                timestamps,
+                                false,
+                                timestamp_granularity,
+                            )
+                        });
+                        match clip {
+                            Ok((audio, words)) => {
+                                pending.push((i, save_path, style, lan, audio, words))
+                            }
+                            Err(e) => report(i, &save_path, &style, &lan, Err(e))?,
+                        }
+                        progress.inc(1);
+                        continue;
+                    }
+                    let result = prepared.and_then(|_| {
+                        synthesize_to_file(
+                            &tts,
+                            &line_text,
                             &lan,
                             &style,
                             speed,
                             initial_silence,
+                            &save_path,
+                            &wav_options,
+                            background.as_ref(),
+                            timestamps,
                             None,
-                            None,
-                            None,
-                        ) {
-                            Ok(Some((audio, words))) => {
-                                // Write WAV
-                                // Note: current engine uses 24kHz
-                                write_wav_file(&save_path, &audio, 24_000, mono)?;
-
-                                // Write TSV sidecar
-                                let tsv_path = derive_tsv_path_from_wav(&save_path);
-                                let rows: Vec<(String, f32, f32)> = words
-                                    .into_iter()
-                                    .map(|w| (w.word, w.start_sec, w.end_sec))
-                                    .collect();
-                                write_tsv(&tsv_path, &rows)?;
-                                eprintln!("Audio saved to {}", save_path);
-                                eprintln!("Timestamps saved to {}", tsv_path);
-                            }
-                            Ok(None) => {
-                                eprintln!("No audio produced for line {}", i + 1);
-                            }
-                            Err(e) => {
-                                eprintln!("Error processing line {}: {}", i + 1, e);
-                            }
+                            timestamp_granularity,
+                            low_memory,
+                            &also,
+                        )
+                    });
+                    report(i, &save_path, &style, &lan, result)?;
+                    progress.inc(1);
+                }
+
+                if normalize_batch {
+                    let clip_stats: Vec<(AudioStats, usize)> = pending
+                        .iter()
+                        .map(|(_, _, _, _, audio, _)| (AudioStats::measure(audio), audio.len()))
+                        .collect();
+                    let gain = batch_gain(&clip_stats, db_to_gain(normalize_batch_target_db));
+                    if !quiet {
+                        note(format!("Batch gain: {:+.1} dB", 20.0 * gain.log10()));
+                    }
+                    for (i, save_path, style, lan, mut audio, words) in pending {
+                        for sample in &mut audio {
+                            *sample *= gain;
                         }
-                    } else {
-                        tts.tts(TTSOpts {
-                            txt: stripped_line,
-                            lan: &lan,
-                            style_name: &style,
-                            save_path: &save_path,
-                            mono,
-                            speed,
-                            initial_silence,
-                        })?;
+                        let result = write_clip(
+                            &save_path,
+                            audio,
+                            &words,
+                            tts.sample_rate(),
+                            &wav_options,
+                            background.as_ref(),
+                            timestamps,
+                            None,
+                            &also,
+                        );
+                        report(i, &save_path, &style, &lan, result)?;
+                    }
+                }
+                progress.finish_and_clear();
+
+                if skip_existing && !quiet {
+                    eprintln!(
+                        "Skipped {} line(s) whose output already exists",
+                        skipped.len()
+                    );
+                }
+                if let Some(m) = file_manifest.as_mut() {
+                    for (i, save_path, style, lan) in &skipped {
+                        m.record_skipped(*i, save_path, style, lan);
+                    }
+                }
+                if let (Some(m), Some(manifest_path)) = (file_manifest, manifest.as_ref()) {
+                    prepare_output_path(manifest_path, mkdir)?;
+                    m.write(manifest_path)?;
+                    if !quiet {
+                        eprintln!("Manifest saved to {}", manifest_path);
+                    }
+                    if m.failures() > 0 {
+                        return Err(format!(
+                            "{} line(s) failed, see {}",
+                            m.failures(),
+                            manifest_path
+                        )
+                        .into());
                     }
                 }
             }
 
-            Mode::Text { text, save_path } => {
+            Mode::Text {
+                text,
+                demo,
+                save_path,
+                dump_features,
+                alignment_out,
+                split_on_silence,
+                silence_threshold_db,
+                min_silence_ms,
+            } => {
+                let text = preprocess.apply(&text_mode_input(&text, demo)?);
                 let s = std::time::Instant::now();
-                if timestamps {
-                    match tts.tts_timestamped_raw_audio(
+                let (lan, style) = resolve_language(&text, &lan, &style, auto_lang);
+                let text = if announce_voice {
+                    announce_voice_text(&text, &style)
+                } else {
+                    text
+                };
+                prepare_output_path(&save_path, mkdir)?;
+                if let Some(alignment_path) = &alignment_out {
+                    prepare_output_path(alignment_path, mkdir)?;
+                }
+                if split_on_silence {
+                    let split = SilenceSplit {
+                        threshold: db_to_gain(silence_threshold_db),
+                        min_silence_ms,
+                    };
+                    let segments = synthesize_split(
+                        &tts,
                         &text,
                         &lan,
                         &style,
                         speed,
                         initial_silence,
-                        None,
-                        None,
-                        None,
-                    ) {
-                        Ok(Some((audio, words))) => {
-                            write_wav_file(&save_path, &audio, 24_000, mono)?;
-                            let tsv_path = derive_tsv_path_from_wav(&save_path);
-                            let rows: Vec<(String, f32, f32)> = words
-                                .into_iter()
-                                .map(|w| (w.word, w.start_sec, w.end_sec))
-                                .collect();
-                            write_tsv(&tsv_path, &rows)?;
-                            eprintln!("Audio saved to {}", save_path);
-                            eprintln!("Timestamps saved to {}", tsv_path);
+                        &save_path,
+                        &wav_options,
+                        background.as_ref(),
+                        &split,
+                    )?;
+                    for (path, audio_stats) in &segments {
+                        if !quiet {
+                            eprintln!("Audio saved to {}", path);
                         }
-                        Ok(None) => {
-                            eprintln!("No audio produced for input text");
-                        }
-                        Err(e) => {
-                            eprintln!("Error processing input text: {}", e);
+                        if stats {
+                            eprintln!("{}: {}", path, audio_stats);
                         }
                     }
                 } else {
-                    tts.tts(TTSOpts {
-                        txt: &text,
-                        lan: &lan,
-                        style_name: &style,
-                        save_path: &save_path,
-                        mono,
+                    match synthesize_to_file(
+                        &tts,
+                        &text,
+                        &lan,
+                        &style,
                         speed,
                         initial_silence,
-                    })?;
+                        &save_path,
+                        &wav_options,
+                        background.as_ref(),
+                        timestamps,
+                        alignment_out.as_deref(),
+                        timestamp_granularity,
+                        low_memory,
+                        &also,
+                    ) {
+                        Ok((_, audio_stats, wav_paths)) => {
+                            if !quiet {
+                                eprintln!(
+                                    "{}",
+                                    saved_message(&save_path, &wav_paths, timestamps, &also)
+                                );
+                                if let Some(alignment_path) = &alignment_out {
+                                    eprintln!("Alignment saved to {}", alignment_path);
+                                }
+                            }
+                            if stats {
+                                eprintln!("{}: {}", save_path, audio_stats);
+                            }
+                        }
+                        Err(e) if timestamps => eprintln!("Error processing input text: {}", e),
+                        Err(e) => return Err(e),
+                    }
+                }
+                if let Some(features_path) = &dump_features {
+                    let features = tts.tts_features(&text, &lan, &style, speed)?;
+                    prepare_output_path(features_path, mkdir)?;
+                    ndarray_npy::write_npy(features_path, &features)?;
+                    if !quiet {
+                        eprintln!(
+                            "Features {:?} saved to {}",
+                            features.shape(),
+                            features_path
+                        );
+                    }
+                }
+                if !quiet {
+                    println!("Time taken: {:?}", s.elapsed());
+                    let words_per_second =
+                        text.split_whitespace().count() as f32 / s.elapsed().as_secs_f32();
+                    println!("Words per second: {:.2}", words_per_second);
+                }
+            }
+
+            #[cfg(feature = "playback")]
+            Mode::Repl => {
+                repl::run_repl(&tts, lan, style, speed, tts.sample_rate())?;
+            }
+
+            Mode::Bench {
+                text_file,
+                runs,
+                warmup,
+                json,
+            } => {
+                let corpus: Vec<String> = read_text_file(&text_file, input_encoding)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                let opts = bench::BenchOpts {
+                    lan: &lan,
+                    style: &style,
+                    speed,
+                    sample_rate: tts.sample_rate(),
+                    runs,
+                    warmup,
+                    quiet,
+                };
+                let report = bench::run_bench(&tts, &corpus, &opts)?;
+                report.print_table();
+
+                if let Some(json_path) = json {
+                    let json = serde_json::to_string_pretty(&report.to_json())?;
+                    if json_path == "-" {
+                        println!("{}", json);
+                    } else {
+                        prepare_output_path(&json_path, mkdir)?;
+                        fs::write(&json_path, json)?;
+                        eprintln!("Benchmark results saved to {}", json_path);
+                    }
                 }
-                println!("Time taken: {:?}", s.elapsed());
-                let words_per_second =
-                    text.split_whitespace().count() as f32 / s.elapsed().as_secs_f32();
-                println!("Words per second: {:.2}", words_per_second);
             }
 
-            Mode::OpenAI { ip, port } => {
+            Mode::OpenAI {
+                ip,
+                port,
+                max_input_chars,
+                models,
+                cache_dir,
+                cache_max_mb,
+                cache_transcode,
+                stream_resample_quality,
+                max_concurrent,
+                overflow,
+                key_defaults,
+                blend_cache_size,
+            } => {
+                init_config.blend_cache_size = blend_cache_size;
                 // Create multiple independent TTS instances for parallel processing
                 let mut tts_instances = Vec::new();
                 for i in 0..instances {
@@ -387,40 +2837,138 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         i + 1,
                         instances
                     );
-                    let instance = TTSKoko::new(&model_path, &data_path).await;
+                    let instance =
+                        TTSKoko::from_config(&model_path, &data_path, init_config.clone()).await?;
                     tts_instances.push(instance);
                 }
-                let app = kokoros_openai::create_server(tts_instances).await;
+                // Each named model gets the same number of instances as the default one
+                let mut named_models = HashMap::new();
+                for (name, path) in models {
+                    if named_models.contains_key(&name) {
+                        return Err(format!("model '{}' is listed more than once", name).into());
+                    }
+                    let path = fetch_model_if_url(path).await?;
+                    let mut model_instances = Vec::new();
+                    for i in 0..instances {
+                        tracing::info!(
+                            "Initializing TTS instance [{}] for model '{}' ({}/{})",
+                            format!("{:02x}", i),
+                            name,
+                            i + 1,
+                            instances
+                        );
+                        let instance =
+                            TTSKoko::from_config(&path, &data_path, init_config.clone()).await?;
+                        model_instances.push(instance);
+                    }
+                    named_models.insert(name, model_instances);
+                }
+
+                let cache = cache_dir
+                    .map(|dir| {
+                        kokoros_openai::cache::AudioCache::new(&dir, cache_max_mb * 1024 * 1024)
+                            .map_err(|e| format!("failed to create cache directory {}: {}", dir, e))
+                    })
+                    .transpose()?
+                    .map(Arc::new);
+                let key_defaults = match key_defaults {
+                    Some(path) => kokoros_openai::parse_key_defaults(&fs::read_to_string(&path)?)
+                        .map_err(|e| format!("{}: {}", path, e))?,
+                    None => HashMap::new(),
+                };
+                let server_config = kokoros_openai::ServerConfig {
+                    max_input_chars,
+                    cache,
+                    cache_transcode,
+                    max_concurrent: max_concurrent.map(std::num::NonZeroUsize::get),
+                    overflow,
+                    key_defaults,
+                    resample_quality,
+                    stream_resample_quality: stream_resample_quality.unwrap_or(resample_quality),
+                };
+                let app = kokoros_openai::create_server_with_config(
+                    tts_instances,
+                    named_models,
+                    server_config,
+                )
+                .await;
                 let addr = SocketAddr::from((ip, port));
                 let binding = tokio::net::TcpListener::bind(&addr).await?;
                 tracing::info!("Starting OpenAI-compatible HTTP server on {}", addr);
                 kokoros_openai::serve(binding, app.into_make_service()).await?;
             }
 
-            Mode::Stream => {
-                let stdin = tokio::io::stdin();
-                let reader = BufReader::new(stdin);
-                let mut lines = reader.lines();
-
-                // Use std::io::stdout() for sync writing
-                let mut stdout = std::io::stdout();
-
-                eprintln!(
-                    "Entering streaming mode. Type text and press Enter. Use Ctrl+D to exit."
-                );
+            Mode::Stream {
+                input_fifo,
+                output,
+                stream_lookahead,
+            } => {
+                if let Some(fifo) = &input_fifo {
+                    check_fifo(fifo)?;
+                }
+                if !quiet {
+                    match &input_fifo {
+                        Some(fifo) => eprintln!(
+                            "Entering streaming mode. Reading lines from {}, stop with Ctrl+C.",
+                            fifo
+                        ),
+                        None => eprintln!(
+                            "Entering streaming mode. Type text and press Enter. Use Ctrl+D to exit."
+                        ),
+                    }
+                }
 
-                // Write WAV header first
-                let header = WavHeader::new(1, 24000, 32);
-                header.write_header(&mut stdout)?;
-                stdout.flush()?;
+                // Write WAV header first, matching the file output layout
+                let header =
+                    WavHeader::new(wav_options.file_channels(), tts.sample_rate(), wav_bits);
+                if let Some(path) = &output {
+                    prepare_output_path(path, mkdir)?;
+                }
+                let mut out = StreamWavWriter::create(output.as_deref(), &header)
+                    .map_err(|e| format!("failed to open the output stream: {}", e))?;
+                let destination = output.as_deref().unwrap_or("stdout");
 
-                while let Some(line) = lines.next_line().await? {
+                let mut speak_line = |line: String| -> std::io::Result<()> {
+                    // Text piped from a file may start with its byte order mark
+                    let line = preprocess.apply(strip_bom(&line));
                     let stripped_line = line.trim();
                     if stripped_line.is_empty() {
-                        continue;
+                        return Ok(());
                     }
 
                     // Process the line and get audio data
+                    let (lan, style) = resolve_language(stripped_line, &lan, &style, auto_lang);
+                    if stream_lookahead > 0 {
+                        // Failed writes end the stream, like below
+                        let mut write_error = None;
+                        let result = tts.tts_raw_audio_streaming_lookahead(
+                            stripped_line,
+                            &lan,
+                            &style,
+                            speed,
+                            initial_silence,
+                            stream_lookahead,
+                            |chunk| {
+                                out.write(&wav_options.frames(&chunk)).map_err(|e| {
+                                    let message = e.to_string();
+                                    write_error = Some(e);
+                                    message.into()
+                                })
+                            },
+                        );
+                        if let Some(e) = write_error {
+                            return Err(e);
+                        }
+                        match result {
+                            Ok(()) if !quiet => eprintln!(
+                                "Audio written to {}. Ready for another line of text.",
+                                destination
+                            ),
+                            Ok(()) => {}
+                            Err(e) => eprintln!("Error processing line: {}", e),
+                        }
+                        return Ok(());
+                    }
                     match tts.tts_raw_audio(
                         &stripped_line,
                         &lan,
@@ -432,15 +2980,45 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         None,
                     ) {
                         Ok(raw_audio) => {
-                            // Write the raw audio samples directly
-                            write_audio_chunk(&mut stdout, &raw_audio)?;
-                            stdout.flush()?;
-                            eprintln!("Audio written to stdout. Ready for another line of text.");
+                            // Write the raw audio samples directly, copied into every channel
+                            out.write(&wav_options.frames(&raw_audio))?;
+                            if !quiet {
+                                eprintln!(
+                                    "Audio written to {}. Ready for another line of text.",
+                                    destination
+                                );
+                            }
                         }
                         Err(e) => eprintln!("Error processing line: {}", e),
                     }
+                    Ok(())
+                };
+
+                match &input_fifo {
+                    None => {
+                        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+                        while let Some(line) = lines.next_line().await? {
+                            speak_line(line)?;
+                        }
+                    }
+                    // Opening blocks until a writer connects and EOF means the last
+                    // writer closed it, so reopen to wait for the next one
+                    Some(fifo) => loop {
+                        let file = tokio::fs::File::open(fifo)
+                            .await
+                            .map_err(|e| format!("failed to open FIFO {}: {}", fifo, e))?;
+                        let mut lines = BufReader::new(file).lines();
+                        while let Some(line) = lines.next_line().await? {
+                            speak_line(line)?;
+                        }
+                    },
                 }
             }
+
+            // Handled before the model is loaded
+            Mode::InspectModel { .. } | Mode::ValidateVoices { .. } | Mode::Join { .. } => {
+                unreachable!()
+            }
         }
 
         Ok(())
@@ -0,0 +1,94 @@
+use kokoros::utils::audio::AudioStats;
+use serde_json::{Value, json};
+
+/// Per-line summary of a file mode run, written with `--manifest`
+#[derive(Default)]
+pub struct Manifest {
+    entries: Vec<Value>,
+}
+
+impl Manifest {
+    pub fn record_ok(
+        &mut self,
+        line: usize,
+        output: &str,
+        style: &str,
+        lan: &str,
+        duration: f32,
+        stats: &AudioStats,
+    ) {
+        self.entries.push(json!({
+            "line": line,
+            "output": output,
+            "voice": style,
+            "lan": lan,
+            "duration_sec": duration,
+            "peak": stats.peak,
+            "rms": stats.rms,
+            "clipped_samples": stats.clipped_samples,
+            "status": "ok",
+            "error": null,
+        }));
+    }
+
+    pub fn record_error(&mut self, line: usize, output: &str, style: &str, lan: &str, error: &str) {
+        self.entries.push(json!({
+            "line": line,
+            "output": output,
+            "voice": style,
+            "lan": lan,
+            "duration_sec": null,
+            "peak": null,
+            "rms": null,
+            "clipped_samples": null,
+            "status": "error",
+            "error": error,
+        }));
+    }
+
+    /// A line left alone with `--skip-existing` because its output exists
+    pub fn record_skipped(&mut self, line: usize, output: &str, style: &str, lan: &str) {
+        self.entries.push(json!({
+            "line": line,
+            "output": output,
+            "voice": style,
+            "lan": lan,
+            "duration_sec": null,
+            "peak": null,
+            "rms": null,
+            "clipped_samples": null,
+            "status": "skipped",
+            "error": null,
+        }));
+    }
+
+    pub fn failures(&self) -> usize {
+        self.count("error")
+    }
+
+    fn count(&self, status: &str) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e["status"] == status)
+            .count()
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        let total_duration: f64 = self
+            .entries
+            .iter()
+            .filter_map(|e| e["duration_sec"].as_f64())
+            .sum();
+        // Lines are recorded as they finish, which isn't always in order
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|e| e["line"].as_u64());
+        let manifest = json!({
+            "entries": entries,
+            "succeeded": self.count("ok"),
+            "failed": self.failures(),
+            "skipped": self.count("skipped"),
+            "total_duration_sec": total_duration,
+        });
+        std::fs::write(path, serde_json::to_string_pretty(&manifest)?)
+    }
+}
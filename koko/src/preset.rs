@@ -0,0 +1,124 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Named presets of a presets file, each mapping long option names to values,
+/// e.g. `{"audiobook": {"style": "bf_emma", "speed": 0.9, "limiter": true}}`
+pub type Presets = BTreeMap<String, serde_json::Map<String, Value>>;
+
+/// `$XDG_CONFIG_HOME/kokoros/presets.json`, or `~/.config/kokoros/presets.json`
+pub fn default_presets_path() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default()
+        .join("kokoros")
+        .join("presets.json")
+}
+
+pub fn load_presets(path: &Path) -> Result<Presets, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read presets file {}: {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Command line arguments for the options of preset `name`, leaving out
+/// options already given on the command line or through the environment so
+/// they override the preset. The arguments go before the subcommand, so
+/// presets hold top-level options only.
+pub fn preset_args(
+    cmd: &Command,
+    matches: &ArgMatches,
+    presets: &Presets,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    let preset = presets.get(name).ok_or_else(|| {
+        let available: Vec<&str> = presets.keys().map(String::as_str).collect();
+        format!(
+            "unknown preset '{}', available presets: {}",
+            name,
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            }
+        )
+    })?;
+
+    let mut args = Vec::new();
+    for (key, value) in preset {
+        let arg = cmd
+            .get_arguments()
+            .find(|a| a.get_long() == Some(key.as_str()) && key != "preset")
+            .ok_or_else(|| format!("preset '{}': unknown option '{}'", name, key))?;
+        let explicit = matches!(
+            matches.value_source(arg.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        if explicit {
+            continue;
+        }
+
+        let flag = format!("--{}", key);
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        for value in values {
+            match value {
+                Value::Bool(set) if !arg.get_action().takes_values() => {
+                    if *set {
+                        args.push(flag.clone());
+                    }
+                }
+                Value::Bool(b) => args.extend([flag.clone(), b.to_string()]),
+                Value::Number(n) => args.extend([flag.clone(), n.to_string()]),
+                Value::String(s) => args.extend([flag.clone(), s.clone()]),
+                _ => {
+                    return Err(format!(
+                        "preset '{}': option '{}' needs a string, number or boolean",
+                        name, key
+                    ));
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    #[test]
+    fn test_preset_args() {
+        let cmd = Command::new("koko")
+            .arg(Arg::new("style").long("style").default_value("af_heart"))
+            .arg(Arg::new("speed").long("speed").default_value("1.0"))
+            .arg(
+                Arg::new("limiter")
+                    .long("limiter")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(Arg::new("preset").long("preset"));
+        let presets: Presets = serde_json::from_str(
+            r#"{"audiobook": {"style": "bf_emma", "speed": 0.9, "limiter": true},
+                "bad": {"pitch": 2}}"#,
+        )
+        .unwrap();
+
+        let matches =
+            cmd.clone()
+                .get_matches_from(["koko", "--speed", "1.2", "--preset", "audiobook"]);
+        let args = preset_args(&cmd, &matches, &presets, "audiobook").unwrap();
+        // --speed was given, so the preset's speed is left out
+        assert_eq!(args, ["--limiter", "--style", "bf_emma"]);
+
+        let unknown = preset_args(&cmd, &matches, &presets, "podcast").unwrap_err();
+        assert!(unknown.contains("audiobook, bad"), "{}", unknown);
+        assert!(preset_args(&cmd, &matches, &presets, "bad").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use kokoros::tts::koko::TTSKoko;
+use rodio::{OutputStream, Sink, buffer::SamplesBuffer};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "Type text to speak it. Commands:
+  :voice STYLE   switch voice or blend (e.g. af_sky or af_sarah.4+af_nicole.6)
+  :speed SPEED   set the speed (e.g. 1.2 or 120%)
+  :lan LANGUAGE  set the eSpeak language
+  :save PATH     save the last utterance as a WAV file
+  :help          show this help
+  :quit          exit (or Ctrl+D)";
+
+/// Interactive loop: synthesize each typed line and play it on the default
+/// output device
+pub fn run_repl(
+    tts: &TTSKoko,
+    mut lan: String,
+    mut style: String,
+    mut speed: f32,
+    sample_rate: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (_stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+    let mut last_audio: Option<Vec<f32>> = None;
+
+    eprintln!("{}", HELP);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        eprint!("[{} @ {}x] > ", style, speed);
+        std::io::stderr().flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(command) = line.strip_prefix(':') {
+            let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+            let arg = arg.trim();
+            match (name, arg) {
+                ("quit" | "q" | "exit", _) => break,
+                ("help" | "h", _) => eprintln!("{}", HELP),
+                ("voice", "") | ("speed", "") | ("lan", "") | ("save", "") => {
+                    eprintln!(":{} needs an argument, see :help", name)
+                }
+                ("voice", voice) => style = voice.to_string(),
+                ("lan", language) => lan = language.to_string(),
+                ("speed", value) => match crate::parse_speed(value) {
+                    Ok(value) => speed = value,
+                    Err(e) => eprintln!("{}", e),
+                },
+                ("save", path) => match &last_audio {
+                    Some(audio) => match crate::wav::write_wav_file(
+                        path,
+                        audio,
+                        sample_rate,
+                        &crate::wav::WavOptions {
+                            channels: 1,
+                            ..Default::default()
+                        },
+                    ) {
+                        Ok(()) => eprintln!("Audio saved to {}", path),
+                        Err(e) => eprintln!("Failed to save {}: {}", path, e),
+                    },
+                    None => eprintln!("Nothing to save yet"),
+                },
+                _ => eprintln!("Unknown command :{}, see :help", name),
+            }
+            continue;
+        }
+
+        match tts.tts_raw_audio(line, &lan, &style, speed, None, None, None, None) {
+            Ok(audio) => {
+                sink.append(SamplesBuffer::new(1, sample_rate, audio.clone()));
+                sink.sleep_until_end();
+                last_audio = Some(audio);
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Synthesize `text` and play it on the default output device, returning
+/// once playback has finished
+pub fn play_text(
+    tts: &TTSKoko,
+    text: &str,
+    lan: &str,
+    style: &str,
+    speed: f32,
+    sample_rate: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (_stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+    let audio = tts.tts_raw_audio(text, lan, style, speed, None, None, None, None)?;
+    sink.append(SamplesBuffer::new(1, sample_rate, audio));
+    sink.sleep_until_end();
+    Ok(())
+}
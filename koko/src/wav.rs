@@ -0,0 +1,872 @@
+use kokoros::utils::wav::{WavHeader, write_audio_samples};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Layout of the WAV files written by text and file mode
+#[derive(Debug, Clone)]
+pub struct WavOptions {
+    /// Number of channels, each carrying a copy of the mono audio
+    pub channels: u16,
+    /// How the first two channels are derived from the mono audio
+    pub stereo_mode: StereoMode,
+    /// Position from -1.0 (left) to 1.0 (right) for `StereoMode::Pan`
+    pub pan: f32,
+    /// Pad the `data` chunk with silence to a multiple of this many bytes
+    pub align: Option<u32>,
+    /// 16 or 24 for integer PCM, 32 for float
+    pub bits_per_sample: u16,
+    /// Write a Broadcast Wave Format file with this `bext` chunk
+    pub bext: Option<Bext>,
+    /// Average the `channels` into one when writing, for a mono deliverable
+    /// from a multi-channel layout
+    pub downmix: bool,
+    /// Split chunk-by-chunk output over files of at most this many bytes,
+    /// see `SplitWavWriter`
+    pub split_bytes: Option<u64>,
+}
+
+/// How `--stereo-mode` fills the left and right channels from the mono
+/// audio. Channels past the first two always carry a copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StereoMode {
+    /// The same signal in both channels
+    #[default]
+    Dup,
+    /// Balance the signal between left and right by `WavOptions::pan`
+    Pan,
+    /// Delay the right channel by `HAAS_DELAY_FRAMES` for a wider image
+    Decorrelate,
+}
+
+impl FromStr for StereoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dup" => Ok(StereoMode::Dup),
+            "pan" => Ok(StereoMode::Pan),
+            "decorrelate" => Ok(StereoMode::Decorrelate),
+            other => Err(format!(
+                "unknown stereo mode '{}', expected dup, pan or decorrelate",
+                other
+            )),
+        }
+    }
+}
+
+/// Right channel delay of `StereoMode::Decorrelate`, 1 ms at the model's
+/// 24 kHz. Short enough to be heard as width rather than an echo
+pub const HAAS_DELAY_FRAMES: usize = 24;
+
+/// Broadcast Wave (EBU Tech 3285) metadata, written as a version 1 `bext` chunk
+#[derive(Debug, Clone)]
+pub struct Bext {
+    /// Free text, up to 256 ASCII characters
+    pub description: String,
+    /// Name of the producing tool or organisation, up to 32 characters
+    pub originator: String,
+    /// Origination date and time, written in UTC. `None` uses the time the
+    /// file is written
+    pub origination: Option<SystemTime>,
+}
+
+/// Size of a version 1 `bext` chunk without coding history
+const BEXT_SIZE: u32 = 602;
+
+impl Bext {
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            originator: "Kokoros".to_string(),
+            origination: None,
+        }
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // Fixed-size ASCII fields, truncated or padded with NULs
+        let field = |w: &mut W, text: &str, len: usize| {
+            let mut bytes: Vec<u8> = text
+                .chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                .take(len)
+                .collect();
+            bytes.resize(len, 0);
+            w.write_all(&bytes)
+        };
+        let (date, time) = utc_date_time(self.origination.unwrap_or_else(SystemTime::now));
+
+        w.write_all(b"bext")?;
+        w.write_all(&BEXT_SIZE.to_le_bytes())?;
+        field(w, &self.description, 256)?;
+        field(w, &self.originator, 32)?;
+        field(w, "", 32)?; // OriginatorReference
+        field(w, &date, 10)?;
+        field(w, &time, 8)?;
+        w.write_all(&0u64.to_le_bytes())?; // TimeReference, samples since midnight of the timeline
+        w.write_all(&1u16.to_le_bytes())?; // Version
+        w.write_all(&[0u8; 64])?; // UMID
+        w.write_all(&[0u8; 190])?; // Reserved
+        Ok(())
+    }
+}
+
+/// `yyyy-mm-dd` and `hh:mm:ss` of `time` in UTC
+fn utc_date_time(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60),
+    )
+}
+
+impl Default for WavOptions {
+    fn default() -> Self {
+        Self {
+            channels: 2,
+            stereo_mode: StereoMode::Dup,
+            pan: 0.0,
+            align: None,
+            bits_per_sample: 32,
+            bext: None,
+            downmix: false,
+            split_bytes: None,
+        }
+    }
+}
+
+impl WavOptions {
+    /// Channels of the written file
+    pub fn file_channels(&self) -> u16 {
+        if self.downmix { 1 } else { self.channels }
+    }
+
+    /// Lay out mono `samples` as the interleaved frames of the written file
+    pub fn frames(&self, samples: &[f32]) -> Vec<f32> {
+        let mut interleaved = interleave(samples, self.channels);
+        if self.channels >= 2 {
+            apply_stereo_mode(
+                &mut interleaved,
+                samples,
+                self.channels,
+                self.stereo_mode,
+                self.pan,
+            );
+        }
+        if self.downmix {
+            downmix(&interleaved, self.channels)
+        } else {
+            interleaved
+        }
+    }
+}
+
+/// Largest channel count accepted by `--channels`
+pub const MAX_CHANNELS: u16 = 8;
+
+/// Replicate mono `samples` into an interleaved buffer of `channels` channels
+pub fn interleave(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels == 1 {
+        return samples.to_vec();
+    }
+    samples
+        .iter()
+        .flat_map(|&s| std::iter::repeat_n(s, channels as usize))
+        .collect()
+}
+
+/// Rewrite the left and right channels of `interleaved`, the copies of
+/// `samples` made by `interleave`, according to `mode`. The decorrelation
+/// delay starts over with every call, so the last `HAAS_DELAY_FRAMES` of the
+/// right channel of each chunk, normally trailing silence, are dropped
+fn apply_stereo_mode(
+    interleaved: &mut [f32],
+    samples: &[f32],
+    channels: u16,
+    mode: StereoMode,
+    pan: f32,
+) {
+    let frames = interleaved.chunks_mut(channels as usize);
+    match mode {
+        StereoMode::Dup => {}
+        StereoMode::Pan => {
+            // Balance law, so a centred pan keeps the level of `Dup`
+            let pan = pan.clamp(-1.0, 1.0);
+            let (left, right) = ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0));
+            for frame in frames {
+                frame[0] *= left;
+                frame[1] *= right;
+            }
+        }
+        StereoMode::Decorrelate => {
+            for (i, frame) in frames.enumerate() {
+                frame[1] = i.checked_sub(HAAS_DELAY_FRAMES).map_or(0.0, |j| samples[j]);
+            }
+        }
+    }
+}
+
+/// Average each frame of an interleaved buffer of `channels` channels into
+/// one mono sample
+pub fn downmix(interleaved: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+pub fn write_wav_file(
+    path: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    opts: &WavOptions,
+) -> io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+    write_wav(&mut f, samples, sample_rate, opts)?;
+    f.flush()
+}
+
+pub fn write_wav<W: Write>(
+    w: &mut W,
+    samples: &[f32],
+    sample_rate: u32,
+    opts: &WavOptions,
+) -> io::Result<()> {
+    write_frames(
+        w,
+        &opts.frames(samples),
+        opts.file_channels(),
+        sample_rate,
+        opts,
+    )
+}
+
+/// Write `frames`, already interleaved into `channels` channels. The
+/// `channels` and `downmix` of `opts` are not used
+pub fn write_frames<W: Write>(
+    w: &mut W,
+    frames: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    opts: &WavOptions,
+) -> io::Result<()> {
+    let header = WavHeader::new(channels, sample_rate, opts.bits_per_sample);
+    let unpadded_size = frames.len() as u64 * (opts.bits_per_sample / 8) as u64;
+    let data_size = padded_data_size(unpadded_size, opts, &header)?;
+
+    write_header(w, &header, data_size, opts)?;
+    write_audio_samples(w, frames, opts.bits_per_sample)?;
+
+    // Silence up to the block boundary, whole frames since align is a multiple of the frame size
+    let padding = (data_size as u64 - unpadded_size) as usize;
+    w.write_all(&vec![0u8; padding])?;
+
+    Ok(())
+}
+
+/// Size of the `data` chunk holding `unpadded_size` bytes of audio, rounded
+/// up to the alignment
+fn padded_data_size(unpadded_size: u64, opts: &WavOptions, header: &WavHeader) -> io::Result<u32> {
+    let block_align = header.block_align();
+    let data_size = match opts.align {
+        Some(align) => {
+            if align == 0 || align % block_align as u32 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "WAV alignment {} is not a multiple of the {}-byte frame size",
+                        align, block_align
+                    ),
+                ));
+            }
+            unpadded_size.div_ceil(align as u64) * align as u64
+        }
+        None => unpadded_size,
+    };
+    // RIFF sizes are 32-bit
+    u32::try_from(data_size)
+        .ok()
+        .filter(|size| size.checked_add(36 + 8 + BEXT_SIZE).is_some())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "audio too long for a WAV file"))
+}
+
+/// Write everything before the samples: the RIFF, optional bext, fmt and
+/// `data` chunk headers
+fn write_header<W: Write>(
+    w: &mut W,
+    header: &WavHeader,
+    data_size: u32,
+    opts: &WavOptions,
+) -> io::Result<()> {
+    let bext_chunk_size = if opts.bext.is_some() {
+        8 + BEXT_SIZE
+    } else {
+        0
+    };
+    let riff_chunk_size: u32 = 36 + bext_chunk_size + data_size; // 4 + (8+16) + (8+data)
+
+    // RIFF header
+    w.write_all(b"RIFF")?;
+    w.write_all(&riff_chunk_size.to_le_bytes())?;
+    w.write_all(b"WAVE")?;
+
+    // bext chunk, placed before fmt as EBU Tech 3285 recommends
+    if let Some(bext) = &opts.bext {
+        bext.write(w)?;
+    }
+
+    // fmt chunk
+    w.write_all(b"fmt ")?;
+    w.write_all(&(16u32).to_le_bytes())?; // PCM fmt chunk size
+    w.write_all(&header.format_tag().to_le_bytes())?; // PCM = 1, IEEE float = 3
+    w.write_all(&header.channels.to_le_bytes())?;
+    w.write_all(&header.sample_rate.to_le_bytes())?;
+    w.write_all(&header.byte_rate().to_le_bytes())?;
+    w.write_all(&header.block_align().to_le_bytes())?;
+    w.write_all(&header.bits_per_sample.to_le_bytes())?;
+
+    // data chunk
+    w.write_all(b"data")?;
+    w.write_all(&data_size.to_le_bytes())
+}
+
+/// A WAV file written chunk by chunk, for `--low-memory`. The header is
+/// written with an empty `data` chunk and patched with the real sizes by
+/// `finish`, so only the current chunk is ever in memory
+pub struct WavFileWriter {
+    file: BufWriter<File>,
+    header: WavHeader,
+    opts: WavOptions,
+    /// Bytes of audio written so far
+    written: u64,
+}
+
+impl WavFileWriter {
+    pub fn create(path: &str, sample_rate: u32, opts: &WavOptions) -> io::Result<Self> {
+        let header = WavHeader::new(opts.file_channels(), sample_rate, opts.bits_per_sample);
+        // Fix the origination time so the patched header matches the first one
+        let mut opts = opts.clone();
+        if let Some(bext) = &mut opts.bext {
+            bext.origination.get_or_insert_with(SystemTime::now);
+        }
+        // Reject a bad alignment before any synthesis happens
+        padded_data_size(0, &opts, &header)?;
+
+        let mut file = BufWriter::new(File::create(path)?);
+        write_header(&mut file, &header, 0, &opts)?;
+        Ok(Self {
+            file,
+            header,
+            opts,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        write_audio_samples(
+            &mut self.file,
+            &self.opts.frames(samples),
+            self.opts.bits_per_sample,
+        )?;
+        self.written += samples.len() as u64 * self.header.block_align() as u64;
+        Ok(())
+    }
+
+    /// Pad the audio to the alignment and patch the sizes in the header
+    pub fn finish(mut self) -> io::Result<()> {
+        let data_size = padded_data_size(self.written, &self.opts, &self.header)?;
+        let padding = (data_size as u64 - self.written) as usize;
+        self.file.write_all(&vec![0u8; padding])?;
+
+        self.file.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.file, &self.header, data_size, &self.opts)?;
+        self.file.flush()
+    }
+}
+
+/// Size of the header `write_header` writes for `opts`
+fn header_size(opts: &WavOptions) -> u64 {
+    44 + if opts.bext.is_some() {
+        8 + u64::from(BEXT_SIZE)
+    } else {
+        0
+    }
+}
+
+/// `path` with a 1-based part number after its stem, e.g. `out.wav` becomes
+/// `out_part1.wav`
+pub fn part_path(path: &str, part: usize) -> String {
+    let p = std::path::Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let name = match p.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{stem}_part{part}.{extension}"),
+        None => format!("{stem}_part{part}"),
+    };
+    p.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// Whether the output for `save_path` exists and isn't empty, for
+/// `--skip-existing`. Split outputs are looked up by their first part
+pub fn has_output(save_path: &str, opts: &WavOptions) -> bool {
+    let first = match opts.split_bytes {
+        Some(_) => part_path(save_path, 1),
+        None => save_path.to_string(),
+    };
+    std::fs::metadata(first).is_ok_and(|m| m.is_file() && m.len() > 0)
+}
+
+/// WAV output written chunk by chunk through `WavFileWriter`, split over
+/// several files of at most `opts.split_bytes` each when set, named by
+/// `part_path`. Every part is a complete WAV file. A new part
+/// starts at the first chunk that doesn't fit in the current one, so parts
+/// end between sentences; only chunks larger than a whole part are cut.
+pub struct SplitWavWriter {
+    path: String,
+    sample_rate: u32,
+    opts: WavOptions,
+    /// Audio bytes a part holds, `None` when not splitting
+    capacity: Option<u64>,
+    current: WavFileWriter,
+    /// Audio bytes in the current part
+    current_bytes: u64,
+    paths: Vec<String>,
+}
+
+impl SplitWavWriter {
+    pub fn create(path: &str, sample_rate: u32, opts: &WavOptions) -> io::Result<Self> {
+        let capacity = match opts.split_bytes {
+            Some(max_bytes) => {
+                let block_align =
+                    WavHeader::new(opts.file_channels(), sample_rate, opts.bits_per_sample)
+                        .block_align();
+                // Whole alignment blocks, so the padding never outgrows a part
+                let unit = u64::from(opts.align.unwrap_or(u32::from(block_align)).max(1));
+                let capacity = max_bytes.saturating_sub(header_size(opts)) / unit * unit;
+                if capacity == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "a split size of {} bytes leaves no room for audio after the WAV header",
+                            max_bytes
+                        ),
+                    ));
+                }
+                // RIFF sizes are 32-bit
+                Some(capacity.min(u64::from(u32::MAX) - header_size(opts)) / unit * unit)
+            }
+            None => None,
+        };
+
+        let first = match capacity {
+            Some(_) => part_path(path, 1),
+            None => path.to_string(),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            sample_rate,
+            opts: opts.clone(),
+            capacity,
+            current: WavFileWriter::create(&first, sample_rate, opts)?,
+            current_bytes: 0,
+            paths: vec![first],
+        })
+    }
+
+    /// Write mono `samples`, starting new parts as needed
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let Some(capacity) = self.capacity else {
+            return self.current.write(samples);
+        };
+        let frame_bytes = u64::from(
+            WavHeader::new(
+                self.opts.file_channels(),
+                self.sample_rate,
+                self.opts.bits_per_sample,
+            )
+            .block_align(),
+        );
+
+        let mut samples = samples;
+        let bytes = samples.len() as u64 * frame_bytes;
+        // A chunk that fits in a part of its own starts one rather than being
+        // cut; larger chunks fill the current part first
+        if self.current_bytes > 0 && self.current_bytes + bytes > capacity && bytes <= capacity {
+            self.next_part()?;
+        }
+        while !samples.is_empty() {
+            let room = ((capacity - self.current_bytes) / frame_bytes) as usize;
+            if room == 0 {
+                self.next_part()?;
+                continue;
+            }
+            let n = room.min(samples.len());
+            self.current.write(&samples[..n])?;
+            self.current_bytes += n as u64 * frame_bytes;
+            samples = &samples[n..];
+        }
+        Ok(())
+    }
+
+    fn next_part(&mut self) -> io::Result<()> {
+        let path = part_path(&self.path, self.paths.len() + 1);
+        let next = WavFileWriter::create(&path, self.sample_rate, &self.opts)?;
+        std::mem::replace(&mut self.current, next).finish()?;
+        self.current_bytes = 0;
+        self.paths.push(path);
+        Ok(())
+    }
+
+    /// Finish the last part, returning the paths of every part written
+    pub fn finish(self) -> io::Result<Vec<String>> {
+        self.current.finish()?;
+        Ok(self.paths)
+    }
+}
+
+/// The WAV stream of stream mode, written to stdout or a file line by line.
+/// The header is streamed with placeholder sizes; when the target is a
+/// regular file they are patched after every write, so the file is a valid,
+/// seekable WAV even if the stream is stopped with Ctrl+C
+pub struct StreamWavWriter {
+    out: Box<dyn Write>,
+    /// The target reopened for patching the header, `None` for pipes,
+    /// terminals and other targets that can't seek
+    patch: Option<File>,
+    bits_per_sample: u16,
+    /// Bytes of audio written so far
+    written: u64,
+}
+
+impl StreamWavWriter {
+    /// Stream to `path`, or to stdout for `None` and `-`
+    pub fn create(path: Option<&str>, header: &WavHeader) -> io::Result<Self> {
+        let (out, patch): (Box<dyn Write>, Option<File>) = match path {
+            Some(path) if path != "-" => {
+                let file = File::create(path)?;
+                let patch = file.try_clone().ok().filter(is_regular_file);
+                (Box::new(BufWriter::new(file)), patch)
+            }
+            _ => (
+                Box::new(io::stdout()),
+                stdout_file().filter(is_regular_file),
+            ),
+        };
+        let mut writer = Self {
+            out,
+            patch,
+            bits_per_sample: header.bits_per_sample,
+            written: 0,
+        };
+        header.write_header(&mut writer.out)?;
+        writer.out.flush()?;
+        Ok(writer)
+    }
+
+    /// Append interleaved `frames` and flush them to the target
+    pub fn write(&mut self, frames: &[f32]) -> io::Result<()> {
+        write_audio_samples(&mut self.out, frames, self.bits_per_sample)?;
+        self.written += frames.len() as u64 * u64::from(self.bits_per_sample / 8);
+        self.out.flush()?;
+        if let Some(file) = &mut self.patch {
+            WavHeader::patch_sizes(file, self.written)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_regular_file(file: &File) -> bool {
+    file.metadata().is_ok_and(|m| m.is_file())
+}
+
+/// Stdout as a file, for patching the header when it is redirected to one.
+/// `None` when it is opened for appending (`>>`), where every write goes to
+/// the end and patching would append the header instead, or isn't positioned
+/// at the start
+#[cfg(unix)]
+fn stdout_file() -> Option<File> {
+    use std::os::fd::{AsFd, AsRawFd};
+    let mut file = File::from(io::stdout().as_fd().try_clone_to_owned().ok()?);
+    // SAFETY: F_GETFL only reads the status flags of a descriptor we own
+    let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+    if flags == -1 || flags & libc::O_APPEND != 0 {
+        return None;
+    }
+    (file.stream_position().ok()? == 0).then_some(file)
+}
+
+#[cfg(not(unix))]
+fn stdout_file() -> Option<File> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_wav_round_trip() {
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 / 100.0).sin() * 0.5).collect();
+        let opts = WavOptions {
+            align: Some(4096),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &samples, 24_000, &opts).unwrap();
+
+        // 44-byte header plus 8000 bytes of audio padded to 8192
+        assert_eq!(bytes.len(), 44 + 8192);
+        assert_eq!(&bytes[4..8], &(36u32 + 8192).to_le_bytes());
+        assert_eq!(&bytes[40..44], &8192u32.to_le_bytes());
+
+        let mut reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 24_000);
+        let decoded: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(decoded.len(), 8192 / 4);
+        assert!(
+            decoded
+                .chunks(2)
+                .zip(&samples)
+                .all(|(frame, &s)| frame == [s, s])
+        );
+        assert!(decoded[2000..].iter().all(|&s| s == 0.0));
+
+        let misaligned = WavOptions {
+            align: Some(6),
+            ..Default::default()
+        };
+        assert!(write_wav(&mut Vec::new(), &samples, 24_000, &misaligned).is_err());
+    }
+
+    #[test]
+    fn test_wav_file_writer_matches_write_wav() {
+        let samples: Vec<f32> = (0..999).map(|i| (i as f32 / 50.0).sin() * 0.5).collect();
+        let opts = WavOptions {
+            align: Some(6000),
+            bits_per_sample: 24,
+            bext: Some(Bext::new("Chapter 1")),
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!("koko-wav-writer-{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut writer = WavFileWriter::create(path, 24_000, &opts).unwrap();
+        writer.write(&samples[..400]).unwrap();
+        writer.write(&samples[400..]).unwrap();
+        writer.finish().unwrap();
+        let streamed = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Same bytes as writing the whole buffer, apart from the origination time
+        let mut expected = Vec::new();
+        write_wav(&mut expected, &samples, 24_000, &opts).unwrap();
+        assert_eq!(streamed.len(), expected.len());
+        assert_eq!(&streamed[..340], &expected[..340]);
+        assert_eq!(&streamed[358..], &expected[358..]);
+    }
+
+    #[test]
+    fn test_24_bit_wav_round_trip() {
+        let samples = [0.25, -0.25, 0.0];
+        let opts = WavOptions {
+            channels: 1,
+            bits_per_sample: 24,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &samples, 24_000, &opts).unwrap();
+        assert_eq!(bytes.len(), 44 + 9);
+
+        let reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        let decoded: Vec<i32> = reader.into_samples().map(Result::unwrap).collect();
+        assert_eq!(decoded, vec![2_097_151, -2_097_151, 0]);
+    }
+
+    #[test]
+    fn test_bwf_round_trip() {
+        let opts = WavOptions {
+            bext: Some(Bext {
+                description: "Station ident".to_string(),
+                originator: "Kokoros".to_string(),
+                origination: Some(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+            }),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.5, -0.5], 24_000, &opts).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 8 + 602 + 16);
+        assert_eq!(&bytes[4..8], &(bytes.len() as u32 - 8).to_le_bytes());
+        assert_eq!(&bytes[12..16], b"bext");
+        assert_eq!(&bytes[20..33], b"Station ident");
+        // OriginationDate and OriginationTime follow the 320 bytes of text fields
+        assert_eq!(&bytes[340..358], b"2023-11-1422:13:20");
+
+        let mut reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        let decoded: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(decoded, vec![0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_downmix() {
+        assert_eq!(downmix(&[0.5, 0.25, -1.0, 0.0], 2), vec![0.375, -0.5]);
+        assert_eq!(downmix(&[0.5, -0.5], 1), vec![0.5, -0.5]);
+
+        let opts = WavOptions {
+            channels: 4,
+            downmix: true,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.5, -0.25], 24_000, &opts).unwrap();
+        let mut reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        let decoded: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(decoded, vec![0.5, -0.25]);
+    }
+
+    #[test]
+    fn test_stereo_mode() {
+        assert_eq!(" Decorrelate".parse(), Ok(StereoMode::Decorrelate));
+        assert!("wide".parse::<StereoMode>().is_err());
+
+        let pan = WavOptions {
+            stereo_mode: StereoMode::Pan,
+            pan: 0.5,
+            channels: 3,
+            ..Default::default()
+        };
+        assert_eq!(pan.frames(&[0.8]), vec![0.4, 0.8, 0.8]);
+
+        let wide = WavOptions {
+            stereo_mode: StereoMode::Decorrelate,
+            ..Default::default()
+        };
+        let samples: Vec<f32> = (1..=HAAS_DELAY_FRAMES + 1).map(|i| i as f32).collect();
+        let frames = wide.frames(&samples);
+        assert_eq!(&frames[..2], &[1.0, 0.0]);
+        assert_eq!(
+            &frames[frames.len() - 2..],
+            &[samples[HAAS_DELAY_FRAMES], 1.0]
+        );
+    }
+
+    #[test]
+    fn test_multichannel_wav() {
+        let opts = WavOptions {
+            channels: 4,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.5, -0.25], 24_000, &opts).unwrap();
+
+        let mut reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().channels, 4);
+        let decoded: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(
+            decoded,
+            vec![0.5, 0.5, 0.5, 0.5, -0.25, -0.25, -0.25, -0.25]
+        );
+    }
+
+    #[test]
+    fn test_stream_wav_writer_patches_file() {
+        let path = std::env::temp_dir().join(format!("kokoros-stream-{}.wav", std::process::id()));
+        let mut writer =
+            StreamWavWriter::create(path.to_str(), &WavHeader::new(1, 24_000, 16)).unwrap();
+        writer.write(&[0.5, -0.5]).unwrap();
+        writer.write(&[0.25]).unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[4..8], &42u32.to_le_bytes());
+        assert_eq!(&bytes[40..44], &6u32.to_le_bytes());
+        let reader = hound::WavReader::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.duration(), 3);
+    }
+
+    #[test]
+    fn test_split_wav_writer() {
+        let dir = std::env::temp_dir().join(format!("kokoros-split-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.wav");
+        // Room for 5 mono 16-bit samples after the header
+        let opts = WavOptions {
+            channels: 1,
+            bits_per_sample: 16,
+            split_bytes: Some(44 + 10),
+            ..Default::default()
+        };
+        let mut writer = SplitWavWriter::create(path.to_str().unwrap(), 24_000, &opts).unwrap();
+        writer.write(&[0.1; 3]).unwrap();
+        // Doesn't fit after the first chunk, so it starts the second part
+        writer.write(&[0.2; 3]).unwrap();
+        // Larger than a part, cut across the rest
+        writer.write(&[0.3; 12]).unwrap();
+        let paths = writer.finish().unwrap();
+
+        assert_eq!(paths.len(), 4);
+        assert!(paths[0].ends_with("out_part1.wav"));
+        let durations: Vec<u32> = paths
+            .iter()
+            .map(|p| hound::WavReader::open(p).unwrap().duration())
+            .collect();
+        assert_eq!(durations, [3, 5, 5, 5]);
+        assert!(
+            paths
+                .iter()
+                .all(|p| std::fs::metadata(p).unwrap().len() <= 54)
+        );
+
+        let too_small = WavOptions {
+            split_bytes: Some(40),
+            ..opts
+        };
+        assert!(SplitWavWriter::create(path.to_str().unwrap(), 24_000, &too_small).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_has_output_split() {
+        let dir = std::env::temp_dir().join(format!("kokoros-has-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.wav");
+        let path = path.to_str().unwrap();
+        let split = WavOptions {
+            split_bytes: Some(1_000_000),
+            ..Default::default()
+        };
+        assert!(!has_output(path, &split));
+
+        let mut writer = SplitWavWriter::create(path, 24_000, &split).unwrap();
+        writer.write(&[0.1; 100]).unwrap();
+        writer.finish().unwrap();
+        // Only out_part1.wav exists
+        assert!(has_output(path, &split));
+        assert!(!has_output(path, &WavOptions::default()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
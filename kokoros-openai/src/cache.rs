@@ -0,0 +1,149 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// On-disk cache of encoded speech responses, keyed by a hash of everything
+/// that affects the audio.
+///
+/// Entries are plain files named by their key. When the directory grows past
+/// `max_bytes`, the least recently used entries are removed.
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl AudioCache {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, max_bytes })
+    }
+
+    /// Hex SHA-256 of `parts`, length-prefixed so different splits of the
+    /// same characters never collide
+    pub fn key(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    /// Whether an entry for `key` is cached, without marking it as used
+    pub fn contains(&self, key: &str) -> bool {
+        self.path(key).is_file()
+    }
+
+    /// Cached bytes for `key`, marking the entry as recently used
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(data)
+    }
+
+    /// Store `data` under `key`, then evict old entries beyond the size cap
+    pub fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        if data.len() as u64 > self.max_bytes {
+            return Ok(());
+        }
+        // Write then rename, so concurrent readers never see a partial entry
+        let tmp = self
+            .dir
+            .join(format!("{}.{}.tmp", key, uuid::Uuid::new_v4().simple()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, self.path(key))?;
+        self.evict()
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(&self.dir)?
+            .flatten()
+            // Skip files a concurrent `put` is still writing
+            .filter(|entry| entry.path().extension().is_none_or(|ext| ext != "tmp"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                metadata.is_file().then(|| {
+                    (
+                        metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                        metadata.len(),
+                        entry.path(),
+                    )
+                })
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        entries.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if remove_entry(&path) {
+                total -= len;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove a cache file, tolerating entries another request already evicted
+fn remove_entry(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => true,
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_cache() {
+        let dir = std::env::temp_dir().join(format!("kokoros-cache-{}", std::process::id()));
+        let cache = AudioCache::new(&dir, 10).unwrap();
+
+        let first = AudioCache::key(&["hello", "af_sky"]);
+        assert_ne!(first, AudioCache::key(&["hello", "af_sk", "y"]));
+        assert_eq!(cache.get(&first), None);
+        assert!(!cache.contains(&first));
+
+        cache.put(&first, b"123456").unwrap();
+        assert!(cache.contains(&first));
+        assert_eq!(cache.get(&first).as_deref(), Some(&b"123456"[..]));
+
+        // A second entry exceeds the cap, so the older one is evicted
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let second = AudioCache::key(&["bye", "af_sky"]);
+        cache.put(&second, b"abcdef").unwrap();
+        assert_eq!(cache.get(&first), None);
+        assert_eq!(cache.get(&second).as_deref(), Some(&b"abcdef"[..]));
+
+        // An in-progress write of another request is left alone
+        let tmp = dir.join(format!("{}.0.tmp", first));
+        fs::write(&tmp, b"12345").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put(&first, b"123456").unwrap();
+        assert!(tmp.is_file());
+        assert_eq!(cache.get(&second), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
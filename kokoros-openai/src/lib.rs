@@ -5,10 +5,46 @@
 //!
 //! ## Implemented Features
 //! - `/v1/audio/speech` - Text-to-speech generation with streaming support
+//! - `/v1/audio/speech/sse` - The same streamed audio as server-sent events: base64 encoded
+//!   16-bit PCM chunks as `data:` events followed by a final `event: done`
+//! - `/v1/audio/speech.json` - The same non-streamed audio as a JSON object
+//!   `{"audio": "<base64>", "format": "mp3", "sample_rate": 24000}`, for clients whose
+//!   proxies mangle binary bodies. `"encoding": "base64"` on `/v1/audio/speech` does the same
 //! - `/v1/audio/voices` - List available voices
-//! - `/v1/models` - List available models (static dummy list)
+//! - `/v1/audio/voice/validate` - Check a `voice` (optionally against a `lang_code`)
+//!   without synthesizing: `{"valid": false, "voice": "af_sky.4+xx", "error": "..."}`
+//! - `/v1/models` - List available models (static aliases plus any named models)
+//! - Named models: the request `model` field selects one of the models passed to
+//!   `create_server_with_config`; the OpenAI aliases (`tts-1`, `tts-1-hd`, `kokoro`,
+//!   `gpt-4o-mini-tts`) use the default model and unknown names return HTTP 400
+//! - Voice blends: `voice` accepts the CLI `--style` syntax (`af_sky.4+af_nicole.6`
+//!   or `af_sky:0.4+af_nicole:0.6`); invalid parts or unknown voices return HTTP 400
 //! - Multiple audio formats: MP3, WAV, PCM, OPUS, AAC, FLAC
-//! - Streaming audio generation for low-latency responses
+//! - `pcm` matches OpenAI byte for byte: raw signed 16-bit little-endian mono samples
+//!   at 24 kHz with no header, streamed or not, so SDK clients can play it unmodified
+//! - `Accept` header: without `response_format`, the most preferred of `audio/mpeg`,
+//!   `audio/wav` and `audio/pcm` is used; headers naming only other audio types get WAV
+//! - `sample_rate`: optional output sample rate, one of `SUPPORTED_SAMPLE_RATES`;
+//!   the audio is resampled from the model's 24 kHz, other rates return HTTP 400.
+//!   `ServerConfig::resample_quality` and `stream_resample_quality` pick the resampler
+//! - Streaming audio generation for low-latency responses: PCM by default, or one
+//!   continuous WAV or MP3 stream when `response_format` or `Accept` asks for it
+//! - Cancellation: synthesis stops at the next chunk boundary when the client disconnects
+//! - Input length limit: requests whose `input` exceeds `ServerConfig::max_input_chars`
+//!   (default 4096 characters, like OpenAI) are rejected with HTTP 413 and a JSON error
+//! - Response cache: with `ServerConfig::cache`, non-streaming responses are stored on
+//!   disk keyed by model, input, voice, speed, language, format and sample rate, and
+//!   repeated requests are answered from the cache without synthesis. With
+//!   `ServerConfig::cache_transcode`, other formats are re-encoded from a cached WAV copy.
+//!   Responses carry their key in an `X-Cache-Key` header, and `/admin/cache/key` returns
+//!   the key of a speech request with the values it is computed from, without synthesis
+//! - Concurrency limit: with `ServerConfig::max_concurrent`, speech requests beyond the
+//!   limit wait for a free slot or, with `OverflowPolicy::Reject`, get HTTP 503
+//! - Per-key defaults: with `ServerConfig::key_defaults`, requests omitting `voice` or
+//!   `speed` use the defaults of the API key in their `Authorization: Bearer` header,
+//!   then the global `af_sky` at speed 1.0. Keys only select defaults, they are not checked
+//! - `/metrics` - Blend cache hits, misses, evictions and hit rate in the Prometheus
+//!   text format, summed over all instances (see `InitConfig::blend_cache_size`)
 //!
 //! ## OpenAI API Compatibility Limitations
 //! - `return_download_link`: Not implemented (files are streamed directly)
@@ -16,8 +52,11 @@
 //! - `volume_multiplier`: Not implemented (audio returned at original levels)
 //! - `download_format`: Not implemented (only response_format used)
 //! - `normalization_options`: Not implemented (basic text processing only)
-//! - Streaming only supports PCM format (other formats fall back to PCM)
 
+pub mod cache;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::error::Error;
 use std::io;
 use std::sync::Arc;
@@ -28,19 +67,29 @@ use axum::{
     body::Body,
     extract::{Path, State},
     http::{StatusCode, header},
-    response::{IntoResponse, Response},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use cache::AudioCache;
 use futures::stream::StreamExt;
 use kokoros::{
+    tts::blend_cache::BlendCacheStats,
+    tts::cancel::CancellationToken,
     tts::koko::{InitConfig as TTSKokoInitConfig, TTSKoko},
+    utils::audio::{ResampleQuality, resample_with, to_pcm16},
+    utils::fileio::strip_bom,
     utils::mp3::pcm_to_mp3,
+    utils::stream_encoder::{StreamEncoder, StreamFormat},
     // utils::opus::pcm_to_opus_ogg,
     utils::wav::{WavHeader, write_audio_chunk},
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use tokio::sync::{Semaphore, mpsc};
 use tower_http::cors::CorsLayer;
 use tracing::{debug, error, info};
 use uuid::Uuid;
@@ -261,7 +310,7 @@ fn find_closest_break_word(words: &[&str], center: usize, break_words: &[&str])
     closest_pos
 }
 
-#[derive(Deserialize, Default, Debug)]
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 enum AudioFormat {
     #[default]
@@ -270,9 +319,56 @@ enum AudioFormat {
     Opus,
     Aac,
     Flac,
+    /// Raw samples exactly as OpenAI sends them: signed 16-bit little-endian,
+    /// mono, 24 kHz (or the request `sample_rate`), no header
     Pcm,
 }
 
+impl AudioFormat {
+    /// Format for a media type of an `Accept` header, if it is one we encode
+    fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "audio/mpeg" | "audio/mp3" => Some(AudioFormat::Mp3),
+            "audio/wav" | "audio/wave" | "audio/x-wav" | "audio/vnd.wave" => Some(AudioFormat::Wav),
+            "audio/pcm" | "audio/l16" => Some(AudioFormat::Pcm),
+            _ => None,
+        }
+    }
+
+    /// Format preferred by an `Accept` header, for requests without
+    /// `response_format`. Wildcards keep the OpenAI default (`None`); headers
+    /// listing only formats we can't encode (e.g. `audio/ogg`) fall back to WAV.
+    fn from_accept(accept: &str) -> Option<Self> {
+        let mut ranges: Vec<(String, f32)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let mime = params.next()?.trim().to_lowercase();
+                let quality = params
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!mime.is_empty() && quality > 0.0).then_some((mime, quality))
+            })
+            .collect();
+        if ranges.is_empty() {
+            return None;
+        }
+        // Stable, so equally preferred types keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (mime, _) in &ranges {
+            if mime == "*/*" || mime == "audio/*" {
+                return None;
+            }
+            if let Some(format) = Self::from_mime(mime) {
+                return Some(format);
+            }
+        }
+        Some(AudioFormat::Wav)
+    }
+}
+
 /// Map OpenAI voice names to Kokoro voice names for backwards compatibility
 ///
 /// OpenAI voices are mapped to their closest Kokoro equivalents:
@@ -339,9 +435,20 @@ impl Default for Voice {
 }
 
 impl Voice {
-    /// Get the Kokoro voice name, mapping from OpenAI voice names if necessary
+    /// Get the Kokoro voice name, mapping from OpenAI voice names if necessary.
+    /// In blends (`alloy.4+af_nicole.6`) each voice name is mapped.
     fn to_kokoro_voice(&self) -> String {
-        map_openai_voice_to_kokoro(&self.0).to_string()
+        if !self.0.contains(['+', ':']) {
+            return map_openai_voice_to_kokoro(&self.0).to_string();
+        }
+        self.0
+            .split('+')
+            .map(|part| match part.find(['.', ':']) {
+                Some(at) => format!("{}{}", map_openai_voice_to_kokoro(&part[..at]), &part[at..]),
+                None => map_openai_voice_to_kokoro(part).to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("+")
     }
 }
 
@@ -356,28 +463,38 @@ impl Default for Speed {
 
 #[derive(Deserialize)]
 struct SpeechRequest {
-    // Only one Kokoro model exists
-    #[allow(dead_code)]
+    /// An OpenAI alias for the default model or the name of a named model
     model: String,
 
     input: String,
 
+    /// Defaults to the API key's default voice, then `af_sky`
     #[serde(default)]
-    voice: Voice,
+    voice: Option<Voice>,
 
+    /// Defaults to the `Accept` header's preference, then MP3
     #[serde(default)]
-    response_format: AudioFormat,
+    response_format: Option<AudioFormat>,
 
+    /// Defaults to the API key's default speed, then 1.0
     #[serde(default)]
-    speed: Speed,
+    speed: Option<Speed>,
 
     #[serde(default)]
     initial_silence: Option<usize>,
 
+    /// Output sample rate, defaults to the model's
+    #[serde(default)]
+    sample_rate: Option<u32>,
+
     /// Enable streaming audio generation (implemented)
     #[serde(default)]
     stream: Option<bool>,
 
+    /// `base64` returns the audio inside a JSON object, like `/v1/audio/speech.json`
+    #[serde(default)]
+    encoding: Option<ResponseEncoding>,
+
     // OpenAI API compatibility parameters - accepted but not implemented
     // These fields ensure request parsing compatibility with OpenAI clients
     /// Return download link after generation (not implemented)
@@ -404,6 +521,16 @@ struct SpeechRequest {
     normalization_options: Option<serde_json::Value>,
 }
 
+/// How the audio of a non-streaming response is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ResponseEncoding {
+    /// The encoded audio as the response body
+    Binary,
+    /// A JSON object holding the base64 encoded audio, see `audio_json`
+    Base64,
+}
+
 /// Async TTS worker task
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -456,6 +583,25 @@ struct VoicesResponse {
     voices: Vec<String>,
 }
 
+/// Body of `/v1/audio/voice/validate`
+#[derive(Deserialize)]
+struct ValidateVoiceRequest {
+    voice: Voice,
+    /// Model whose voices to check against, the default model when left out
+    model: Option<String>,
+    /// Also check that the voices belong to this language
+    lang_code: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ValidateVoiceResponse {
+    valid: bool,
+    /// The Kokoro style the voice maps to
+    voice: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
 #[derive(Serialize)]
 struct ModelObject {
     id: String,
@@ -470,24 +616,235 @@ struct ModelsResponse {
     data: Vec<ModelObject>,
 }
 
+/// Default for `ServerConfig::max_input_chars`, matching OpenAI's limit
+pub const DEFAULT_MAX_INPUT_CHARS: usize = 4096;
+
+/// Limits and options of the HTTP server
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Longest accepted `input`, in characters. Longer requests are rejected
+    /// with HTTP 413 before any synthesis happens.
+    pub max_input_chars: usize,
+    /// Serve repeated non-streaming requests from this on-disk cache
+    pub cache: Option<Arc<AudioCache>>,
+    /// Keep a WAV copy of every cached response and answer requests for the
+    /// same speech in another format by re-encoding it instead of
+    /// re-synthesizing. Each format is still cached under its own key.
+    pub cache_transcode: bool,
+    /// Most speech requests synthesized at once, counting streamed responses
+    /// until their last chunk is sent. `None` is unlimited.
+    pub max_concurrent: Option<usize>,
+    /// What happens to speech requests beyond `max_concurrent`
+    pub overflow: OverflowPolicy,
+    /// Defaults for requests omitting `voice` or `speed`, by the API key sent
+    /// as `Authorization: Bearer <key>`
+    pub key_defaults: HashMap<String, KeyDefaults>,
+    /// Resampler used for the `sample_rate` of non-streaming responses
+    pub resample_quality: ResampleQuality,
+    /// Resampler used for the `sample_rate` of streamed chunks, e.g.
+    /// `ResampleQuality::Fast` to keep per-chunk latency down
+    pub stream_resample_quality: ResampleQuality,
+}
+
+/// Request defaults of one API key, see `ServerConfig::key_defaults`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyDefaults {
+    /// Voice or blend, in the syntax of the request `voice` field
+    #[serde(default)]
+    pub voice: Option<String>,
+    #[serde(default)]
+    pub speed: Option<f32>,
+}
+
+/// Parse a key defaults file, a JSON object from API key to defaults:
+/// `{"sk-team-a": {"voice": "af_nicole", "speed": 1.1}}`
+pub fn parse_key_defaults(json: &str) -> Result<HashMap<String, KeyDefaults>, String> {
+    let key_defaults: HashMap<String, KeyDefaults> =
+        serde_json::from_str(json).map_err(|e| format!("invalid key defaults: {}", e))?;
+    for (key, defaults) in &key_defaults {
+        if let Some(speed) = defaults.speed {
+            if !(speed.is_finite() && speed > 0.0) {
+                return Err(format!(
+                    "default speed {} of key '{}' must be a positive number",
+                    speed, key
+                ));
+            }
+        }
+    }
+    Ok(key_defaults)
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_input_chars: DEFAULT_MAX_INPUT_CHARS,
+            cache: None,
+            cache_transcode: false,
+            max_concurrent: None,
+            overflow: OverflowPolicy::default(),
+            key_defaults: HashMap::new(),
+            resample_quality: ResampleQuality::default(),
+            stream_resample_quality: ResampleQuality::default(),
+        }
+    }
+}
+
+/// Handling of speech requests arriving while `ServerConfig::max_concurrent`
+/// are already in flight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for a running request to finish
+    #[default]
+    Queue,
+    /// Answer immediately with HTTP 503
+    Reject,
+}
+
+impl std::str::FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "queue" => Ok(OverflowPolicy::Queue),
+            "reject" => Ok(OverflowPolicy::Reject),
+            other => Err(format!(
+                "unknown overflow policy '{}', expected queue or reject",
+                other
+            )),
+        }
+    }
+}
+
+/// Shared slots bounding the speech requests in flight
+#[derive(Clone)]
+struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    overflow: OverflowPolicy,
+}
+
+/// Model names OpenAI clients send, all served by the default model
+const OPENAI_MODEL_ALIASES: &[&str] = &["tts-1", "tts-1-hd", "kokoro", "gpt-4o-mini-tts"];
+
+/// The TTS instances loaded for one model
+#[derive(Clone)]
+struct ModelInstances {
+    /// First instance, used by the non-streaming endpoints
+    tts_single: TTSKoko,
+    tts_instances: Vec<TTSKoko>,
+}
+
+impl ModelInstances {
+    fn new(tts_instances: Vec<TTSKoko>) -> Self {
+        let tts_single = tts_instances
+            .first()
+            .cloned()
+            .expect("At least one TTS instance required");
+        Self {
+            tts_single,
+            tts_instances,
+        }
+    }
+}
+
+/// State shared by the HTTP handlers
+#[derive(Clone)]
+struct AppState {
+    default_model: ModelInstances,
+    named_models: Arc<HashMap<String, ModelInstances>>,
+    config: Arc<ServerConfig>,
+}
+
+impl AppState {
+    /// Blend cache counters of every instance of every model
+    fn blend_cache_stats(&self) -> BlendCacheStats {
+        let stats: Vec<BlendCacheStats> = std::iter::once(&self.default_model)
+            .chain(self.named_models.values())
+            .flat_map(|model| &model.tts_instances)
+            .map(TTSKoko::blend_cache_stats)
+            .collect();
+        BlendCacheStats::sum(&stats)
+    }
+
+    /// Look up the instances serving the request `model` field
+    fn model(&self, name: &str) -> Option<&ModelInstances> {
+        if let Some(named) = self.named_models.get(name) {
+            return Some(named);
+        }
+        if OPENAI_MODEL_ALIASES.contains(&name) {
+            return Some(&self.default_model);
+        }
+        None
+    }
+}
+
+/// Sample rates a request may ask for with `sample_rate`
+pub const SUPPORTED_SAMPLE_RATES: [u32; 6] = [8000, 16000, 22050, 24000, 44100, 48000];
+
 pub async fn create_server(tts_instances: Vec<TTSKoko>) -> Router {
+    create_server_with_config(tts_instances, HashMap::new(), ServerConfig::default()).await
+}
+
+/// Create the server with additional named models, selected by the `model`
+/// field of speech requests. Named models take precedence over the OpenAI aliases.
+pub async fn create_server_with_config(
+    tts_instances: Vec<TTSKoko>,
+    named_models: HashMap<String, Vec<TTSKoko>>,
+    config: ServerConfig,
+) -> Router {
     info!("Starting TTS server with {} instances", tts_instances.len());
+    for name in named_models.keys() {
+        info!("Serving named model '{}'", name);
+    }
 
-    // Use first instance for compatibility with non-streaming endpoints
-    let tts_single = tts_instances
-        .first()
-        .cloned()
-        .expect("At least one TTS instance required");
+    let concurrency_limit = config.max_concurrent.map(|max| {
+        info!(
+            "Limiting synthesis to {} concurrent requests ({:?} on overflow)",
+            max, config.overflow
+        );
+        ConcurrencyLimit {
+            semaphore: Arc::new(Semaphore::new(max)),
+            overflow: config.overflow,
+        }
+    });
+
+    let state = AppState {
+        default_model: ModelInstances::new(tts_instances),
+        named_models: Arc::new(
+            named_models
+                .into_iter()
+                .map(|(name, instances)| (name, ModelInstances::new(instances)))
+                .collect(),
+        ),
+        config: Arc::new(config),
+    };
+
+    // Only the synthesis endpoints count against the limit, so health checks
+    // and listings stay responsive under load
+    let speech_routes = Router::new()
+        .route("/v1/audio/speech", post(handle_tts))
+        .route("/v1/audio/speech.json", post(handle_tts_json))
+        .route("/v1/audio/speech/sse", post(handle_tts_sse));
+    let speech_routes = match concurrency_limit {
+        Some(limit) => speech_routes.layer(axum::middleware::from_fn_with_state(
+            limit,
+            concurrency_limit_middleware,
+        )),
+        None => speech_routes,
+    };
 
     Router::new()
         .route("/", get(handle_home))
-        .route("/v1/audio/speech", post(handle_tts))
+        .merge(speech_routes)
         .route("/v1/audio/voices", get(handle_voices))
+        .route("/v1/audio/voice/validate", post(handle_validate_voice))
+        .route("/admin/cache/key", post(handle_cache_key))
         .route("/v1/models", get(handle_models))
         .route("/v1/models/{model}", get(handle_model))
+        .route("/metrics", get(handle_metrics))
         .layer(axum::middleware::from_fn(request_id_middleware))
         .layer(CorsLayer::permissive())
-        .with_state((tts_single, tts_instances))
+        .with_state(state)
 }
 
 pub use axum::serve;
@@ -510,6 +867,25 @@ enum SpeechError {
 
     #[allow(dead_code)]
     OpusConversion(std::io::Error),
+
+    /// The request `input` is longer than the configured limit
+    InputTooLong { length: usize, max: usize },
+
+    /// The request `model` is neither an OpenAI alias nor a named model
+    UnknownModel(String),
+
+    /// The request `sample_rate` is not in `SUPPORTED_SAMPLE_RATES`
+    UnsupportedSampleRate(u32),
+
+    /// The request `voice` does not parse or names an unknown voice
+    InvalidVoice(String),
+
+    /// `ServerConfig::max_concurrent` requests are in flight and the overflow
+    /// policy is `Reject`
+    Overloaded,
+
+    /// Base64 JSON responses were asked for together with `stream`
+    StreamedBase64,
 }
 
 impl std::fmt::Display for SpeechError {
@@ -520,14 +896,95 @@ impl std::fmt::Display for SpeechError {
             SpeechError::Chunk(e) => write!(f, "Chunk error: {}", e),
             SpeechError::Mp3Conversion(e) => write!(f, "MP3 conversion error: {}", e),
             SpeechError::OpusConversion(e) => write!(f, "Opus conversion error: {}", e),
+            SpeechError::InputTooLong { length, max } => write!(
+                f,
+                "Input is {} characters long, the maximum is {}",
+                length, max
+            ),
+            SpeechError::UnknownModel(model) => write!(f, "Unknown model '{}'", model),
+            SpeechError::UnsupportedSampleRate(rate) => write!(
+                f,
+                "Unsupported sample rate {}, expected one of {:?}",
+                rate, SUPPORTED_SAMPLE_RATES
+            ),
+            SpeechError::InvalidVoice(e) => write!(f, "Invalid voice: {}", e),
+            SpeechError::Overloaded => write!(
+                f,
+                "The server is at its concurrency limit, retry the request later"
+            ),
+            SpeechError::StreamedBase64 => write!(
+                f,
+                "Base64 responses can't be streamed, use /v1/audio/speech/sse for base64 chunks"
+            ),
         }
     }
 }
 
+/// Build an OpenAI-style JSON error response
+fn json_error(status: StatusCode, message: String, param: &str, code: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "param": param,
+            "code": code,
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
 impl IntoResponse for SpeechError {
     fn into_response(self) -> Response {
-        // None of these errors make sense to expose to the user of the API
-        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        match self {
+            SpeechError::InputTooLong { .. } => json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                self.to_string(),
+                "input",
+                "input_too_long",
+            ),
+            SpeechError::UnknownModel(_) => json_error(
+                StatusCode::BAD_REQUEST,
+                self.to_string(),
+                "model",
+                "model_not_found",
+            ),
+            SpeechError::UnsupportedSampleRate(_) => json_error(
+                StatusCode::BAD_REQUEST,
+                self.to_string(),
+                "sample_rate",
+                "unsupported_sample_rate",
+            ),
+            SpeechError::InvalidVoice(_) => json_error(
+                StatusCode::BAD_REQUEST,
+                self.to_string(),
+                "voice",
+                "invalid_voice",
+            ),
+            SpeechError::StreamedBase64 => json_error(
+                StatusCode::BAD_REQUEST,
+                self.to_string(),
+                "stream",
+                "unsupported_stream_encoding",
+            ),
+            SpeechError::Overloaded => {
+                let body = serde_json::json!({
+                    "error": {
+                        "message": self.to_string(),
+                        "type": "server_error",
+                        "param": null,
+                        "code": "server_overloaded",
+                    }
+                });
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, "1")],
+                    Json(body),
+                )
+                    .into_response()
+            }
+            // None of these errors make sense to expose to the user of the API
+            _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
     }
 }
 
@@ -537,15 +994,31 @@ async fn handle_home() -> &'static str {
     "OK"
 }
 
-async fn handle_tts(
-    State((tts_single, tts_instances)): State<(TTSKoko, Vec<TTSKoko>)>,
+/// Read and validate a speech request body, resolving its model.
+/// Returns the request with the model instances and the request id and start time
+/// set by `request_id_middleware`.
+async fn read_speech_request(
+    state: &AppState,
     request: axum::extract::Request,
-) -> Result<Response, SpeechError> {
+) -> Result<(SpeechRequest, ModelInstances, String, Instant), SpeechError> {
     let (request_id, request_start) = request
         .extensions()
         .get::<(String, Instant)>()
         .cloned()
         .unwrap_or_else(|| ("unknown".to_string(), Instant::now()));
+    let accept_format = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .and_then(AudioFormat::from_accept);
+    let key_defaults = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|key| state.config.key_defaults.get(key.trim()))
+        .cloned()
+        .unwrap_or_default();
 
     // OpenAI TTS always streams by default - client decides how to consume
     // Only send complete file when explicitly requested via stream: false
@@ -558,24 +1031,110 @@ async fn handle_tts(
             SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
         })?;
 
-    let speech_request: SpeechRequest = serde_json::from_slice(&bytes).map_err(|e| {
+    let mut speech_request: SpeechRequest = serde_json::from_slice(&bytes).map_err(|e| {
         error!("JSON parsing error: {:?}", e);
         SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
     })?;
+    if speech_request.response_format.is_none() {
+        speech_request.response_format = accept_format;
+    }
+    // Input pasted from a file may carry its byte order mark
+    let input = strip_bom(&speech_request.input);
+    if input.len() != speech_request.input.len() {
+        speech_request.input = input.to_string();
+    }
+    // Request fields win over the key's defaults, which win over the global ones
+    if speech_request.voice.is_none() {
+        speech_request.voice = key_defaults.voice.map(Voice);
+    }
+    if speech_request.speed.is_none() {
+        speech_request.speed = key_defaults.speed.map(Speed);
+    }
+
+    // Reject oversized input before doing any work
+    let max_input_chars = state.config.max_input_chars;
+    let input_chars = speech_request.input.chars().count();
+    if input_chars > max_input_chars {
+        return Err(SpeechError::InputTooLong {
+            length: input_chars,
+            max: max_input_chars,
+        });
+    }
+
+    if let Some(rate) = speech_request.sample_rate {
+        if !SUPPORTED_SAMPLE_RATES.contains(&rate) {
+            return Err(SpeechError::UnsupportedSampleRate(rate));
+        }
+    }
+
+    let model = state
+        .model(&speech_request.model)
+        .cloned()
+        .ok_or_else(|| SpeechError::UnknownModel(speech_request.model.clone()))?;
+
+    // Same parsing and validation as the CLI's --style, including blends
+    let voice = speech_request.voice.get_or_insert_with(Voice::default);
+    model
+        .tts_single
+        .check_style(&voice.to_kokoro_voice())
+        .map_err(SpeechError::InvalidVoice)?;
+
+    Ok((speech_request, model, request_id, request_start))
+}
+
+async fn handle_tts(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+) -> Result<Response, SpeechError> {
+    speech_response(state, request, false).await
+}
+
+/// `/v1/audio/speech.json`: a speech request answered with `audio_json`
+/// whatever its `encoding`
+async fn handle_tts_json(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+) -> Result<Response, SpeechError> {
+    speech_response(state, request, true).await
+}
+
+/// Synthesize a speech request, sending the audio as JSON when `json` is set
+/// or the request asks for base64
+async fn speech_response(
+    state: AppState,
+    request: axum::extract::Request,
+    json: bool,
+) -> Result<Response, SpeechError> {
+    let (speech_request, model, request_id, request_start) =
+        read_speech_request(&state, request).await?;
 
     let SpeechRequest {
+        model: model_name,
         input,
         voice,
-        response_format,
-        speed: Speed(speed),
+        response_format: requested_format,
+        speed,
         initial_silence,
+        sample_rate,
         stream,
         lang_code,
+        encoding,
         ..
     } = speech_request;
+    let json = json || encoding == Some(ResponseEncoding::Base64);
+    let response_format = requested_format.unwrap_or_default();
+    let Speed(speed) = speed.unwrap_or_default();
+
+    let model_sample_rate = TTSKokoInitConfig::default().sample_rate;
+    let sample_rate = sample_rate.unwrap_or(model_sample_rate);
+
+    let ModelInstances {
+        tts_single,
+        tts_instances,
+    } = model;
 
     // Map OpenAI voice names to Kokoro voice names
-    let voice = voice.to_kokoro_voice();
+    let voice = voice.unwrap_or_default().to_kokoro_voice();
     let language = get_language_code(lang_code.as_deref(), &voice);
 
     // OpenAI-compliant behavior: Stream by default, only send complete file if stream: false
@@ -587,14 +1146,19 @@ async fn handle_tts(
         colored_request_id, stream, should_stream
     );
 
+    if should_stream && json {
+        return Err(SpeechError::StreamedBase64);
+    }
     if should_stream {
         return handle_tts_streaming(
             tts_instances,
             input,
             voice,
-            response_format,
+            requested_format,
             speed,
             initial_silence,
+            sample_rate,
+            state.config.stream_resample_quality,
             language.clone(),
             request_id,
             request_start,
@@ -602,32 +1166,259 @@ async fn handle_tts(
         .await;
     }
 
-    // Non-streaming mode (existing implementation)
-    let raw_audio = tts_single
-        .tts_raw_audio(
-            &input,
-            &language,
-            &voice,
+    let (content_type, format_name) = response_content_type(&response_format);
+    // Every format gets its own entry for the same speech
+    let speech_key = |format_name: &str| {
+        SpeechCacheParts {
+            model: &model_name,
+            input: &input,
+            voice: &voice,
             speed,
-            initial_silence,
-            Some(&request_id),
-            Some("00"),
-            None,
-        )
-        .map_err(SpeechError::Koko)?;
+            lang_code: &language,
+            initial_silence: initial_silence.unwrap_or(0),
+            format: format_name,
+            sample_rate,
+        }
+        .key()
+    };
+    let cache_key = state.config.cache.as_ref().map(|_| speech_key(format_name));
+    // The WAV master other formats are transcoded from
+    let (_, wav_format_name) = response_content_type(&AudioFormat::Wav);
+    let master_key = cache_key
+        .as_ref()
+        .filter(|_| state.config.cache_transcode && format_name != wav_format_name)
+        .map(|_| speech_key(wav_format_name));
+    if let (Some(cache), Some(key)) = (&state.config.cache, &cache_key) {
+        let mut cached = cache.get(key).map(|data| (data, ""));
+        let master = match (&cached, &master_key) {
+            (None, Some(master_key)) => cache.get(master_key),
+            _ => None,
+        };
+        if let Some(samples) = master.as_deref().and_then(wav_f32_samples) {
+            let audio_data = encode_response(&samples, sample_rate, &response_format)?;
+            if let Err(e) = cache.put(key, &audio_data) {
+                error!("Failed to write audio cache entry: {}", e);
+            }
+            cached = Some((audio_data, ", transcoded from WAV"));
+        }
+        if let Some((audio_data, transcoded)) = cached {
+            info!(
+                "{} TTS served from cache - {} bytes, {} format{}",
+                colored_request_id,
+                audio_data.len(),
+                format_name,
+                transcoded
+            );
+            return audio_response(
+                audio_data,
+                content_type,
+                format_name,
+                sample_rate,
+                json,
+                Some(key.as_str()),
+            );
+        }
+    }
+
+    // Non-streaming mode: synthesize off the async runtime. If the client
+    // disconnects this future is dropped, and the guard cancels the synthesis
+    // at the next chunk boundary.
+    let cancel = CancellationToken::new();
+    let _cancel_on_drop = cancel.drop_guard();
+    let request_id_clone = request_id.clone();
+    let raw_audio = tokio::task::spawn_blocking(move || {
+        tts_single
+            .tts_raw_audio_cancellable(
+                &input,
+                &language,
+                &voice,
+                speed,
+                initial_silence,
+                Some(&request_id_clone),
+                Some("00"),
+                None,
+                &cancel,
+            )
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| SpeechError::Koko(e.to_string().into()))?
+    .map_err(|e| SpeechError::Koko(e.into()))?;
+
+    let raw_audio = resample_with(
+        &raw_audio,
+        model_sample_rate,
+        sample_rate,
+        state.config.resample_quality,
+    );
+    let audio_data = encode_response(&raw_audio, sample_rate, &response_format)?;
+
+    if let (Some(cache), Some(key)) = (state.config.cache.clone(), cache_key.clone()) {
+        let master = match master_key {
+            Some(master_key) => Some((
+                master_key,
+                encode_response(&raw_audio, sample_rate, &AudioFormat::Wav)?,
+            )),
+            None => None,
+        };
+        let audio_data = audio_data.clone();
+        tokio::task::spawn_blocking(move || {
+            let entries = std::iter::once((key, audio_data)).chain(master);
+            for (key, data) in entries {
+                if let Err(e) = cache.put(&key, &data) {
+                    error!("Failed to write audio cache entry: {}", e);
+                }
+            }
+        });
+    }
+
+    let colored_request_id = get_colored_request_id_with_relative(&request_id, request_start);
+    info!(
+        "{} TTS non-streaming completed - {} bytes, {} format",
+        colored_request_id,
+        audio_data.len(),
+        format_name
+    );
+
+    audio_response(
+        audio_data,
+        content_type,
+        format_name,
+        sample_rate,
+        json,
+        cache_key.as_deref(),
+    )
+}
+
+/// Response carrying `audio_data`, as the body or inside `audio_json`, with
+/// its `cache_key` in an `X-Cache-Key` header when the cache is on
+fn audio_response(
+    audio_data: Vec<u8>,
+    content_type: &str,
+    format_name: &str,
+    sample_rate: u32,
+    json: bool,
+    cache_key: Option<&str>,
+) -> Result<Response, SpeechError> {
+    let mut response = if json {
+        Json(audio_json(&audio_data, format_name, sample_rate)).into_response()
+    } else {
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(audio_data.into())
+            .map_err(|e| {
+                SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::Other, e))
+            })?
+    };
+    if let Some(value) = cache_key.and_then(|key| header::HeaderValue::from_str(key).ok()) {
+        response.headers_mut().insert("x-cache-key", value);
+    }
+    Ok(response)
+}
+
+/// Everything a cached speech response depends on, hashed into its key by
+/// `key`. `/admin/cache/key` returns it to explain cache misses
+#[derive(Debug, Serialize)]
+struct SpeechCacheParts<'a> {
+    model: &'a str,
+    input: &'a str,
+    /// Kokoro style, after mapping OpenAI voice names
+    voice: &'a str,
+    speed: f32,
+    /// eSpeak language, given or derived from the voice
+    lang_code: &'a str,
+    initial_silence: usize,
+    format: &'a str,
+    sample_rate: u32,
+}
+
+impl SpeechCacheParts<'_> {
+    fn key(&self) -> String {
+        AudioCache::key(&[
+            self.model,
+            self.input,
+            self.voice,
+            &self.speed.to_string(),
+            self.lang_code,
+            &self.initial_silence.to_string(),
+            self.format,
+            &self.sample_rate.to_string(),
+        ])
+    }
+}
+
+/// Cache key a speech request would be stored under, with the normalized
+/// values it is computed from and whether the cache holds it, for debugging
+/// cache misses. Nothing is synthesized; the key is computed with the cache
+/// off too
+async fn handle_cache_key(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+) -> Result<Json<serde_json::Value>, SpeechError> {
+    let (speech_request, _, _, _) = read_speech_request(&state, request).await?;
+    let SpeechRequest {
+        model,
+        input,
+        voice,
+        response_format,
+        speed,
+        initial_silence,
+        sample_rate,
+        lang_code,
+        ..
+    } = speech_request;
+    let voice = voice.unwrap_or_default().to_kokoro_voice();
+    let language = get_language_code(lang_code.as_deref(), &voice);
+    let (_, format_name) = response_content_type(&response_format.unwrap_or_default());
+    let parts = SpeechCacheParts {
+        model: &model,
+        input: &input,
+        voice: &voice,
+        speed: speed.unwrap_or_default().0,
+        lang_code: &language,
+        initial_silence: initial_silence.unwrap_or(0),
+        format: format_name,
+        sample_rate: sample_rate.unwrap_or(TTSKokoInitConfig::default().sample_rate),
+    };
+    let key = parts.key();
+    let cached = state
+        .config
+        .cache
+        .as_ref()
+        .map(|cache| cache.contains(&key));
+    Ok(Json(serde_json::json!({
+        "key": key,
+        "cached": cached,
+        "parts": parts,
+    })))
+}
 
-    let sample_rate = TTSKokoInitConfig::default().sample_rate;
+/// Body of base64 responses: the encoded audio with its format and rate
+fn audio_json(audio_data: &[u8], format_name: &str, sample_rate: u32) -> serde_json::Value {
+    serde_json::json!({
+        "audio": BASE64.encode(audio_data),
+        "format": format_name.to_lowercase(),
+        "sample_rate": sample_rate,
+    })
+}
 
-    let (content_type, audio_data, format_name) = match response_format {
+/// Encode mono `raw_audio` at `sample_rate` as a complete response body in
+/// `response_format`
+fn encode_response(
+    raw_audio: &[f32],
+    sample_rate: u32,
+    response_format: &AudioFormat,
+) -> Result<Vec<u8>, SpeechError> {
+    let audio_data = match response_format {
         AudioFormat::Wav => {
             let mut wav_data = Vec::default();
             let header = WavHeader::new(1, sample_rate, 32);
             header
                 .write_header(&mut wav_data)
                 .map_err(SpeechError::Header)?;
-            write_audio_chunk(&mut wav_data, &raw_audio).map_err(SpeechError::Chunk)?;
+            write_audio_chunk(&mut wav_data, raw_audio).map_err(SpeechError::Chunk)?;
 
-            ("audio/wav", wav_data, "WAV")
+            wav_data
         }
         // AudioFormat::Opus => {
         //     let opus_data = pcm_to_opus_ogg(&raw_audio, sample_rate)
@@ -636,44 +1427,46 @@ async fn handle_tts(
         //     ("audio/opus", opus_data, "OPUS")
         // }
         AudioFormat::Mp3 => {
-            let mp3_data =
-                pcm_to_mp3(&raw_audio, sample_rate).map_err(|e| SpeechError::Mp3Conversion(e))?;
-
-            ("audio/mpeg", mp3_data, "MP3")
+            pcm_to_mp3(raw_audio, sample_rate).map_err(|e| SpeechError::Mp3Conversion(e))?
         }
         AudioFormat::Pcm => {
             // For PCM, we return the raw audio data directly
             // Convert f32 samples to 16-bit PCM
-            let mut pcm_data = Vec::with_capacity(raw_audio.len() * 2);
-            for sample in raw_audio {
-                let pcm_sample = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
-                pcm_data.extend_from_slice(&pcm_sample.to_le_bytes());
-            }
-            ("audio/pcm", pcm_data, "PCM")
+            to_pcm16(raw_audio)
         }
         // For now, unsupported formats fall back to MP3
-        _ => {
-            let mp3_data =
-                pcm_to_mp3(&raw_audio, sample_rate).map_err(|e| SpeechError::Mp3Conversion(e))?;
-
-            ("audio/mpeg", mp3_data, "MP3")
-        }
+        _ => pcm_to_mp3(raw_audio, sample_rate).map_err(|e| SpeechError::Mp3Conversion(e))?,
     };
+    Ok(audio_data)
+}
 
-    let colored_request_id = get_colored_request_id_with_relative(&request_id, request_start);
-    info!(
-        "{} TTS non-streaming completed - {} bytes, {} format",
-        colored_request_id,
-        audio_data.len(),
-        format_name
-    );
+/// Samples of a WAV response `encode_response` wrote: a 44-byte header of
+/// 32-bit float mono audio followed by the samples. Other files are `None`.
+fn wav_f32_samples(wav: &[u8]) -> Option<Vec<f32>> {
+    let header = wav.get(..44)?;
+    let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+    let ours = &header[..4] == b"RIFF"
+        && &header[8..12] == b"WAVE"
+        && &header[36..40] == b"data"
+        && u16_at(20) == 3
+        && u16_at(22) == 1
+        && u16_at(34) == 32;
+    ours.then(|| {
+        wav[44..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    })
+}
 
-    Ok(Response::builder()
-        .header(header::CONTENT_TYPE, content_type)
-        .body(audio_data.into())
-        .map_err(|e| {
-            SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?)
+/// Content type and log name of a non-streaming response in `format`.
+/// Formats without an encoder fall back to MP3.
+fn response_content_type(format: &AudioFormat) -> (&'static str, &'static str) {
+    match format {
+        AudioFormat::Wav => ("audio/wav", "WAV"),
+        AudioFormat::Pcm => ("audio/pcm", "PCM"),
+        _ => ("audio/mpeg", "MP3"),
+    }
 }
 
 /// Handle streaming TTS requests with true async processing
@@ -684,19 +1477,144 @@ async fn handle_tts_streaming(
     tts_instances: Vec<TTSKoko>,
     input: String,
     voice: String,
-    response_format: AudioFormat,
+    response_format: Option<AudioFormat>,
     speed: f32,
     initial_silence: Option<usize>,
+    sample_rate: u32,
+    resample_quality: ResampleQuality,
     language: String,
     request_id: String,
     request_start: Instant,
 ) -> Result<Response, SpeechError> {
-    // Streaming implementation: PCM format for optimal performance
-    let content_type = match response_format {
-        AudioFormat::Pcm => "audio/pcm",
-        _ => "audio/pcm", // Force PCM for optimal streaming performance
+    // An explicit WAV or MP3 is streamed as such; everything else, including
+    // the MP3 default, keeps OpenAI's raw PCM
+    let (content_type, format) = match response_format {
+        Some(AudioFormat::Wav) => ("audio/wav", StreamFormat::Wav),
+        Some(AudioFormat::Mp3) => ("audio/mpeg", StreamFormat::Mp3),
+        _ => ("audio/pcm", StreamFormat::Pcm),
     };
 
+    let audio_rx = start_tts_stream(
+        tts_instances,
+        input,
+        voice,
+        format,
+        speed,
+        initial_silence,
+        sample_rate,
+        resample_quality,
+        language,
+        request_id,
+        request_start,
+    )?;
+
+    // No ordering needed - sequential processing guarantees order
+
+    // Create immediate streaming - chunks are already sent in order from TTS processing
+    let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(audio_rx)
+        .map(|(_chunk_id, data)| -> Result<Vec<u8>, std::io::Error> {
+            // Check for termination signal (empty data)
+            if data.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Stream complete",
+                ));
+            }
+            Ok(data)
+        })
+        .take_while(|result| {
+            // Continue until we hit an error (termination signal)
+            std::future::ready(result.is_ok())
+        });
+
+    // Convert to HTTP body with explicit ordering
+    let body = Body::from_stream(stream);
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONNECTION, "keep-alive")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header("X-Accel-Buffering", "no") // Disable nginx buffering
+        .header("Transfer-Encoding", "chunked") // Enable HTTP chunked transfer encoding
+        .header("Access-Control-Allow-Origin", "*") // CORS for browser clients
+        .body(body)
+        .map_err(|e| {
+            SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::Other, e))
+        })?)
+}
+
+/// Stream the speech as server-sent events, for browser clients using plain `fetch`.
+///
+/// Each 16-bit little-endian mono PCM chunk (24 kHz unless `sample_rate` is
+/// set) is sent base64 encoded as a `data:` event, followed by a final
+/// `event: done`. `response_format` and `stream` are ignored.
+async fn handle_tts_sse(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+) -> Result<Response, SpeechError> {
+    let (speech_request, model, request_id, request_start) =
+        read_speech_request(&state, request).await?;
+
+    let SpeechRequest {
+        input,
+        voice,
+        speed,
+        initial_silence,
+        sample_rate,
+        lang_code,
+        ..
+    } = speech_request;
+
+    let voice = voice.unwrap_or_default().to_kokoro_voice();
+    let Speed(speed) = speed.unwrap_or_default();
+    let language = get_language_code(lang_code.as_deref(), &voice);
+    let sample_rate = sample_rate.unwrap_or(TTSKokoInitConfig::default().sample_rate);
+
+    let audio_rx = start_tts_stream(
+        model.tts_instances,
+        input,
+        voice,
+        StreamFormat::Pcm,
+        speed,
+        initial_silence,
+        sample_rate,
+        state.config.stream_resample_quality,
+        language,
+        request_id,
+        request_start,
+    )?;
+
+    let events = tokio_stream::wrappers::UnboundedReceiverStream::new(audio_rx)
+        // Empty data is the termination signal
+        .take_while(|(_chunk_id, data)| std::future::ready(!data.is_empty()))
+        .map(|(_chunk_id, data)| Ok::<_, Infallible>(Event::default().data(BASE64.encode(data))))
+        .chain(futures::stream::once(async {
+            Ok(Event::default().event("done").data("[DONE]"))
+        }));
+
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Start synthesizing `input` in ordered micro-chunks on the worker pool.
+///
+/// Returns the receiving end of the producer: chunks of one stream encoded
+/// as `format` at `sample_rate`, tagged with their order id and terminated by
+/// an empty chunk. Dropping the receiver cancels the remaining synthesis.
+fn start_tts_stream(
+    tts_instances: Vec<TTSKoko>,
+    input: String,
+    voice: String,
+    format: StreamFormat,
+    speed: f32,
+    initial_silence: Option<usize>,
+    sample_rate: u32,
+    resample_quality: ResampleQuality,
+    language: String,
+    request_id: String,
+    request_start: Instant,
+) -> Result<mpsc::UnboundedReceiver<(usize, Vec<u8>)>, SpeechError> {
     // Create worker pool with vector of TTS instances for true parallelism
     let worker_pool = TTSWorkerPool::new(tts_instances);
 
@@ -726,8 +1644,11 @@ async fn handle_tts_streaming(
     let (task_tx, mut task_rx) = mpsc::unbounded_channel::<TTSTask>();
     let (audio_tx, audio_rx) = mpsc::unbounded_channel::<(usize, Vec<u8>)>(); // Tag chunks with order ID
 
-    // Track total bytes transferred
-    let total_bytes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    // Chunks are encoded in order, so the encoder state carries across them
+    let encoder = StreamEncoder::new(format, sample_rate).map_err(SpeechError::Mp3Conversion)?;
+
+    // Track total samples synthesized
+    let total_samples = Arc::new(std::sync::atomic::AtomicUsize::new(0));
 
     // Create session for tracking
     let session = StreamingSession {
@@ -759,9 +1680,12 @@ async fn handle_tts_streaming(
     // Drop the task sender to signal completion
     drop(task_tx);
 
+    // Cancelled once the client disconnects, so queued and multi-chunk work stops early
+    let cancel = CancellationToken::new();
+
     // Windowed parallel processing: allow chunks to process concurrently up to available TTS instances
     let worker_pool_clone = worker_pool.clone();
-    let total_bytes_clone = total_bytes.clone();
+    let total_samples_clone = total_samples.clone();
     let audio_tx_clone = audio_tx.clone();
     let total_chunks_expected = total_chunks;
     tokio::spawn(async move {
@@ -770,13 +1694,37 @@ async fn handle_tts_streaming(
         let mut chunk_counter = 0;
         let mut pending_chunks: BTreeMap<
             usize,
-            tokio::task::JoinHandle<Result<(usize, Vec<u8>), String>>,
+            tokio::task::JoinHandle<Result<(usize, Vec<f32>), String>>,
         > = BTreeMap::new();
+        let mut encoder = Some(encoder);
+        let mut bytes_transferred = 0;
+        // Encode and send the next chunk in order, false once the client is gone
+        let mut send_chunk = |task_id: usize, samples: Vec<f32>| -> bool {
+            let data = match encode_stream_chunk(&mut encoder, &samples) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("Stream encoding failed: {}", e);
+                    return false;
+                }
+            };
+            bytes_transferred += data.len();
+            let sent = data.is_empty() || audio_tx_clone.send((task_id, data)).is_ok();
+            // The empty completion chunk is passed on as the termination signal
+            sent && (!samples.is_empty() || audio_tx_clone.send((task_id, Vec::new())).is_ok())
+        };
         let mut next_to_send = 0;
         let mut chunks_processed = 0;
         let window_size = worker_pool_clone.instance_count(); // Allow chunks to process in parallel up to available TTS instances
 
         loop {
+            // The receiving end is gone when the client disconnected
+            if audio_tx_clone.is_closed() {
+                cancel.cancel();
+            }
+            if cancel.is_cancelled() {
+                break;
+            }
+
             // Receive new tasks while we have window space and tasks are available
             while pending_chunks.len() < window_size {
                 // Use a non-blocking approach but with proper channel closure detection
@@ -784,7 +1732,7 @@ async fn handle_tts_streaming(
                     Ok(task) => {
                         let task_id = task.id;
                         let worker_pool_clone = worker_pool_clone.clone();
-                        let total_bytes_clone = total_bytes_clone.clone();
+                        let total_samples_clone = total_samples_clone.clone();
                         let request_id_clone = request_id.clone();
 
                         // Process chunk with dedicated TTS instance (alternates between instances)
@@ -796,6 +1744,7 @@ async fn handle_tts_streaming(
                         let initial_silence = task.initial_silence;
                         let language = task.language.clone();
                         let chunk_num = chunk_counter;
+                        let cancel = cancel.clone();
 
                         // Spawn parallel processing
                         let handle = tokio::spawn(async move {
@@ -806,7 +1755,7 @@ async fn handle_tts_streaming(
                             }
 
                             let result = tokio::task::spawn_blocking(move || {
-                                let audio_result = tts_instance.tts_raw_audio(
+                                let audio_result = tts_instance.tts_raw_audio_cancellable(
                                     &chunk_text,
                                     &language,
                                     &voice,
@@ -815,6 +1764,7 @@ async fn handle_tts_streaming(
                                     Some(&request_id_clone),
                                     Some(&actual_instance_id),
                                     Some(chunk_num),
+                                    &cancel,
                                 );
 
                                 audio_result
@@ -823,20 +1773,20 @@ async fn handle_tts_streaming(
                             })
                             .await;
 
-                            // Convert audio to PCM
+                            // Convert audio to PCM at the requested rate
                             match result {
                                 Ok(Ok(audio_samples)) => {
-                                    let mut pcm_data = Vec::with_capacity(audio_samples.len() * 2);
-                                    for sample in audio_samples {
-                                        let pcm_sample =
-                                            (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
-                                        pcm_data.extend_from_slice(&pcm_sample.to_le_bytes());
-                                    }
-                                    total_bytes_clone.fetch_add(
-                                        pcm_data.len(),
+                                    let audio_samples = resample_with(
+                                        &audio_samples,
+                                        TTSKokoInitConfig::default().sample_rate,
+                                        sample_rate,
+                                        resample_quality,
+                                    );
+                                    total_samples_clone.fetch_add(
+                                        audio_samples.len(),
                                         std::sync::atomic::Ordering::Relaxed,
                                     );
-                                    Ok((task_id, pcm_data))
+                                    Ok((task_id, audio_samples))
                                 }
                                 Ok(Err(e)) => Err(e),
                                 Err(e) => Err(format!("Task execution error: {:?}", e)),
@@ -861,8 +1811,9 @@ async fn handle_tts_streaming(
             if let Some(handle) = pending_chunks.remove(&next_to_send) {
                 if handle.is_finished() {
                     match handle.await {
-                        Ok(Ok((task_id, pcm_data))) => {
-                            if let Err(_) = audio_tx_clone.send((task_id, pcm_data)) {
+                        Ok(Ok((task_id, samples))) => {
+                            if !send_chunk(task_id, samples) {
+                                cancel.cancel();
                                 break;
                             }
                             next_to_send += 1;
@@ -914,9 +1865,9 @@ async fn handle_tts_streaming(
 
         for (chunk_id, handle) in pending_chunks {
             match handle.await {
-                Ok(Ok((task_id, pcm_data))) => {
+                Ok(Ok((task_id, samples))) => {
                     // Collect all successful chunks regardless of order
-                    remaining_chunks.push((chunk_id, task_id, pcm_data));
+                    remaining_chunks.push((chunk_id, task_id, samples));
                 }
                 Ok(Err(_e)) => {
                     // TTS processing error - still count as processed
@@ -934,71 +1885,59 @@ async fn handle_tts_streaming(
         remaining_chunks.sort_by_key(|(chunk_id, _, _)| *chunk_id);
 
         // Send all remaining chunks in order, preventing data loss
-        for (chunk_id, task_id, pcm_data) in remaining_chunks {
+        for (chunk_id, task_id, samples) in remaining_chunks {
             // Only send chunks that are in the expected sequence (>= next_to_send)
             // This prevents duplicate sends while ensuring no valid chunks are skipped
             if chunk_id >= next_to_send {
-                let _ = audio_tx_clone.send((task_id, pcm_data));
+                let _ = send_chunk(task_id, samples);
                 chunks_processed += 1;
             }
         }
 
         let _session_time = session.start_time.elapsed();
 
+        if cancel.is_cancelled() {
+            let colored_request_id =
+                get_colored_request_id_with_relative(&request_id, request_start);
+            info!(
+                "{} TTS session cancelled - client disconnected after {} of {} chunks",
+                colored_request_id, next_to_send, total_chunks
+            );
+            return;
+        }
+
         // Log completion
-        let bytes_transferred = total_bytes.load(std::sync::atomic::Ordering::Relaxed);
-        // Calculate audio duration: 16-bit PCM (2 bytes per sample) at 24000 Hz
-        let total_samples = bytes_transferred / 2;
-        let duration_seconds = total_samples as f64 / 24000.0;
+        let total_samples = total_samples.load(std::sync::atomic::Ordering::Relaxed);
+        let duration_seconds = total_samples as f64 / sample_rate as f64;
         let colored_request_id = get_colored_request_id_with_relative(&request_id, request_start);
         info!(
-            "{} TTS session completed - {} chunks, {} bytes, {:.1}s audio, PCM format",
-            colored_request_id, total_chunks, bytes_transferred, duration_seconds
+            "{} TTS session completed - {} chunks, {} bytes, {:.1}s audio, {:?} format",
+            colored_request_id, total_chunks, bytes_transferred, duration_seconds, format
         );
 
         // Send termination signal
         let _ = audio_tx.send((total_chunks, vec![])); // Empty data as termination signal
     });
 
-    // No ordering needed - sequential processing guarantees order
-
-    // Create immediate streaming - chunks are already sent in order from TTS processing
-    let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(audio_rx)
-        .map(|(_chunk_id, data)| -> Result<Vec<u8>, std::io::Error> {
-            // Check for termination signal (empty data)
-            if data.is_empty() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::UnexpectedEof,
-                    "Stream complete",
-                ));
-            }
-            Ok(data)
-        })
-        .take_while(|result| {
-            // Continue until we hit an error (termination signal)
-            std::future::ready(result.is_ok())
-        });
-
-    // Convert to HTTP body with explicit ordering
-    let body = Body::from_stream(stream);
+    Ok(audio_rx)
+}
 
-    Ok(Response::builder()
-        .header(header::CONTENT_TYPE, content_type)
-        .header(header::CONNECTION, "keep-alive")
-        .header(header::CACHE_CONTROL, "no-cache")
-        .header("X-Accel-Buffering", "no") // Disable nginx buffering
-        .header("Transfer-Encoding", "chunked") // Enable HTTP chunked transfer encoding
-        .header("Access-Control-Allow-Origin", "*") // CORS for browser clients
-        .body(body)
-        .map_err(|e| {
-            SpeechError::Mp3Conversion(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?)
+/// Bytes of the next ordered stream chunk `samples`. The empty completion
+/// chunk finishes the encoder, flushing what it still buffers.
+fn encode_stream_chunk(
+    encoder: &mut Option<StreamEncoder>,
+    samples: &[f32],
+) -> std::io::Result<Vec<u8>> {
+    if !samples.is_empty() {
+        if let Some(encoder) = encoder.as_mut() {
+            return encoder.encode(samples);
+        }
+    }
+    encoder.take().map_or(Ok(Vec::new()), StreamEncoder::finish)
 }
 
-async fn handle_voices(
-    State((tts_single, _tts_instances)): State<(TTSKoko, Vec<TTSKoko>)>,
-) -> Json<VoicesResponse> {
-    let mut voices = tts_single.get_available_voices();
+async fn handle_voices(State(state): State<AppState>) -> Json<VoicesResponse> {
+    let mut voices = state.default_model.tts_single.get_available_voices();
 
     // Add OpenAI voice names for compatibility
     let openai_voices = vec![
@@ -1021,37 +1960,108 @@ async fn handle_voices(
     Json(VoicesResponse { voices })
 }
 
+/// Check a voice or blend like a speech request would, without synthesizing,
+/// so frontends can validate input as it is typed. Invalid voices are
+/// reported in the body, only unknown models are an error
+async fn handle_validate_voice(
+    State(state): State<AppState>,
+    Json(request): Json<ValidateVoiceRequest>,
+) -> Result<Json<ValidateVoiceResponse>, SpeechError> {
+    let model_name = request.model.as_deref().unwrap_or(OPENAI_MODEL_ALIASES[0]);
+    let model = state
+        .model(model_name)
+        .ok_or_else(|| SpeechError::UnknownModel(model_name.to_string()))?;
+    let voice = request.voice.to_kokoro_voice();
+    let result = match &request.lang_code {
+        Some(lan) => model.tts_single.validate_style_for_language(&voice, lan),
+        None => model.tts_single.validate_style(&voice),
+    };
+    Ok(Json(ValidateVoiceResponse {
+        valid: result.is_ok(),
+        voice,
+        error: result.err().map(|e| e.to_string()),
+    }))
+}
+
+/// Render blend cache counters in the Prometheus text exposition format
+fn render_metrics(stats: &BlendCacheStats) -> String {
+    let metrics: [(&str, &str, &str, f64); 6] = [
+        (
+            "kokoros_blend_cache_hits_total",
+            "counter",
+            "Voice blends served from the blend cache",
+            stats.hits as f64,
+        ),
+        (
+            "kokoros_blend_cache_misses_total",
+            "counter",
+            "Voice blends computed because they were not cached",
+            stats.misses as f64,
+        ),
+        (
+            "kokoros_blend_cache_evictions_total",
+            "counter",
+            "Blends dropped from the cache to make room",
+            stats.evictions as f64,
+        ),
+        (
+            "kokoros_blend_cache_entries",
+            "gauge",
+            "Blends currently cached",
+            stats.entries as f64,
+        ),
+        (
+            "kokoros_blend_cache_capacity",
+            "gauge",
+            "Maximum number of cached blends",
+            stats.capacity as f64,
+        ),
+        (
+            "kokoros_blend_cache_hit_ratio",
+            "gauge",
+            "Share of blend lookups served from the cache",
+            stats.hit_rate(),
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    }
+    out
+}
+
+async fn handle_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render_metrics(&state.blend_cache_stats()),
+    )
+}
+
+fn model_object(id: &str) -> ModelObject {
+    ModelObject {
+        id: id.to_string(),
+        object: "model".to_string(),
+        created: 1686935002,
+        owned_by: "kokoro".to_string(),
+    }
+}
+
 /// Handle /v1/models endpoint
 ///
-/// Returns a static list of models for OpenAI API compatibility.
-/// Note: All models use the same underlying Kokoro TTS engine.
-async fn handle_models() -> Json<ModelsResponse> {
-    let models = vec![
-        ModelObject {
-            id: "tts-1".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        ModelObject {
-            id: "tts-1-hd".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        ModelObject {
-            id: "kokoro".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        ModelObject {
-            id: "gpt-4o-mini-tts".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-    ];
+/// Returns the OpenAI aliases of the default model followed by the named models.
+/// Note: All aliases use the same underlying Kokoro TTS engine.
+async fn handle_models(State(state): State<AppState>) -> Json<ModelsResponse> {
+    let mut named: Vec<&String> = state.named_models.keys().collect();
+    named.sort();
+
+    let models = OPENAI_MODEL_ALIASES
+        .iter()
+        .copied()
+        .chain(named.into_iter().map(String::as_str))
+        .map(model_object)
+        .collect();
 
     Json(ModelsResponse {
         object: "list".to_string(),
@@ -1059,42 +2069,53 @@ async fn handle_models() -> Json<ModelsResponse> {
     })
 }
 
-async fn handle_model(Path(model_id): Path<String>) -> Result<Json<ModelObject>, StatusCode> {
-    let model = match model_id.as_str() {
-        "tts-1" => ModelObject {
-            id: "tts-1".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        "tts-1-hd" => ModelObject {
-            id: "tts-1-hd".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        "kokoro" => ModelObject {
-            id: "kokoro".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        "gpt-4o-mini-tts" => ModelObject {
-            id: "tts-1-hd".to_string(),
-            object: "model".to_string(),
-            created: 1686935002,
-            owned_by: "kokoro".to_string(),
-        },
-        _ => return Err(StatusCode::NOT_FOUND),
-    };
-
-    Ok(Json(model))
+async fn handle_model(
+    State(state): State<AppState>,
+    Path(model_id): Path<String>,
+) -> Result<Json<ModelObject>, StatusCode> {
+    match model_id.as_str() {
+        // Historically reported under the tts-1-hd id
+        "gpt-4o-mini-tts" => Ok(Json(model_object("tts-1-hd"))),
+        id if state.model(id).is_some() => Ok(Json(model_object(id))),
+        _ => Err(StatusCode::NOT_FOUND),
+    }
 }
 
 fn get_colored_request_id_with_relative(request_id: &str, start_time: Instant) -> String {
     kokoros::utils::debug::get_colored_request_id_with_relative(request_id, start_time)
 }
 
+/// Hold a `ConcurrencyLimit` slot for the lifetime of a speech request,
+/// including the whole body of streamed responses
+async fn concurrency_limit_middleware(
+    State(limit): State<ConcurrencyLimit>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let permit = match limit.overflow {
+        OverflowPolicy::Queue => match limit.semaphore.acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => return SpeechError::Overloaded.into_response(),
+        },
+        OverflowPolicy::Reject => match limit.semaphore.try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                debug!("Rejecting speech request, concurrency limit reached");
+                return SpeechError::Overloaded.into_response();
+            }
+        },
+    };
+
+    let (parts, body) = next.run(request).await.into_parts();
+    // The permit is released when the body stream is dropped, i.e. after the
+    // last chunk is sent or the client disconnects
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _held = &permit;
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
 async fn request_id_middleware(
     mut request: axum::extract::Request,
     next: axum::middleware::Next,
@@ -1126,3 +2147,121 @@ async fn request_id_middleware(
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_f32_samples() {
+        let wav = encode_response(&[0.5, -0.25], 24000, &AudioFormat::Wav).unwrap();
+        assert_eq!(wav_f32_samples(&wav), Some(vec![0.5, -0.25]));
+        assert_eq!(wav_f32_samples(&to_pcm16(&[0.5; 40])), None);
+        assert_eq!(wav_f32_samples(b"RIFF"), None);
+    }
+
+    #[test]
+    fn test_audio_json() {
+        let body = audio_json(b"RIFF", "WAV", 16000);
+        assert_eq!(
+            body,
+            serde_json::json!({"audio": "UklGRg==", "format": "wav", "sample_rate": 16000})
+        );
+        let request: SpeechRequest =
+            serde_json::from_str(r#"{"model": "tts-1", "input": "Hi", "encoding": "base64"}"#)
+                .unwrap();
+        assert_eq!(request.encoding, Some(ResponseEncoding::Base64));
+    }
+
+    #[test]
+    fn test_cache_key_header() {
+        let parts = SpeechCacheParts {
+            model: "tts-1",
+            input: "Hi",
+            voice: "af_sky",
+            speed: 1.0,
+            lang_code: "en-us",
+            initial_silence: 0,
+            format: "WAV",
+            sample_rate: 24000,
+        };
+        let key = parts.key();
+        assert_ne!(
+            key,
+            SpeechCacheParts {
+                format: "MP3",
+                ..parts
+            }
+            .key()
+        );
+
+        let response = audio_response(
+            b"RIFF".to_vec(),
+            "audio/wav",
+            "WAV",
+            24000,
+            false,
+            Some(&key),
+        )
+        .unwrap();
+        assert_eq!(response.headers()["x-cache-key"], key.as_str());
+        let response =
+            audio_response(b"RIFF".to_vec(), "audio/wav", "WAV", 24000, true, None).unwrap();
+        assert!(!response.headers().contains_key("x-cache-key"));
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let stats = BlendCacheStats {
+            hits: 3,
+            misses: 1,
+            evictions: 0,
+            entries: 1,
+            capacity: 64,
+        };
+        let text = render_metrics(&stats);
+        assert!(text.contains(
+            "# TYPE kokoros_blend_cache_hits_total counter\nkokoros_blend_cache_hits_total 3\n"
+        ));
+        assert!(text.contains("kokoros_blend_cache_hit_ratio 0.75\n"));
+    }
+
+    #[test]
+    fn test_parse_key_defaults() {
+        let defaults = parse_key_defaults(
+            r#"{"sk-a": {"voice": "af_nicole", "speed": 1.2}, "sk-b": {"speed": 0.9}}"#,
+        )
+        .unwrap();
+        assert_eq!(defaults["sk-a"].voice.as_deref(), Some("af_nicole"));
+        assert_eq!(defaults["sk-b"].voice, None);
+        assert_eq!(defaults["sk-b"].speed, Some(0.9));
+
+        assert!(parse_key_defaults(r#"{"sk-a": {"speed": 0}}"#).is_err());
+        assert!(parse_key_defaults(r#"{"sk-a": {"pitch": 2}}"#).is_err());
+    }
+
+    #[test]
+    fn test_audio_format_from_accept() {
+        assert_eq!(
+            AudioFormat::from_accept("audio/wav"),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(
+            AudioFormat::from_accept("audio/mpeg;q=0.5, audio/L16;rate=24000"),
+            Some(AudioFormat::Pcm)
+        );
+        assert_eq!(
+            AudioFormat::from_accept("audio/ogg, audio/mpeg;q=0.8"),
+            Some(AudioFormat::Mp3)
+        );
+        // Nothing we encode: WAV
+        assert_eq!(
+            AudioFormat::from_accept("audio/ogg"),
+            Some(AudioFormat::Wav)
+        );
+        // Wildcards and missing preferences keep the default
+        assert_eq!(AudioFormat::from_accept("*/*"), None);
+        assert_eq!(AudioFormat::from_accept("audio/*, audio/wav;q=0.1"), None);
+        assert_eq!(AudioFormat::from_accept(""), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Play speech through the default audio device while it is being synthesized.
+//!
+//! Each chunk from `TTSKoko::tts_raw_audio_streaming` is queued on a rodio
+//! sink as soon as it arrives, so playback starts after the first chunk
+//! instead of after the whole text.
+//!
+//! ```text
+//! cargo run --release -p kokoros --example stream_playback -- "Some long text to read"
+//! ```
+//!
+//! With `--no-playback` the chunks are only checked (non-empty, finite
+//! samples) and summarized, which works as a smoke test of the streaming
+//! path on machines without an audio device.
+
+use kokoros::tts::koko::TTSKoko;
+use rodio::{OutputStream, Sink, buffer::SamplesBuffer};
+use std::time::Instant;
+
+const MODEL_PATH: &str = "checkpoints/kokoro-v1.0.onnx";
+const VOICES_PATH: &str = "data/voices-v1.0.bin";
+const DEFAULT_TEXT: &str = "Streaming synthesis hands over audio one chunk at a time. \
+    Playback starts as soon as the first chunk is ready, while the rest of the text \
+    is still being synthesized.";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut no_playback = false;
+    let mut words = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-playback" => no_playback = true,
+            _ => words.push(arg),
+        }
+    }
+    let text = if words.is_empty() {
+        DEFAULT_TEXT.to_string()
+    } else {
+        words.join(" ")
+    };
+
+    let tts = TTSKoko::new_blocking(MODEL_PATH, VOICES_PATH)?;
+    let sample_rate = tts.sample_rate();
+
+    // The stream must outlive the sink, or playback stops
+    let output = if no_playback {
+        None
+    } else {
+        let (stream, handle) = OutputStream::try_default()?;
+        Some((stream, Sink::try_new(&handle)?))
+    };
+
+    let start = Instant::now();
+    let mut chunks = 0;
+    let mut total_samples = 0;
+    tts.tts_raw_audio_streaming(
+        &text,
+        "en-us",
+        "af_sky",
+        1.0,
+        None,
+        None,
+        None,
+        None,
+        |chunk| {
+            if chunk.is_empty() {
+                return Err(format!("chunk {} is empty", chunks).into());
+            }
+            if let Some(i) = chunk.iter().position(|s| !s.is_finite()) {
+                return Err(format!("chunk {} has a non-finite sample at {}", chunks, i).into());
+            }
+            if chunks == 0 {
+                eprintln!("First chunk after {:?}", start.elapsed());
+            }
+            chunks += 1;
+            total_samples += chunk.len();
+            if let Some((_, sink)) = &output {
+                sink.append(SamplesBuffer::new(1, sample_rate, chunk));
+            }
+            Ok(())
+        },
+    )?;
+
+    eprintln!(
+        "Synthesized {} chunks, {:.2}s of audio in {:?}",
+        chunks,
+        total_samples as f32 / sample_rate as f32,
+        start.elapsed()
+    );
+    if chunks == 0 {
+        return Err("no audio produced".into());
+    }
+
+    if let Some((_, sink)) = &output {
+        sink.sleep_until_end();
+    }
+    Ok(())
+}
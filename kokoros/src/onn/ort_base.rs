@@ -2,34 +2,127 @@ use ort::ep;
 use ort::logging::LogLevel;
 use ort::session::Session;
 use ort::session::builder::SessionBuilder;
+use ort::value::ValueType;
+
+/// Name, element type and shape of a model input or output
+#[derive(Debug, Clone)]
+pub struct TensorInfo {
+    pub name: String,
+    /// Element type, or the value type for non-tensor values
+    pub dtype: String,
+    /// Dimensions, with -1 for dynamic ones. `None` for non-tensor values
+    pub shape: Option<Vec<i64>>,
+}
+
+impl std::fmt::Display for TensorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.shape {
+            Some(shape) => {
+                let dims: Vec<String> = shape
+                    .iter()
+                    .map(|&d| {
+                        if d < 0 {
+                            "?".to_string()
+                        } else {
+                            d.to_string()
+                        }
+                    })
+                    .collect();
+                write!(f, "{}: {} [{}]", self.name, self.dtype, dims.join(", "))
+            }
+            None => write!(f, "{}: {}", self.name, self.dtype),
+        }
+    }
+}
+
+fn tensor_info(name: &str, dtype: &ValueType) -> TensorInfo {
+    TensorInfo {
+        name: name.to_string(),
+        dtype: match dtype.tensor_type() {
+            Some(ty) => format!("{:?}", ty),
+            None => dtype.to_string(),
+        },
+        shape: dtype.tensor_shape().map(|shape| shape.to_vec()),
+    }
+}
+
+/// Execution provider sessions are created with, `cuda` when the CUDA
+/// provider is enabled at build time and `cpu` otherwise
+pub fn execution_provider() -> &'static str {
+    if cfg!(feature = "cuda") {
+        "cuda"
+    } else {
+        "cpu"
+    }
+}
+
+/// Memory settings of an ONNX Runtime session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionMemory {
+    /// Memory arena of the execution provider in megabytes. On CUDA a
+    /// positive value caps the arena and makes it grow only by what is
+    /// requested; 0 disables the CPU arena, which ONNX Runtime can't cap.
+    /// `None` keeps ONNX Runtime's default arena, which grows as needed and
+    /// never shrinks
+    pub arena_mb: Option<usize>,
+    /// Plan allocations ahead from the shapes of earlier runs (ONNX Runtime's
+    /// default). Faster, but keeps extra memory around for varying input lengths
+    pub memory_pattern: bool,
+}
+
+impl Default for SessionMemory {
+    fn default() -> Self {
+        Self {
+            arena_mb: None,
+            memory_pattern: true,
+        }
+    }
+}
 
 pub trait OrtBase {
     fn load_model(&mut self, model_path: String) -> Result<(), String> {
+        self.load_model_on(model_path, false, &SessionMemory::default())
+    }
+
+    /// Load the model, on the CPU only when `cpu_only` is set even if a GPU
+    /// execution provider is enabled at build time
+    fn load_model_on(
+        &mut self,
+        model_path: String,
+        cpu_only: bool,
+        memory: &SessionMemory,
+    ) -> Result<(), String> {
+        let cpu = ep::CPU::default().with_arena_allocator(memory.arena_mb != Some(0));
+        let on_cpu = cpu_only || !cfg!(feature = "cuda");
+        if on_cpu && memory.arena_mb.is_some_and(|mb| mb > 0) {
+            tracing::warn!(
+                "The CPU memory arena can't be capped, only disabled with an arena size of 0"
+            );
+        }
+
         #[cfg(feature = "cuda")]
-        let providers = [ep::CUDA::default().build()];
+        let providers = if on_cpu {
+            [cpu.build()]
+        } else {
+            let cuda = match memory.arena_mb {
+                Some(mb) if mb > 0 => ep::CUDA::default()
+                    .with_memory_limit(mb * 1024 * 1024)
+                    .with_arena_extend_strategy(ep::ArenaExtendStrategy::SameAsRequested),
+                _ => ep::CUDA::default(),
+            };
+            [cuda.build()]
+        };
 
         #[cfg(not(feature = "cuda"))]
-        let providers = [ep::CPU::default().build()];
+        let providers = [cpu.build()];
 
         match SessionBuilder::new() {
             Ok(builder) => {
-                // If CUDA feature is enabled, register CUDA EP; otherwise use defaults
-                let builder = {
-                    #[cfg(feature = "cuda")]
-                    {
-                        let builder = builder
-                            .with_execution_providers(providers)
-                            .map_err(|e| format!("Failed to build session: {}", e))?;
-                        Ok::<_, String>(builder)
-                    }
-
-                    #[cfg(not(feature = "cuda"))]
-                    {
-                        Ok::<_, String>(builder)
-                    }
-                }?;
-
                 let session = builder
+                    .with_execution_providers(providers)
+                    .map_err(|e| format!("Failed to build session: {}", e))?
+                    .with_memory_pattern(memory.memory_pattern)
+                    .map_err(|e| format!("Failed to set memory pattern: {}", e))?
                     .with_log_level(LogLevel::Warning)
                     .map_err(|e| format!("Failed to set log level: {}", e))?
                     .commit_from_file(model_path)
@@ -62,6 +155,22 @@ pub trait OrtBase {
         }
     }
 
+    /// Inputs and outputs of the loaded session, `None` before a model is loaded
+    fn io_info(&self) -> Option<(Vec<TensorInfo>, Vec<TensorInfo>)> {
+        let session = self.sess()?;
+        let inputs = session
+            .inputs()
+            .iter()
+            .map(|input| tensor_info(input.name(), input.dtype()))
+            .collect();
+        let outputs = session
+            .outputs()
+            .iter()
+            .map(|output| tensor_info(output.name(), output.dtype()))
+            .collect();
+        Some((inputs, outputs))
+    }
+
     fn set_sess(&mut self, sess: Session);
     fn sess(&self) -> Option<&Session>;
 }
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::ort_base;
 use crate::utils::debug::format_debug_prefix;
@@ -6,9 +7,9 @@ use model_schema::v1_0_timestamped::DURATIONS;
 use ndarray::{ArrayBase, IxDyn, OwnedRepr};
 use ort::{
     session::{Session, SessionInputValue, SessionInputs},
-    value::{Tensor, Value},
+    value::{DynValue, Tensor, Value},
 };
-use ort_base::OrtBase;
+use ort_base::{OrtBase, SessionMemory};
 
 mod model_schema {
     pub const STYLE: &str = "style";
@@ -27,6 +28,91 @@ mod model_schema {
     }
 }
 
+/// Names of the language id input some multilingual Kokoro exports take next
+/// to tokens, style and speed, see `lang::kokoro_language_id`
+const LANGUAGE_INPUTS: &[&str] = &["language", "lang", "language_id", "lang_id"];
+
+/// Names of the energy/expressiveness input some Kokoro variants take, a
+/// scalar in 0.0 to 1.0 fed like `speed`
+const EXPRESSIVENESS_INPUTS: &[&str] = &["expressiveness", "energy"];
+
+/// Expressiveness fed to models with such an input when none is set
+pub const DEFAULT_EXPRESSIVENESS: f32 = 0.5;
+
+/// Warn about an ignored expressiveness once, not for every chunk and instance
+static EXPRESSIVENESS_IGNORED_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Outputs `infer` reads. Any other output is an intermediate representation
+/// the graph was exported with, see `OrtKoko::feature_output`
+const KNOWN_OUTPUTS: &[&str] = &["audio", "waveform", "waveforms", DURATIONS];
+
+/// Numeric precision of the loaded model's I/O.
+///
+/// Quantized (INT8) exports usually keep f32 inputs and outputs, but some emit
+/// integer audio. With `Auto` or `Int8`, integer outputs are dequantized to f32
+/// in [-1.0, 1.0]; `Fp32` insists on f32 outputs and errors otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModelPrecision {
+    #[default]
+    Auto,
+    Fp32,
+    Int8,
+}
+
+impl std::str::FromStr for ModelPrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ModelPrecision::Auto),
+            "fp32" | "f32" => Ok(ModelPrecision::Fp32),
+            "int8" | "i8" | "uint8" | "u8" => Ok(ModelPrecision::Int8),
+            _ => Err(format!(
+                "unknown model precision '{}', expected auto, fp32 or int8",
+                s
+            )),
+        }
+    }
+}
+
+/// Extract an output tensor as f32, dequantizing integer outputs of quantized models
+fn extract_f32_tensor(
+    value: &DynValue,
+    precision: ModelPrecision,
+) -> Result<(Vec<usize>, Vec<f32>), Box<dyn std::error::Error>> {
+    if let Ok((shape, data)) = value.try_extract_tensor::<f32>() {
+        let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
+        return Ok((shape_vec, data.to_vec()));
+    }
+
+    if precision == ModelPrecision::Fp32 {
+        return Err(
+            "expected an f32 output tensor; use --model-precision int8 for quantized models".into(),
+        );
+    }
+
+    if let Ok((shape, data)) = value.try_extract_tensor::<i16>() {
+        let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
+        return Ok((
+            shape_vec,
+            data.iter().map(|&x| x as f32 / 32768.0).collect(),
+        ));
+    }
+    if let Ok((shape, data)) = value.try_extract_tensor::<i8>() {
+        let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
+        return Ok((shape_vec, data.iter().map(|&x| x as f32 / 128.0).collect()));
+    }
+    if let Ok((shape, data)) = value.try_extract_tensor::<u8>() {
+        let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
+        return Ok((
+            shape_vec,
+            data.iter().map(|&x| (x as f32 - 128.0) / 128.0).collect(),
+        ));
+    }
+
+    Err("unsupported output tensor type (expected f32, i16, i8 or u8)".into())
+}
+
 pub enum ModelStrategy {
     Standard(Session),
     Timestamped(Session),
@@ -34,6 +120,19 @@ pub enum ModelStrategy {
 
 pub struct OrtKoko {
     inner: Option<ModelStrategy>,
+    precision: ModelPrecision,
+    /// Model file, kept to reload the session on the CPU
+    model_path: String,
+    /// Reload on the CPU when the execution provider fails, see `new_with_fallback`
+    provider_fallback: bool,
+    /// The session runs on the CPU provider, so there is nothing to fall back to
+    on_cpu: bool,
+    /// Arena and memory pattern settings, kept for the CPU fallback
+    memory: SessionMemory,
+    /// Name of the graph's language id input, if it has one
+    language_input: Option<String>,
+    /// Name of the graph's expressiveness input, if it has one
+    expressiveness_input: Option<String>,
 }
 
 impl ModelStrategy {
@@ -55,8 +154,27 @@ impl ModelStrategy {
 impl OrtBase for OrtKoko {
     fn set_sess(&mut self, sess: Session) {
         let output_count = sess.outputs().len();
+        let has_durations = sess.outputs().iter().any(|o| o.name() == DURATIONS);
+        self.language_input = sess
+            .inputs()
+            .iter()
+            .map(|i| i.name())
+            .find(|name| LANGUAGE_INPUTS.contains(name))
+            .map(str::to_string);
+        if let Some(name) = &self.language_input {
+            tracing::info!("OrtKoko: model takes a language id input '{}'", name);
+        }
+        self.expressiveness_input = sess
+            .inputs()
+            .iter()
+            .map(|i| i.name())
+            .find(|name| EXPRESSIVENESS_INPUTS.contains(name))
+            .map(str::to_string);
+        if let Some(name) = &self.expressiveness_input {
+            tracing::info!("OrtKoko: model takes an expressiveness input '{}'", name);
+        }
 
-        let strategy = if output_count > 1 {
+        let strategy = if has_durations {
             tracing::info!(
                 "OrtKoko: Timestamped backend activated ({} outputs)",
                 output_count
@@ -82,35 +200,222 @@ impl OrtBase for OrtKoko {
 }
 impl OrtKoko {
     pub fn new(model_path: String) -> Result<Self, String> {
-        let mut instance = OrtKoko { inner: None };
-        instance.load_model(model_path)?;
+        Self::new_with_precision(model_path, ModelPrecision::Auto)
+    }
+
+    pub fn new_with_precision(
+        model_path: String,
+        precision: ModelPrecision,
+    ) -> Result<Self, String> {
+        Self::new_with_fallback(model_path, precision, false)
+    }
+
+    /// With `provider_fallback`, a GPU execution provider that fails to
+    /// initialize the session, or later fails to run it (e.g. for a missing
+    /// op), is replaced by a CPU session instead of failing synthesis
+    pub fn new_with_fallback(
+        model_path: String,
+        precision: ModelPrecision,
+        provider_fallback: bool,
+    ) -> Result<Self, String> {
+        Self::new_with_options(
+            model_path,
+            precision,
+            provider_fallback,
+            SessionMemory::default(),
+        )
+    }
+
+    /// `new_with_fallback` with the memory settings of the session
+    pub fn new_with_options(
+        model_path: String,
+        precision: ModelPrecision,
+        provider_fallback: bool,
+        memory: SessionMemory,
+    ) -> Result<Self, String> {
+        let mut instance = OrtKoko {
+            inner: None,
+            precision,
+            model_path: model_path.clone(),
+            provider_fallback,
+            on_cpu: !cfg!(feature = "cuda"),
+            memory,
+            language_input: None,
+            expressiveness_input: None,
+        };
+        match instance.load_model_on(model_path, false, &memory) {
+            Ok(()) => {}
+            Err(e) if provider_fallback && !instance.on_cpu => {
+                instance.fall_back_to_cpu(&e)?;
+            }
+            Err(e) => return Err(e),
+        }
+        if precision != ModelPrecision::Auto {
+            tracing::info!("OrtKoko: model precision hint {:?}", precision);
+        }
         Ok(instance)
     }
 
+    /// An instance without a session, for callers that never run inference
+    pub fn uninitialized() -> Self {
+        OrtKoko {
+            inner: None,
+            precision: ModelPrecision::Auto,
+            model_path: String::new(),
+            provider_fallback: false,
+            on_cpu: true,
+            memory: SessionMemory::default(),
+            language_input: None,
+            expressiveness_input: None,
+        }
+    }
+
+    /// Replace the session with one on the CPU provider after `error`
+    fn fall_back_to_cpu(&mut self, error: &dyn std::fmt::Display) -> Result<(), String> {
+        tracing::warn!(
+            "OrtKoko: execution provider failed ({}), falling back to the CPU provider. \
+             Synthesis continues, but slower",
+            error
+        );
+        let memory = self.memory;
+        self.load_model_on(self.model_path.clone(), true, &memory)
+            .map_err(|e| format!("CPU fallback failed too: {}", e))?;
+        self.on_cpu = true;
+        Ok(())
+    }
+
     pub fn strategy(&self) -> Option<&ModelStrategy> {
         self.inner.as_ref()
     }
 
+    /// Name of the first output that is neither audio nor durations, e.g. the
+    /// mel spectrogram fed to the vocoder when the graph was exported with it
+    /// as an extra output. Stock Kokoro exports have none.
+    pub fn feature_output(&self) -> Option<String> {
+        self.sess()?
+            .outputs()
+            .iter()
+            .map(|o| o.name().to_string())
+            .find(|name| !KNOWN_OUTPUTS.contains(&name.as_str()))
+    }
+
+    /// Run the model and return the shape and data of `feature_output`.
+    /// Falls back to the CPU provider like `infer`
+    pub fn infer_features(
+        &mut self,
+        tokens: &[Vec<i64>],
+        styles: &[Vec<f32>],
+        speed: f32,
+        expressiveness: Option<f32>,
+        language_id: Option<i64>,
+    ) -> Result<(Vec<usize>, Vec<f32>), Box<dyn std::error::Error>> {
+        let output = self.feature_output().ok_or(
+            "the model has no intermediate feature output; re-export the ONNX graph \
+             with the vocoder input (e.g. the mel spectrogram) as an extra output",
+        )?;
+        if !self.provider_fallback || self.on_cpu {
+            return self.run_features(&output, tokens, styles, speed, expressiveness, language_id);
+        }
+        match self.run_features(&output, tokens, styles, speed, expressiveness, language_id) {
+            Ok(features) => Ok(features),
+            Err(e) => {
+                self.fall_back_to_cpu(&e)?;
+                self.run_features(&output, tokens, styles, speed, expressiveness, language_id)
+            }
+        }
+    }
+
+    fn run_features(
+        &mut self,
+        output: &str,
+        tokens: &[Vec<i64>],
+        styles: &[Vec<f32>],
+        speed: f32,
+        expressiveness: Option<f32>,
+        language_id: Option<i64>,
+    ) -> Result<(Vec<usize>, Vec<f32>), Box<dyn std::error::Error>> {
+        let precision = self.precision;
+        let language = self.language_input(language_id)?;
+        let expressiveness = self.expressiveness_input(expressiveness);
+        let strategy = self.inner.as_mut().ok_or("Session is not initialized.")?;
+        let inputs = Self::prepare_inputs(
+            strategy.tokens_key(),
+            tokens,
+            styles,
+            speed,
+            language,
+            expressiveness,
+        )?;
+        let sess = match strategy {
+            ModelStrategy::Standard(sess) | ModelStrategy::Timestamped(sess) => sess,
+        };
+        let outputs = sess.run(SessionInputs::from(inputs))?;
+        extract_f32_tensor(&outputs[output], precision)
+            .map_err(|e| format!("Could not read feature output '{}': {}", output, e).into())
+    }
+
+    /// Name and value of the language id input, for models that have one.
+    /// Errors if the model needs an id but `language_id` is `None`
+    fn language_input(
+        &self,
+        language_id: Option<i64>,
+    ) -> Result<Option<(String, i64)>, Box<dyn std::error::Error>> {
+        match (&self.language_input, language_id) {
+            (None, _) => Ok(None),
+            (Some(name), Some(id)) => Ok(Some((name.clone(), id))),
+            (Some(name), None) => Err(format!(
+                "the model takes a language id ('{}' input), but the language has none; \
+                 use one of the Kokoro languages (en-us, en-gb, es, fr-fr, hi, it, ja, pt-br, cmn)",
+                name
+            )
+            .into()),
+        }
+    }
+
+    /// Name and value of the expressiveness input, for models that have one,
+    /// falling back to `DEFAULT_EXPRESSIVENESS`. Models without one ignore
+    /// `expressiveness` with a warning
+    fn expressiveness_input(&self, expressiveness: Option<f32>) -> Option<(String, f32)> {
+        match (&self.expressiveness_input, expressiveness) {
+            (Some(name), value) => Some((name.clone(), value.unwrap_or(DEFAULT_EXPRESSIVENESS))),
+            (None, Some(value)) => {
+                if !EXPRESSIVENESS_IGNORED_WARNED.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(
+                        "OrtKoko: the model has no expressiveness input ({}), ignoring expressiveness {}",
+                        EXPRESSIVENESS_INPUTS.join(" or "),
+                        value
+                    );
+                }
+                None
+            }
+            (None, None) => None,
+        }
+    }
+
     fn prepare_inputs(
         tokens_key: &'static str,
-        tokens: Vec<Vec<i64>>,
-        styles: Vec<Vec<f32>>,
+        tokens: &[Vec<i64>],
+        styles: &[Vec<f32>],
         speed: f32,
+        language: Option<(String, i64)>,
+        expressiveness: Option<(String, f32)>,
     ) -> Result<Vec<(Cow<'static, str>, SessionInputValue<'static>)>, Box<dyn std::error::Error>>
     {
         let shape = [tokens.len(), tokens[0].len()];
-        let tokens_tensor =
-            Tensor::from_array((shape, tokens.into_iter().flatten().collect::<Vec<i64>>()))?;
+        let tokens_tensor = Tensor::from_array((
+            shape,
+            tokens.iter().flatten().copied().collect::<Vec<i64>>(),
+        ))?;
 
         let shape_style = [styles.len(), styles[0].len()];
         let style_tensor = Tensor::from_array((
             shape_style,
-            styles.into_iter().flatten().collect::<Vec<f32>>(),
+            styles.iter().flatten().copied().collect::<Vec<f32>>(),
         ))?;
 
         let speed_tensor = Tensor::from_array(([1], vec![speed]))?;
 
-        Ok(vec![
+        let mut inputs = vec![
             (
                 Cow::Borrowed(tokens_key),
                 SessionInputValue::Owned(Value::from(tokens_tensor)),
@@ -123,14 +428,82 @@ impl OrtKoko {
                 Cow::Borrowed(model_schema::SPEED),
                 SessionInputValue::Owned(Value::from(speed_tensor)),
             ),
-        ])
+        ];
+        if let Some((name, id)) = language {
+            let language_tensor = Tensor::from_array(([1], vec![id]))?;
+            inputs.push((
+                Cow::Owned(name),
+                SessionInputValue::Owned(Value::from(language_tensor)),
+            ));
+        }
+        if let Some((name, value)) = expressiveness {
+            let expressiveness_tensor = Tensor::from_array(([1], vec![value]))?;
+            inputs.push((
+                Cow::Owned(name),
+                SessionInputValue::Owned(Value::from(expressiveness_tensor)),
+            ));
+        }
+        Ok(inputs)
     }
 
     pub fn infer(
         &mut self,
-        tokens: Vec<Vec<i64>>,
-        styles: Vec<Vec<f32>>,
+        tokens: &[Vec<i64>],
+        styles: &[Vec<f32>],
+        speed: f32,
+        expressiveness: Option<f32>,
+        language_id: Option<i64>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+    ) -> Result<(ArrayBase<OwnedRepr<f32>, IxDyn>, Option<Vec<f32>>), Box<dyn std::error::Error>>
+    {
+        if !self.provider_fallback || self.on_cpu {
+            return self.run_inference(
+                tokens,
+                styles,
+                speed,
+                expressiveness,
+                language_id,
+                request_id,
+                instance_id,
+                chunk_number,
+            );
+        }
+        match self.run_inference(
+            tokens,
+            styles,
+            speed,
+            expressiveness,
+            language_id,
+            request_id,
+            instance_id,
+            chunk_number,
+        ) {
+            Ok(output) => Ok(output),
+            Err(e) => {
+                self.fall_back_to_cpu(&e)?;
+                self.run_inference(
+                    tokens,
+                    styles,
+                    speed,
+                    expressiveness,
+                    language_id,
+                    request_id,
+                    instance_id,
+                    chunk_number,
+                )
+            }
+        }
+    }
+
+    fn run_inference(
+        &mut self,
+        tokens: &[Vec<i64>],
+        styles: &[Vec<f32>],
         speed: f32,
+        expressiveness: Option<f32>,
+        language_id: Option<i64>,
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
@@ -147,34 +520,39 @@ impl OrtKoko {
             tokens.len()
         );
 
+        let precision = self.precision;
+        let language = self.language_input(language_id)?;
+        let expressiveness = self.expressiveness_input(expressiveness);
         let strategy = self.inner.as_mut().ok_or("Session is not initialized.")?;
         let audio_key = strategy.audio_key();
         let tokens_key = strategy.tokens_key();
-        let inputs = Self::prepare_inputs(tokens_key, tokens.clone(), styles, speed)?;
+        let inputs =
+            Self::prepare_inputs(tokens_key, tokens, styles, speed, language, expressiveness)?;
         match strategy {
             ModelStrategy::Standard(sess) => {
                 let outputs = sess.run(SessionInputs::from(inputs))?;
 
-                let (shape, data) = outputs[audio_key]
-                    .try_extract_tensor::<f32>()
-                    .or_else(|_| outputs["waveforms"].try_extract_tensor::<f32>())
-                    .map_err(|_| "Standard Model: Could not find 'audio' output")?;
+                let (shape_vec, data) = extract_f32_tensor(&outputs[audio_key], precision)
+                    .or_else(|_| extract_f32_tensor(&outputs["waveforms"], precision))
+                    .map_err(|e| format!("Standard Model: Could not read 'audio' output: {}", e))?;
 
-                let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
-                let audio_array = ArrayBase::from_shape_vec(shape_vec, data.to_vec())?;
+                let audio_array = ArrayBase::from_shape_vec(shape_vec, data)?;
 
                 Ok((audio_array, None))
             }
             ModelStrategy::Timestamped(sess) => {
                 let outputs = sess.run(SessionInputs::from(inputs))?;
 
-                let (shape, data) = outputs[audio_key]
-                    .try_extract_tensor::<f32>()
-                    .or_else(|_| outputs["audio"].try_extract_tensor::<f32>())
-                    .map_err(|_| "Timestamped Model: Could not find 'waveforms' or 'audio'")?;
+                let (shape_vec, data) = extract_f32_tensor(&outputs[audio_key], precision)
+                    .or_else(|_| extract_f32_tensor(&outputs["audio"], precision))
+                    .map_err(|e| {
+                        format!(
+                            "Timestamped Model: Could not read 'waveforms' or 'audio': {}",
+                            e
+                        )
+                    })?;
 
-                let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
-                let audio_array = ArrayBase::from_shape_vec(shape_vec, data.to_vec())?;
+                let audio_array = ArrayBase::from_shape_vec(shape_vec, data)?;
 
                 let durations_vec = outputs[DURATIONS]
                     .try_extract_tensor::<f32>()
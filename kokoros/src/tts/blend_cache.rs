@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Hit and size counters of a `BlendCache`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlendCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room for new ones
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl BlendCacheStats {
+    /// Sum of the counters of several caches, e.g. one per TTS instance
+    pub fn sum<'a>(stats: impl IntoIterator<Item = &'a BlendCacheStats>) -> Self {
+        stats.into_iter().fold(Self::default(), |acc, s| Self {
+            hits: acc.hits + s.hits,
+            misses: acc.misses + s.misses,
+            evictions: acc.evictions + s.evictions,
+            entries: acc.entries + s.entries,
+            capacity: acc.capacity + s.capacity,
+        })
+    }
+
+    /// Share of lookups served from the cache, 0 before the first lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Least-recently-used cache of blended voice tables, keyed by the blend
+/// spec, so servers seeing many different blends bound the memory they keep.
+/// A capacity of 0 disables caching.
+pub struct BlendCache<K, V> {
+    capacity: usize,
+    entries: Mutex<LruEntries<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+struct LruEntries<K, V> {
+    /// Value and the tick of its last use
+    map: HashMap<K, (V, u64)>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> BlendCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(LruEntries {
+                map: HashMap::new(),
+                tick: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Look up `key`, computing and inserting the value on a miss. Errors of
+    /// `compute` are returned and nothing is cached.
+    pub fn get_or_try_insert<E>(
+        &self,
+        key: &K,
+        compute: impl FnOnce() -> Result<V, E>,
+    ) -> Result<V, E> {
+        if !self.is_enabled() {
+            return compute();
+        }
+        {
+            let mut lru = self.entries.lock().unwrap();
+            lru.tick += 1;
+            let tick = lru.tick;
+            if let Some((value, last_used)) = lru.map.get_mut(key) {
+                *last_used = tick;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(value.clone());
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Computed without the lock, so other blends aren't held up
+        let value = compute()?;
+
+        let mut lru = self.entries.lock().unwrap();
+        if !lru.map.contains_key(key) && lru.map.len() >= self.capacity {
+            // Linear scan; capacities are small next to the cost of a blend
+            let oldest = lru
+                .map
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                lru.map.remove(&oldest);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.map.insert(key.clone(), (value.clone(), tick));
+        Ok(value)
+    }
+
+    pub fn stats(&self) -> BlendCacheStats {
+        BlendCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().map.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_cache_lru() {
+        let cache: BlendCache<String, usize> = BlendCache::new(2);
+        let get = |key: &str| {
+            cache
+                .get_or_try_insert(&key.to_string(), || Ok::<_, ()>(key.len()))
+                .unwrap()
+        };
+
+        get("a");
+        get("bb");
+        get("a"); // "bb" is now the least recently used
+        get("ccc");
+        assert_eq!(get("a"), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 3, 1));
+        assert_eq!(stats.entries, 2);
+
+        // "bb" was evicted and is computed again
+        get("bb");
+        assert_eq!(cache.stats().misses, 4);
+        assert!((cache.stats().hit_rate() - 2.0 / 6.0).abs() < 1e-9);
+
+        let failing = cache.get_or_try_insert(&"x".to_string(), || Err("bad blend"));
+        assert_eq!(failing, Err("bad blend"));
+        assert_eq!(cache.stats().entries, 2);
+
+        let disabled: BlendCache<String, usize> = BlendCache::new(0);
+        disabled
+            .get_or_try_insert(&"a".to_string(), || Ok::<_, ()>(1))
+            .unwrap();
+        assert_eq!(disabled.stats(), BlendCacheStats::default());
+    }
+}
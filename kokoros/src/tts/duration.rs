@@ -0,0 +1,183 @@
+use std::path::Path;
+
+/// IPA length mark, lengthens the preceding sound
+const LENGTH_MARK: char = 'ː';
+
+const VOWELS: &str = "aeiouyɑɐɒæəɘɚɛɜɝɞɨɪɯɵøœɶɔʊʉʌɤʏᵻ";
+
+// Stress, length and secondary articulation marks: neither vowels nor consonants
+const MODIFIERS: &str = "ˈˌːˑʼʴʰʱʲʷˠˤ˞";
+
+/// Factors at or above this lengthen a class, at or below `SHORTEN_AT` shorten it
+const LENGTHEN_AT: f32 = 1.25;
+const SHORTEN_AT: f32 = 0.8;
+
+/// Lengthening from each added length mark, so 1.5 adds one and 2.0 two
+const LENGTH_MARK_STEP: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PhonemeClass {
+    Vowel,
+    Consonant,
+}
+
+fn class_of(c: char) -> Option<PhonemeClass> {
+    if VOWELS.contains(c) {
+        Some(PhonemeClass::Vowel)
+    } else if c.is_alphabetic() && !MODIFIERS.contains(c) {
+        Some(PhonemeClass::Consonant)
+    } else {
+        None
+    }
+}
+
+/// Per-class duration multipliers, e.g. `vowel=1.5,consonant=1.0` for a drawl.
+///
+/// The stock Kokoro ONNX export fuses the duration predictor with the decoder,
+/// so predicted durations can't be rescaled between the two. The profile is
+/// therefore applied to the phoneme sequence before tokenization: classes with
+/// a factor >= 1.25 get IPA length marks (ː) after each run of that class, one
+/// per 0.5 above 1.0 (rounded, at least one), and classes with a factor <= 0.8
+/// lose their existing length marks, whatever the factor. Factors in between
+/// would have no effect, so only 1.0 is accepted there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DurationProfile {
+    pub vowel: f32,
+    pub consonant: f32,
+}
+
+impl Default for DurationProfile {
+    fn default() -> Self {
+        Self {
+            vowel: 1.0,
+            consonant: 1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for DurationProfile {
+    type Err = String;
+
+    /// Parse `class=factor` pairs separated by commas or newlines
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profile = DurationProfile::default();
+        for entry in s.split([',', '\n']) {
+            let entry = entry.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let (class, factor) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected class=factor, got '{}'", entry))?;
+            let factor: f32 = factor
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid duration factor '{}'", factor.trim()))?;
+            if !(0.1..=4.0).contains(&factor) {
+                return Err(format!(
+                    "duration factor {} out of range (0.1 to 4.0)",
+                    factor
+                ));
+            }
+            if factor > SHORTEN_AT && factor < LENGTHEN_AT && factor != 1.0 {
+                return Err(format!(
+                    "duration factor {} has no effect, use 1.0, at most {} or at least {}",
+                    factor, SHORTEN_AT, LENGTHEN_AT
+                ));
+            }
+            match class.trim().to_lowercase().as_str() {
+                "vowel" | "vowels" => profile.vowel = factor,
+                "consonant" | "consonants" => profile.consonant = factor,
+                other => {
+                    return Err(format!(
+                        "unknown phoneme class '{}', expected vowel or consonant",
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(profile)
+    }
+}
+
+impl DurationProfile {
+    /// Parse a profile given either inline (`vowel=1.5`) or as a path to a file
+    /// containing one `class=factor` pair per line
+    pub fn load(spec: &str) -> Result<Self, String> {
+        if Path::new(spec).is_file() {
+            let contents = std::fs::read_to_string(spec)
+                .map_err(|e| format!("failed to read duration profile {}: {}", spec, e))?;
+            contents.parse()
+        } else {
+            spec.parse()
+        }
+    }
+
+    fn factor(&self, class: PhonemeClass) -> f32 {
+        match class {
+            PhonemeClass::Vowel => self.vowel,
+            PhonemeClass::Consonant => self.consonant,
+        }
+    }
+
+    /// Length marks added after each run of `class`
+    fn length_marks(&self, class: PhonemeClass) -> usize {
+        let factor = self.factor(class);
+        if factor < LENGTHEN_AT {
+            return 0;
+        }
+        ((factor - 1.0) / LENGTH_MARK_STEP).round().max(1.0) as usize
+    }
+
+    /// Rewrite a phoneme string according to the profile
+    pub fn apply(&self, phonemes: &str) -> String {
+        let chars: Vec<char> = phonemes.chars().collect();
+        let mut out = String::with_capacity(phonemes.len() + chars.len() / 2);
+
+        for (i, &c) in chars.iter().enumerate() {
+            if c == LENGTH_MARK {
+                let prev_class = i.checked_sub(1).and_then(|p| class_of(chars[p]));
+                if prev_class.is_some_and(|class| self.factor(class) <= SHORTEN_AT) {
+                    continue;
+                }
+                out.push(c);
+                continue;
+            }
+
+            out.push(c);
+
+            if let Some(class) = class_of(c) {
+                // Only mark the end of a run so diphthongs and clusters get one mark
+                let run_continues = chars
+                    .get(i + 1)
+                    .is_some_and(|&next| next == LENGTH_MARK || class_of(next) == Some(class));
+                if !run_continues {
+                    out.extend(std::iter::repeat_n(LENGTH_MARK, self.length_marks(class)));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_profile() {
+        let drawl: DurationProfile = "vowel=1.5".parse().unwrap();
+        assert_eq!(drawl.apply("hˈaɪ ðɛɹ"), "hˈaɪː ðɛːɹ");
+        let slow_drawl: DurationProfile = "vowel=2".parse().unwrap();
+        assert_eq!(slow_drawl.apply("hˈaɪ ðɛɹ"), "hˈaɪːː ðɛːːɹ");
+
+        let clipped: DurationProfile = "vowels=0.7, consonants=1.0".parse().unwrap();
+        assert_eq!(clipped.apply("fˈuːd"), "fˈud");
+
+        assert_eq!(DurationProfile::default().apply("fˈuːd"), "fˈuːd");
+        assert!("tone=2".parse::<DurationProfile>().is_err());
+        assert!("vowel=9".parse::<DurationProfile>().is_err());
+        assert!("vowel=1.1".parse::<DurationProfile>().is_err());
+    }
+}
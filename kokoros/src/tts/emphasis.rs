@@ -0,0 +1,80 @@
+/// Speed factor of emphasized spans, slowing them down a little
+pub const EMPHASIS_SPEED: f32 = 0.85;
+
+/// Gain of emphasized spans, about +1.5 dB
+pub const EMPHASIS_GAIN: f32 = 1.19;
+
+/// Split `text` into plain and emphasized spans, the latter enclosed in
+/// `marker` (e.g. `*` in `This is *really* important`). A backslash before
+/// the marker keeps it as text, and a marker left open is kept as text too.
+/// Whitespace-only spans are dropped.
+pub fn split_emphasis(text: &str, marker: &str) -> Vec<(bool, String)> {
+    let mut spans: Vec<(bool, String)> = Vec::new();
+    let push =
+        |spans: &mut Vec<(bool, String)>, emphasized: bool, span: &str| match spans.last_mut() {
+            Some((last, text)) if *last == emphasized => text.push_str(span),
+            _ => spans.push((emphasized, span.to_string())),
+        };
+    if marker.is_empty() {
+        push(&mut spans, false, text);
+        return spans;
+    }
+
+    let mut current = String::new();
+    let mut emphasized = false;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix('\\').and_then(|r| r.strip_prefix(marker)) {
+            current.push_str(marker);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(marker) {
+            push(&mut spans, emphasized, &current);
+            current.clear();
+            emphasized = !emphasized;
+            rest = after;
+        } else {
+            current.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if emphasized {
+        // Unclosed, so the marker was meant literally
+        push(&mut spans, false, &format!("{}{}", marker, current));
+    } else {
+        push(&mut spans, false, &current);
+    }
+
+    spans.retain(|(_, span)| !span.trim().is_empty());
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_emphasis() {
+        let spans = |text: &str, marker: &str| split_emphasis(text, marker);
+        assert_eq!(
+            spans("This is *really* important.", "*"),
+            vec![
+                (false, "This is ".to_string()),
+                (true, "really".to_string()),
+                (false, " important.".to_string()),
+            ]
+        );
+        assert_eq!(
+            spans(r"2 \* 3 is 6", "*"),
+            vec![(false, "2 * 3 is 6".to_string())]
+        );
+        assert_eq!(spans("a *b", "*"), vec![(false, "a *b".to_string())]);
+        assert_eq!(
+            spans("==Never== again", "=="),
+            vec![(true, "Never".to_string()), (false, " again".to_string())]
+        );
+        assert_eq!(
+            spans("no markers", "*"),
+            vec![(false, "no markers".to_string())]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// Errors synthesis reports with details callers may want to inspect, e.g. by
+/// downcasting the boxed error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KokoroError {
+    /// The audio output of the ONNX model is not a waveform, typically because
+    /// the graph is not a Kokoro export or exports another tensor as `audio`
+    ModelOutputMismatch {
+        /// Description of the accepted shapes
+        expected: String,
+        /// Shape the model returned
+        got: Vec<usize>,
+    },
+    /// A voice blend that doesn't parse, with the reason
+    InvalidStyle(String),
+    /// A voice name or index that isn't among the loaded voices
+    UnknownVoice(String),
+    /// A style with voices of another language than the one asked for
+    VoiceLanguageMismatch { style: String, lan: String },
+}
+
+impl fmt::Display for KokoroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KokoroError::ModelOutputMismatch { expected, got } => write!(
+                f,
+                "unexpected model output shape {:?}, expected {}; check that the model is a \
+                 Kokoro export whose audio output is the waveform",
+                got, expected
+            ),
+            KokoroError::InvalidStyle(reason) => write!(f, "{}", reason),
+            KokoroError::UnknownVoice(name) => write!(f, "unknown voice '{}'", name),
+            KokoroError::VoiceLanguageMismatch { style, lan } => write!(
+                f,
+                "style '{}' has voices that don't belong to language '{}'",
+                style, lan
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KokoroError {}
+
+/// Check that `shape` is that of a mono waveform: `[samples]`, or with
+/// leading batch and channel dimensions of 1 such as `[1, samples]`
+pub fn check_audio_shape(shape: &[usize]) -> Result<(), KokoroError> {
+    let waveform = match shape.split_last() {
+        Some((&samples, leading)) => samples > 0 && leading.iter().all(|&d| d == 1),
+        None => false,
+    };
+    if waveform && shape.len() <= 3 {
+        Ok(())
+    } else {
+        Err(KokoroError::ModelOutputMismatch {
+            expected: "[samples] or [1, samples]".to_string(),
+            got: shape.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_audio_shape() {
+        assert!(check_audio_shape(&[48000]).is_ok());
+        assert!(check_audio_shape(&[1, 48000]).is_ok());
+        assert!(check_audio_shape(&[1, 1, 48000]).is_ok());
+
+        // A mel spectrogram exported in place of the audio
+        let err = check_audio_shape(&[1, 80, 200]).unwrap_err();
+        assert_eq!(
+            err,
+            KokoroError::ModelOutputMismatch {
+                expected: "[samples] or [1, samples]".to_string(),
+                got: vec![1, 80, 200],
+            }
+        );
+        assert!(err.to_string().contains("[1, 80, 200]"));
+        assert!(check_audio_shape(&[]).is_err());
+        assert!(check_audio_shape(&[1, 0]).is_err());
+    }
+
+    #[test]
+    fn test_style_errors() {
+        assert_eq!(
+            KokoroError::UnknownVoice("xx_nobody".to_string()).to_string(),
+            "unknown voice 'xx_nobody'"
+        );
+        let mismatch = KokoroError::VoiceLanguageMismatch {
+            style: "af_sky.4+ef_dora.6".to_string(),
+            lan: "en-us".to_string(),
+        };
+        assert!(mismatch.to_string().contains("'en-us'"));
+    }
+}
@@ -1,9 +1,26 @@
-use crate::onn::ort_koko::{self, ModelStrategy};
-use crate::tts::tokenize::tokenize;
+use crate::onn::ort_base::SessionMemory;
+use crate::onn::ort_koko::{self, ModelPrecision, ModelStrategy};
+use crate::tts::blend_cache::{BlendCache, BlendCacheStats};
+use crate::tts::cancel::CancellationToken;
+use crate::tts::duration::DurationProfile;
+use crate::tts::emphasis::{EMPHASIS_GAIN, EMPHASIS_SPEED, split_emphasis};
+use crate::tts::error::{KokoroError, check_audio_shape};
+use crate::tts::lang;
+use crate::tts::lexicon::{Lexicon, LexiconSegment};
+use crate::tts::tokenize::{
+    UnknownPhonemePolicy, reverse_vocab, tokenize_with_vocab, tokens_to_phonemes_with_vocab,
+};
+use crate::tts::vocab::VOCAB;
+use crate::tts::voices;
 use crate::utils;
+use crate::utils::audio::{
+    AudioStats, DeEsser, clamp_pauses, crossfade_append, de_ess, fade_out, ms_to_samples,
+    soft_limit, trim_silence,
+};
 use crate::utils::debug::format_debug_prefix;
+use crate::utils::stream_encoder::{StreamEncoder, StreamFormat};
 use lazy_static::lazy_static;
-use ndarray::Array3;
+use ndarray::{Array2, Axis};
 use ndarray_npy::NpzReader;
 use std::collections::HashMap;
 use std::error::Error;
@@ -23,11 +40,81 @@ lazy_static! {
 // Flag to ensure voice styles are only logged once
 static VOICES_LOGGED: AtomicBool = AtomicBool::new(false);
 
+/// Point eSpeak NG at a specific data directory.
+///
+/// `path` may be the `espeak-ng-data` directory itself or the directory
+/// containing it. eSpeak NG reads the location from `ESPEAK_DATA_PATH` when it
+/// initializes, so this only takes effect before the first phonemization.
+pub fn set_espeak_data_path(path: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    let root = if path.join("phontab").is_file() {
+        // ESPEAK_DATA_PATH names the parent of `espeak-ng-data`
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some("espeak-ng-data") => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            _ => {
+                return Err(format!(
+                    "eSpeak data directory must be named espeak-ng-data: {}",
+                    path.display()
+                )
+                .into());
+            }
+        }
+    } else if path.join("espeak-ng-data").join("phontab").is_file() {
+        path.to_path_buf()
+    } else {
+        return Err(format!("no eSpeak NG data found at {}", path.display()).into());
+    };
+
+    let _guard = ESPEAK_MUTEX.lock().unwrap();
+    // SAFETY: the variable is written while holding the eSpeak mutex, before any
+    // phonemization, and nothing else in the process reads or writes it concurrently.
+    unsafe { std::env::set_var("ESPEAK_DATA_PATH", &root) };
+    tracing::debug!("Using eSpeak NG data from {}", root.display());
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct WordAlignment {
     pub word: String,
     pub start_sec: f32,
     pub end_sec: f32,
+    /// Timing of each phoneme of the word, from the same predicted durations.
+    /// Empty for punctuation pauses
+    pub phonemes: Vec<PhonemeAlignment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhonemeAlignment {
+    pub phoneme: String,
+    pub start_sec: f32,
+    pub end_sec: f32,
+}
+
+/// One sentence of a `TTSResult` and the audio synthesized for it
+#[derive(Debug, Clone)]
+pub struct SentenceAudio {
+    pub text: String,
+    pub audio: Vec<f32>,
+}
+
+/// Speech synthesized sentence by sentence, so that after an edit of the text
+/// `TTSKoko::resynthesize` only has to synthesize the sentences that changed
+#[derive(Debug, Clone)]
+pub struct TTSResult {
+    pub lan: String,
+    pub style_name: String,
+    pub speed: f32,
+    pub sentences: Vec<SentenceAudio>,
+}
+
+impl TTSResult {
+    /// The audio of all sentences, joined in order
+    pub fn audio(&self) -> Vec<f32> {
+        self.sentences
+            .iter()
+            .flat_map(|s| s.audio.iter().copied())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -45,6 +132,39 @@ impl TtsOutput {
             TtsOutput::Aligned(a, b) => (a, Some(b)),
         }
     }
+
+    fn audio_len(&self) -> usize {
+        match self {
+            TtsOutput::Audio(a) | TtsOutput::Aligned(a, _) => a.len(),
+        }
+    }
+
+    fn audio_mut(&mut self) -> &mut Vec<f32> {
+        match self {
+            TtsOutput::Audio(a) | TtsOutput::Aligned(a, _) => a,
+        }
+    }
+
+    /// Cut the audio to `len` samples with a short fade-out, dropping the
+    /// words that start after the cut. Timestamps are relative to the audio.
+    fn truncate(&mut self, len: usize, sample_rate: f32) {
+        let (audio, alignments) = match self {
+            TtsOutput::Audio(a) => (a, None),
+            TtsOutput::Aligned(a, b) => (a, Some(b)),
+        };
+        truncate_with_fade(audio, len);
+        if let Some(alignments) = alignments {
+            alignments.retain(|w| w.start_sec < len as f32 / sample_rate);
+        }
+    }
+}
+
+/// Fade applied where `InitConfig::max_duration_secs` cuts the output
+const MAX_DURATION_FADE_MS: u32 = 50;
+
+fn truncate_with_fade(audio: &mut Vec<f32>, len: usize) {
+    audio.truncate(len);
+    fade_out(audio, ms_to_samples(MAX_DURATION_FADE_MS, 24000));
 }
 
 enum ExecutionMode<'a> {
@@ -54,6 +174,187 @@ enum ExecutionMode<'a> {
     Stream(&'a mut dyn FnMut(TtsOutput) -> Result<(), Box<dyn std::error::Error>>),
 }
 
+/// One chunk as it would be synthesized, see `TTSKoko::dry_run`
+#[derive(Debug, Clone)]
+pub struct DryRunChunk {
+    pub text: String,
+    pub lan: String,
+    pub style_name: String,
+    pub phonemes: String,
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    pub chunks: Vec<DryRunChunk>,
+    pub estimated_duration_sec: f32,
+}
+
+/// What to do when inference produces NaN or infinite samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    /// Replace the bad samples with silence and log a warning
+    #[default]
+    Zero,
+    /// Run the chunk again once, then fall back to `Zero`
+    Retry,
+    /// Fail the synthesis
+    Error,
+}
+
+impl std::str::FromStr for NanPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "zero" => Ok(NanPolicy::Zero),
+            "retry" => Ok(NanPolicy::Retry),
+            "error" => Ok(NanPolicy::Error),
+            other => Err(format!(
+                "unknown NaN policy '{}', expected zero, retry or error",
+                other
+            )),
+        }
+    }
+}
+
+/// How finely `TTSKoko::tts_timestamped_raw_audio` times the words it returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampGranularity {
+    /// Word timings only, leaving `WordAlignment::phonemes` empty. Cheapest,
+    /// and enough for captions
+    Word,
+    /// Word and phoneme timings
+    #[default]
+    Phoneme,
+    /// Word and phoneme timings rounded to whole frames of the duration
+    /// predictor (25 ms), as the model predicted them
+    Frame,
+}
+
+impl std::str::FromStr for TimestampGranularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "word" => Ok(TimestampGranularity::Word),
+            "phoneme" => Ok(TimestampGranularity::Phoneme),
+            "frame" => Ok(TimestampGranularity::Frame),
+            other => Err(format!(
+                "unknown timestamp granularity '{}', expected word, phoneme or frame",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether a style string blends several voices (or weights a single one)
+/// rather than naming one voice
+fn is_blend(style_name: &str) -> bool {
+    style_name.contains('+') || style_name.contains(':')
+}
+
+/// Parse a style into `(voice, weight)` pairs, the syntax shared by `--style`
+/// and the server's `voice` field.
+///
+/// A plain voice name has weight 1.0. Blends are `+`-separated parts, each
+/// either `name.N` (weight N/10, e.g. `af_sarah.4`) or `name:weight` with a
+/// literal weight (e.g. `af_sarah:0.45`). Errors name the offending part.
+///
+/// Names may also be voice indices (`3`, `3.4+7.6`), resolved by
+/// `TTSKoko::voice`; since names and indices never contain `.` or `:`, the
+/// first one always starts the weight.
+pub fn parse_style(style_name: &str) -> Result<Vec<(&str, f32)>, String> {
+    if !is_blend(style_name) {
+        return Ok(vec![(style_name, 1.0)]);
+    }
+
+    style_name
+        .split('+')
+        .map(|part| {
+            let (name, weight) = if let Some((name, weight)) = part.split_once(':') {
+                (name, weight.parse::<f32>().ok())
+            } else if let Some((name, portion)) = part.split_once('.') {
+                (name, portion.parse::<f32>().ok().map(|p| p * 0.1))
+            } else {
+                return Err(format!(
+                    "voice blend part '{}' has no weight, expected e.g. '{}.5' or '{}:0.5'",
+                    part, part, part
+                ));
+            };
+            match weight {
+                Some(weight) if !name.is_empty() && weight.is_finite() && weight >= 0.0 => {
+                    Ok((name, weight))
+                }
+                _ => Err(format!("invalid voice blend part '{}'", part)),
+            }
+        })
+        .collect()
+}
+
+/// Pause after a punctuation mark in seconds at speed 1.0, used for punctuation
+/// timestamps and `--smart-gaps`. Scaled by 1/speed so faster speech shortens pauses.
+fn punctuation_pause_secs(label: &str) -> f32 {
+    match label {
+        "." | "!" | "?" => 0.300, // 300 ms
+        "," => 0.150,             // 150 ms
+        ";" | ":" => 0.200,
+        _ => 0.0,
+    }
+}
+
+/// Samples below this level (about -50 dBFS) count as silence for smart gaps
+const SMART_GAP_SILENCE_THRESHOLD: f32 = 0.003;
+
+/// Frame length in samples (10 ms) of the silence detection of
+/// `InitConfig::max_internal_pause_ms`
+const PAUSE_FRAME_LEN: usize = 240;
+
+/// Natural edge silence kept around each chunk by smart gaps so soft onsets
+/// and decays are not clipped
+const SMART_GAP_MARGIN_MS: u32 = 20;
+
+/// Pause inserted by smart gaps after a chunk that does not end in punctuation
+const SMART_GAP_DEFAULT_SECS: f32 = 0.150;
+
+/// Token length whose style vector every chunk uses with
+/// `InitConfig::consistent_voice`, a typical sentence length
+const CONSISTENT_VOICE_STYLE_TOKENS: usize = 200;
+
+/// How one whitespace-separated piece of the input is synthesized, see `TTSKoko::analyze`
+#[derive(Debug, Clone)]
+pub struct AnalyzedSegment {
+    /// Character (not byte) range of the piece in the original input
+    pub char_start: usize,
+    pub char_end: usize,
+    pub text: String,
+    /// Phonemes that reach the model; characters missing from the vocab are dropped
+    pub phonemes: String,
+    pub tokens: usize,
+    /// False when the piece produces no tokens and is therefore not spoken
+    pub spoken: bool,
+}
+
+/// Rough speaking rate of the model at speed 1.0, in phoneme tokens per second
+/// (tokens include stress marks and word separators)
+const ESTIMATED_TOKENS_PER_SEC: f32 = 16.0;
+
+/// Estimate the audio duration produced for `tokens` phoneme tokens at `speed`
+pub fn estimate_duration_secs(tokens: usize, speed: f32) -> f32 {
+    let speed = if speed > 1e-6 { speed } else { 1.0 };
+    tokens as f32 / (ESTIMATED_TOKENS_PER_SEC * speed)
+}
+
+/// Phoneme tokens estimated to take `secs` seconds at `speed`, the inverse of
+/// `estimate_duration_secs`. At least one token, so chunking always advances
+pub fn estimate_tokens_for_secs(secs: f32, speed: f32) -> usize {
+    let speed = if speed > 1e-6 { speed } else { 1.0 };
+    ((secs * ESTIMATED_TOKENS_PER_SEC * speed).floor() as usize).max(1)
+}
+
+/// Token limit of a chunk, below the model's 512 token context
+const MAX_CHUNK_TOKENS: usize = 500;
+
 #[derive(Debug, Clone)]
 pub struct TTSOpts<'a> {
     pub txt: &'a str,
@@ -63,6 +364,9 @@ pub struct TTSOpts<'a> {
     pub mono: bool,
     pub speed: f32,
     pub initial_silence: Option<usize>,
+    /// Write each chunk to the output as it is synthesized instead of
+    /// holding the whole audio in memory, for very long inputs
+    pub low_memory: bool,
 }
 
 #[derive(Clone)]
@@ -71,9 +375,17 @@ pub struct TTSKoko {
     model_path: String,
     model: Arc<Mutex<ort_koko::OrtKoko>>,
     styles: HashMap<String, Vec<[[f32; 256]; 1]>>,
+    /// Blended style tables, shared by clones of this instance
+    blend_cache: Arc<StyleBlendCache>,
     init_config: InitConfig,
 }
 
+/// Blend spec to the blended style vector of every input length
+type StyleBlendCache = BlendCache<String, Arc<Vec<[[f32; 256]; 1]>>>;
+
+/// Text, language and style of a chunk, and whether it is emphasized
+type PlannedChunk = (String, String, String, bool);
+
 /// Parallel TTS with multiple ONNX instances for true concurrency
 #[derive(Clone)]
 pub struct TTSKokoParallel {
@@ -81,6 +393,7 @@ pub struct TTSKokoParallel {
     model_path: String,
     models: Vec<Arc<Mutex<ort_koko::OrtKoko>>>,
     styles: HashMap<String, Vec<[[f32; 256]; 1]>>,
+    blend_cache: Arc<StyleBlendCache>,
     init_config: InitConfig,
 }
 
@@ -89,6 +402,92 @@ pub struct InitConfig {
     pub model_url: String,
     pub voices_url: String,
     pub sample_rate: u32,
+    /// Detect the language of each sentence and synthesize it with a matching
+    /// eSpeak language and voice, instead of using one language for the whole input
+    pub per_sentence_lang: bool,
+    /// Precision hint for the ONNX model, see `ModelPrecision`
+    pub model_precision: ModelPrecision,
+    /// Per phoneme class duration multipliers applied before inference
+    pub duration_profile: Option<DurationProfile>,
+    /// Overlap adjacent chunks by this many milliseconds with a cross-fade when
+    /// concatenating them (0 disables). Streaming output is not cross-faded.
+    pub chunk_crossfade_ms: u32,
+    /// eSpeak NG data directory, see `set_espeak_data_path`. When unset eSpeak NG
+    /// uses `ESPEAK_DATA_PATH` or its built-in location.
+    pub espeak_data_path: Option<String>,
+    /// Soft-limit the output above this level (0.0 to 1.0) instead of letting
+    /// integer conversions hard clip it. Applied to the joined audio, after
+    /// cross-fades and smart gaps, or to each chunk when streaming
+    pub limiter_threshold: Option<f32>,
+    /// Turn down harsh sibilance of each chunk, see `de_ess`. Applied before
+    /// the limiter, so the limiter sees the final levels
+    pub de_ess: Option<DeEsser>,
+    /// Handling of NaN/Inf samples in the model output
+    pub on_nan: NanPolicy,
+    /// Handling of phonemes missing from the model vocab
+    pub on_unknown_phoneme: UnknownPhonemePolicy,
+    /// Phoneme-to-id vocab overriding the built-in one, for converted Kokoro
+    /// variants, see `vocab::load_vocab_file`
+    pub vocab: Option<Arc<HashMap<char, usize>>>,
+    /// Trim the natural edge silence of each chunk when concatenating and
+    /// insert a uniform pause chosen by the chunk's final punctuation instead.
+    /// Streaming output is not affected.
+    pub smart_gaps: bool,
+    /// Voice packs hold one style vector per input length, so chunks of
+    /// different lengths get subtly different timbres. When set, every chunk
+    /// uses the same vector, keeping the voice identical across the joins of a
+    /// long document. Input that fits in one chunk keeps the vector of its
+    /// length either way.
+    pub consistent_voice: bool,
+    /// Fixed pronunciations for words and phrases, applied before eSpeak
+    /// phonemization, see `Lexicon::load`
+    pub lexicon: Option<Arc<Lexicon>>,
+    /// Stop synthesizing once the output reaches this many seconds, cutting
+    /// it there with a short fade-out, to guard against runaway inputs
+    pub max_duration_secs: Option<f32>,
+    /// Expected hex SHA-256 of the model file. When set, the model is hashed
+    /// before it is loaded and a mismatch fails instead of running a corrupted
+    /// or truncated file
+    pub model_sha256: Option<String>,
+    /// eSpeak NG voice variant (e.g. `f3`, `m2`, `whisper`) applied on top of
+    /// the language when phonemizing. It only changes eSpeak's front-end
+    /// phonemes, never the neural voice
+    pub espeak_variant: Option<String>,
+    /// Fall back to the CPU execution provider when a GPU provider fails to
+    /// create or run the session, instead of failing
+    pub provider_fallback: bool,
+    /// Keep the blended style tables of up to this many voice blends, least
+    /// recently used first out, instead of re-blending on every request
+    /// (0 disables)
+    pub blend_cache_size: usize,
+    /// Also end chunks before their estimated audio (see
+    /// `estimate_duration_secs`) exceeds this many seconds, for predictable
+    /// streaming latency. The token limit still applies
+    pub max_chunk_secs: Option<f32>,
+    /// Memory arena and memory pattern settings of the ONNX sessions
+    pub session_memory: SessionMemory,
+    /// End sentences lacking terminal punctuation (e.g. UI strings) with a
+    /// period, so they aren't clipped or cut off abruptly. Existing `?`, `!`
+    /// and other marks are kept
+    pub auto_punctuate: bool,
+    /// More voices files to load after the main one, for voice packs kept in
+    /// separate files
+    pub voice_packs: Vec<String>,
+    /// Let a voice of a later voices file replace one of the same name,
+    /// instead of failing on the duplicate
+    pub voice_packs_override: bool,
+    /// Energy/expressiveness from 0.0 to 1.0 for models with such an input,
+    /// passed to the model with the speed of every call. Models without one
+    /// ignore it with a warning, logged once per process
+    pub expressiveness: Option<f32>,
+    /// Marker of emphasized spans, e.g. `*` for `This is *really* important`.
+    /// Emphasized spans are spoken slower and louder; the model has no pitch
+    /// control. `\` escapes the marker
+    pub emphasis_marker: Option<String>,
+    /// Shorten pauses inside a chunk, such as overly long ones at commas, to
+    /// at most this many milliseconds. Word timestamps don't account for
+    /// the shortened pauses
+    pub max_internal_pause_ms: Option<u32>,
 }
 
 impl Default for InitConfig {
@@ -97,45 +496,489 @@ impl Default for InitConfig {
             model_url: "https://github.com/thewh1teagle/kokoro-onnx/releases/download/model-files-v1.0/kokoro-v1.0.onnx".into(),
             voices_url: "https://github.com/thewh1teagle/kokoro-onnx/releases/download/model-files-v1.0/voices-v1.0.bin".into(),
             sample_rate: 24000,
+            per_sentence_lang: false,
+            model_precision: ModelPrecision::Auto,
+            duration_profile: None,
+            chunk_crossfade_ms: 0,
+            espeak_data_path: None,
+            limiter_threshold: None,
+            de_ess: None,
+            on_nan: NanPolicy::Zero,
+            on_unknown_phoneme: UnknownPhonemePolicy::Skip,
+            vocab: None,
+            smart_gaps: false,
+            consistent_voice: false,
+            lexicon: None,
+            max_duration_secs: None,
+            model_sha256: None,
+            espeak_variant: None,
+            provider_fallback: false,
+            blend_cache_size: 0,
+            max_chunk_secs: None,
+            session_memory: SessionMemory::default(),
+            auto_punctuate: true,
+            voice_packs: Vec::new(),
+            voice_packs_override: false,
+            expressiveness: None,
+            emphasis_marker: None,
+            max_internal_pause_ms: None,
+        }
+    }
+}
+
+/// The text-to-phoneme front end of `TTSKoko` on its own: eSpeak NG with the
+/// configured variant and lexicon, but no voices or ONNX session. Cheap to
+/// create, for iterating on pronunciations
+#[derive(Clone)]
+pub struct TextPhonemizer {
+    espeak_variant: Option<String>,
+    lexicon: Option<Arc<Lexicon>>,
+}
+
+impl TextPhonemizer {
+    /// Use the eSpeak settings of `cfg`, pointing eSpeak NG at
+    /// `InitConfig::espeak_data_path` if set
+    pub fn new(cfg: &InitConfig) -> Result<Self, Box<dyn Error>> {
+        if let Some(espeak_data_path) = &cfg.espeak_data_path {
+            set_espeak_data_path(espeak_data_path)?;
+        }
+        Ok(Self::from_config(cfg))
+    }
+
+    fn from_config(cfg: &InitConfig) -> Self {
+        Self {
+            espeak_variant: cfg.espeak_variant.clone(),
+            lexicon: cfg.lexicon.clone(),
+        }
+    }
+
+    /// Phonemize `text` with eSpeak, splicing in the pronunciations of the
+    /// lexicon
+    pub fn phonemize(&self, text: &str, lan: &str) -> Result<String, String> {
+        // eSpeak selects variants with a `+variant` suffix on the voice name
+        let voice = match &self.espeak_variant {
+            Some(variant) => format!("{}+{}", lan, variant),
+            None => lan.to_string(),
+        };
+        let espeak = |text: &str| {
+            let _guard = ESPEAK_MUTEX.lock().unwrap();
+            text_to_phonemes(text, &voice, None, true, false)
+                .map(|phonemes| phonemes.join(""))
+                .map_err(|e| format!("{:?}", e))
+        };
+
+        let Some(lexicon) = &self.lexicon else {
+            return espeak(text);
+        };
+
+        let mut phonemes = String::new();
+        for segment in lexicon.segment(text) {
+            let (piece, is_punct) = match segment {
+                LexiconSegment::Text(text) => {
+                    let is_punct = text
+                        .trim_start()
+                        .starts_with(|c: char| c.is_ascii_punctuation());
+                    (espeak(&text)?, is_punct)
+                }
+                LexiconSegment::Phonemes(ipa) => (ipa, false),
+            };
+            if piece.is_empty() {
+                continue;
+            }
+            // Keep word boundaries between pieces, but not before trailing punctuation
+            if !phonemes.is_empty() && !is_punct {
+                phonemes.push(' ');
+            }
+            phonemes.push_str(piece.trim());
+        }
+        Ok(phonemes)
+    }
+}
+
+/// Check the model file against `InitConfig::model_sha256`, if set. The
+/// computed hash is logged at debug level either way
+fn verify_model_checksum(model_path: &str, cfg: &InitConfig) -> Result<(), String> {
+    match &cfg.model_sha256 {
+        Some(expected) => {
+            let hash = utils::fileio::verify_sha256(model_path, expected)?;
+            tracing::debug!("Model {} sha256 {} (verified)", model_path, hash);
+        }
+        None if tracing::enabled!(tracing::Level::DEBUG) => {
+            if let Ok(hash) = utils::fileio::sha256_file(model_path) {
+                tracing::debug!("Model {} sha256 {}", model_path, hash);
+            }
         }
+        None => {}
     }
+    Ok(())
 }
 
 impl TTSKoko {
-    pub async fn new(model_path: &str, voices_path: &str) -> Self {
+    pub async fn new(model_path: &str, voices_path: &str) -> Result<Self, Box<dyn Error>> {
         Self::from_config(model_path, voices_path, InitConfig::default()).await
     }
 
-    pub async fn from_config(model_path: &str, voices_path: &str, cfg: InitConfig) -> Self {
+    /// Load the model and voices, downloading missing files first. Only async
+    /// because of the download, see `from_config_blocking`
+    pub async fn from_config(
+        model_path: &str,
+        voices_path: &str,
+        cfg: InitConfig,
+    ) -> Result<Self, Box<dyn Error>> {
         if !Path::new(model_path).exists() {
             utils::fileio::download_file_from_url(cfg.model_url.as_str(), model_path)
                 .await
-                .expect("download model failed.");
+                .map_err(|e| format!("download model failed: {}", e))?;
         }
 
         if !Path::new(voices_path).exists() {
             utils::fileio::download_file_from_url(cfg.voices_url.as_str(), voices_path)
                 .await
-                .expect("download voices data file failed.");
+                .map_err(|e| format!("download voices data file failed: {}", e))?;
+        }
+
+        Self::load(model_path, voices_path, cfg)
+    }
+
+    /// Synchronous `new`, for callers without an async runtime. Synthesis is
+    /// synchronous either way; only the OpenAI server needs tokio.
+    pub fn new_blocking(model_path: &str, voices_path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_config_blocking(model_path, voices_path, InitConfig::default())
+    }
+
+    /// Synchronous `from_config`. Missing files are downloaded with a blocking
+    /// HTTP client, which must not be used from within an async runtime.
+    pub fn from_config_blocking(
+        model_path: &str,
+        voices_path: &str,
+        cfg: InitConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        if !Path::new(model_path).exists() {
+            utils::fileio::download_file_from_url_blocking(cfg.model_url.as_str(), model_path)
+                .map_err(|e| format!("download model failed: {}", e))?;
+        }
+
+        if !Path::new(voices_path).exists() {
+            utils::fileio::download_file_from_url_blocking(cfg.voices_url.as_str(), voices_path)
+                .map_err(|e| format!("download voices data file failed: {}", e))?;
+        }
+
+        Self::load(model_path, voices_path, cfg)
+    }
+
+    /// Create the ONNX session and load the voices from files on disk
+    fn load(model_path: &str, voices_path: &str, cfg: InitConfig) -> Result<Self, Box<dyn Error>> {
+        if let Some(espeak_data_path) = &cfg.espeak_data_path {
+            set_espeak_data_path(espeak_data_path)?;
         }
+        verify_model_checksum(model_path, &cfg)?;
 
-        let model = Arc::new(Mutex::new(
-            ort_koko::OrtKoko::new(model_path.to_string())
-                .expect("Failed to create Kokoro TTS model"),
-        ));
+        let model = ort_koko::OrtKoko::new_with_options(
+            model_path.to_string(),
+            cfg.model_precision,
+            cfg.provider_fallback,
+            cfg.session_memory,
+        )
+        .map_err(|e| format!("failed to create Kokoro TTS model: {}", e))?;
+        let model = Arc::new(Mutex::new(model));
         // TODO: if(not streaming) { model.print_info(); }
         // model.print_info();
 
-        let styles = Self::load_voices(voices_path);
+        let styles = Self::load_voices(voices_path, &cfg)?;
 
-        TTSKoko {
+        Ok(TTSKoko {
             model_path: model_path.to_string(),
             model,
             styles,
+            blend_cache: Arc::new(BlendCache::new(cfg.blend_cache_size)),
+            init_config: cfg,
+        })
+    }
+
+    /// eSpeak language codes that can be passed as `lan`, read from the eSpeak
+    /// NG data directory (see `set_espeak_data_path`). Falls back to the
+    /// languages Kokoro has voices for when the data can't be found.
+    pub fn supported_languages() -> Vec<String> {
+        let languages = lang::espeak_data_dir()
+            .map(|dir| lang::espeak_languages(&dir))
+            .unwrap_or_default();
+        if languages.is_empty() {
+            return lang::kokoro_languages()
+                .into_iter()
+                .map(String::from)
+                .collect();
+        }
+        languages
+    }
+
+    /// Load only the voices, without creating an ONNX session.
+    ///
+    /// The returned instance can phonemize text and validate styles (e.g. for
+    /// `dry_run`), but any attempt to synthesize fails because no session exists.
+    pub fn without_model(voices_path: &str, cfg: InitConfig) -> Result<Self, Box<dyn Error>> {
+        if let Some(espeak_data_path) = &cfg.espeak_data_path {
+            set_espeak_data_path(espeak_data_path)?;
+        }
+
+        Ok(TTSKoko {
+            model_path: String::new(),
+            model: Arc::new(Mutex::new(ort_koko::OrtKoko::uninitialized())),
+            styles: Self::load_voices(voices_path, &cfg)?,
+            blend_cache: Arc::new(BlendCache::new(cfg.blend_cache_size)),
             init_config: cfg,
+        })
+    }
+
+    /// Split the input into chunks, each tagged with the language and style it
+    /// should be synthesized with and whether it is emphasized. Spans in
+    /// language tags (`<es>Hola</es>`) are phonemized in their language with
+    /// the voice of the rest, and spans in `InitConfig::emphasis_marker` make
+    /// chunks of their own. Auto-punctuation leaves the sentences such spans
+    /// cut short alone, so `This is *really* important.` reads as one sentence.
+    fn plan_chunks(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+    ) -> Result<Vec<PlannedChunk>, String> {
+        // Whichever of the token and the duration limit is reached first
+        let max_tokens = match self.init_config.max_chunk_secs {
+            Some(secs) => MAX_CHUNK_TOKENS.min(estimate_tokens_for_secs(secs, speed)),
+            None => MAX_CHUNK_TOKENS,
+        };
+        // Read from the eSpeak data only when the text has something like a tag
+        let mut supported: Option<Vec<String>> = None;
+        let spans = lang::split_language_tags(txt, |code| {
+            supported
+                .get_or_insert_with(Self::supported_languages)
+                .iter()
+                .any(|l| l == code)
+        })?;
+
+        let mut chunks: Vec<PlannedChunk> = Vec::new();
+        for (tag_lan, span) in spans {
+            let pieces = match &self.init_config.emphasis_marker {
+                Some(marker) => split_emphasis(&span, marker),
+                None => vec![(false, span)],
+            };
+            let last = pieces.len().saturating_sub(1);
+            for (i, (emphasized, piece)) in pieces.into_iter().enumerate() {
+                // Only the last piece ends where the text's own sentence does
+                let ends_sentence = i == last;
+                match &tag_lan {
+                    Some(tag_lan) => {
+                        for chunk in
+                            self.split_text_into_chunks(&piece, max_tokens, tag_lan, ends_sentence)
+                        {
+                            chunks.push((
+                                chunk,
+                                tag_lan.clone(),
+                                style_name.to_string(),
+                                emphasized,
+                            ));
+                        }
+                    }
+                    None => self.plan_untagged_chunks(
+                        &piece,
+                        lan,
+                        style_name,
+                        emphasized,
+                        ends_sentence,
+                        max_tokens,
+                        &mut chunks,
+                    ),
+                }
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Chunks of text outside language tags, in `lan` or, with
+    /// `InitConfig::per_sentence_lang`, in the detected language of each
+    /// sentence
+    fn plan_untagged_chunks(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        emphasized: bool,
+        ends_sentence: bool,
+        max_tokens: usize,
+        chunks: &mut Vec<PlannedChunk>,
+    ) {
+        if self.init_config.per_sentence_lang {
+            let segments = lang::segment_by_language(txt, lan);
+            let last = segments.len().saturating_sub(1);
+            for (i, (segment_lan, segment)) in segments.into_iter().enumerate() {
+                let segment_style = lang::style_for_language(style_name, &segment_lan);
+                tracing::debug!(
+                    "Language segment [{}] with style '{}': '{}'",
+                    segment_lan,
+                    segment_style,
+                    segment
+                );
+                let ends_sentence = ends_sentence || i < last;
+                for chunk in
+                    self.split_text_into_chunks(&segment, max_tokens, &segment_lan, ends_sentence)
+                {
+                    chunks.push((
+                        chunk,
+                        segment_lan.clone(),
+                        segment_style.clone(),
+                        emphasized,
+                    ));
+                }
+            }
+        } else {
+            for chunk in self.split_text_into_chunks(txt, max_tokens, lan, ends_sentence) {
+                chunks.push((chunk, lan.to_string(), style_name.to_string(), emphasized));
+            }
         }
     }
 
+    /// Run every stage up to (but excluding) ONNX inference: chunking,
+    /// phonemization and voice/language validation, plus a duration estimate.
+    pub fn dry_run(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+    ) -> Result<DryRunReport, Box<dyn Error>> {
+        if txt.trim().is_empty() {
+            return Err("input text is empty".into());
+        }
+
+        let mut chunks = Vec::new();
+        for (chunk, chunk_lan, chunk_style, _) in self.plan_chunks(txt, lan, style_name, speed)? {
+            self.check_style(&chunk_style)?;
+
+            let phonemes = self
+                .phonemize(&chunk, &chunk_lan)
+                .map_err(|e| format!("eSpeak failed for language '{}': {}", chunk_lan, e))?;
+            let tokens = self.tokenize_phonemes(&phonemes)?.len();
+            if tokens == 0 {
+                return Err(format!(
+                    "language '{}' produced no phonemes for '{}'",
+                    chunk_lan, chunk
+                )
+                .into());
+            }
+
+            chunks.push(DryRunChunk {
+                text: chunk,
+                lan: chunk_lan,
+                style_name: chunk_style,
+                phonemes,
+                tokens,
+            });
+        }
+
+        let total_tokens: usize = chunks.iter().map(|c| c.tokens).sum();
+        Ok(DryRunReport {
+            estimated_duration_sec: estimate_duration_secs(total_tokens, speed),
+            chunks,
+        })
+    }
+
+    /// Map every whitespace-separated piece of `text` to the phonemes it is
+    /// synthesized as, so frontends can highlight skipped or rewritten text.
+    ///
+    /// Pieces are phonemized on their own, the same way word timestamps are
+    /// estimated, so phonemes can differ slightly from the in-context rendering.
+    pub fn analyze(&self, text: &str, lan: &str) -> Result<Vec<AnalyzedSegment>, Box<dyn Error>> {
+        let mut segments = Vec::new();
+        let mut piece_start: Option<usize> = None;
+        let chars: Vec<char> = text.chars().collect();
+
+        for i in 0..=chars.len() {
+            let is_space = chars.get(i).is_none_or(|c| c.is_whitespace());
+            match (piece_start, is_space) {
+                (None, false) => piece_start = Some(i),
+                (Some(start), true) => {
+                    let piece: String = chars[start..i].iter().collect();
+                    let raw_phonemes = self
+                        .phonemize(&piece, lan)
+                        .map_err(|e| format!("eSpeak failed for language '{}': {}", lan, e))?;
+                    let tokens =
+                        self.tokenize_phonemes(&self.apply_duration_profile(raw_phonemes))?;
+                    segments.push(AnalyzedSegment {
+                        char_start: start,
+                        char_end: i,
+                        text: piece,
+                        phonemes: tokens_to_phonemes_with_vocab(&tokens, self.vocab()),
+                        tokens: tokens.len(),
+                        spoken: !tokens.is_empty(),
+                    });
+                    piece_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(segments)
+    }
+
+    /// Check that `style_name` parses and only names voices this instance has
+    pub fn check_style(&self, style_name: &str) -> Result<(), String> {
+        for (name, _) in parse_style(style_name)? {
+            self.voice(name)?;
+        }
+        Ok(())
+    }
+
+    /// Check `style_name` like `check_style`, without running inference, for
+    /// validating user input as it is typed
+    pub fn validate_style(&self, style_name: &str) -> Result<(), KokoroError> {
+        for (name, _) in parse_style(style_name).map_err(KokoroError::InvalidStyle)? {
+            self.voice(name)
+                .map_err(|_| KokoroError::UnknownVoice(name.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// `validate_style`, also checking that every voice of `style_name`
+    /// belongs to language `lan` (see `lang::voice_matches_language`)
+    pub fn validate_style_for_language(
+        &self,
+        style_name: &str,
+        lan: &str,
+    ) -> Result<(), KokoroError> {
+        self.validate_style(style_name)?;
+        if lang::voice_matches_language(style_name, lan) {
+            Ok(())
+        } else {
+            Err(KokoroError::VoiceLanguageMismatch {
+                style: style_name.to_string(),
+                lan: lan.to_string(),
+            })
+        }
+    }
+
+    /// Style vectors of the voice `name`, or of the voice at that index in
+    /// `get_available_voices` order when no voice has that name
+    fn voice(&self, name: &str) -> Result<&Vec<[[f32; 256]; 1]>, String> {
+        if let Some(style) = self.styles.get(name) {
+            return Ok(style);
+        }
+        let Ok(index) = name.parse::<usize>() else {
+            return Err(format!("can not found from styles_map: {}", name));
+        };
+        let voices = self.get_available_voices();
+        voices
+            .get(index)
+            .and_then(|voice| self.styles.get(voice))
+            .ok_or_else(|| {
+                format!(
+                    "voice index {} is out of range, there are {} voices (0 to {})",
+                    index,
+                    voices.len(),
+                    voices.len().saturating_sub(1)
+                )
+            })
+    }
+
     fn process_internal(
         &self,
         txt: &str,
@@ -146,18 +989,45 @@ impl TTSKoko {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number_start: Option<usize>,
+        cancel: Option<&CancellationToken>,
+        granularity: TimestampGranularity,
         mut mode: ExecutionMode,
     ) -> Result<Option<(Vec<f32>, Vec<WordAlignment>)>, Box<dyn std::error::Error>> {
-        let chunks = self.split_text_into_chunks(txt, 500, lan);
+        let chunks = self.plan_chunks(txt, lan, style_name, speed)?;
+        let chunk_count = chunks.len();
 
         let start_chunk_num = chunk_number_start.unwrap_or(0);
 
         let debug_prefix = format_debug_prefix(request_id, instance_id);
 
+        let is_cancelled = |chunk_index: usize| {
+            let cancelled = cancel.is_some_and(|c| c.is_cancelled());
+            if cancelled {
+                tracing::debug!(
+                    "{} Cancelled after {} of {} chunks",
+                    debug_prefix,
+                    chunk_index,
+                    chunks.len()
+                );
+            }
+            cancelled
+        };
+
+        // Built once for the per-phoneme timestamps of every chunk
+        let reverse_vocab = reverse_vocab(self.vocab());
+
         let process_one_chunk = |chunk: &str,
+                                 lan: &str,
+                                 style_name: &str,
+                                 emphasized: bool,
                                  chunk_num: usize|
          -> Result<TtsOutput, Box<dyn std::error::Error>> {
             let chunk_info = format!("Chunk: {}, ", chunk_num);
+            let speed = if emphasized {
+                speed * EMPHASIS_SPEED
+            } else {
+                speed
+            };
             tracing::debug!("{} {}text: '{}'", debug_prefix, chunk_info, chunk);
 
             // A. Tokenize
@@ -168,10 +1038,10 @@ impl TTSKoko {
             };
 
             let (mut tokens, word_map) = if use_alignment {
-                self.tokenize_with_alignment(chunk, lan)
+                self.tokenize_with_alignment(chunk, lan)?
             } else {
                 // Fast path for audio-only models: single eSpeak pass, no per-item calls
-                self.tokenize_full_no_alignment(chunk, lan)
+                self.tokenize_full_no_alignment(chunk, lan)?
             };
 
             // Log token count (helpful for debugging context limits)
@@ -189,7 +1059,8 @@ impl TTSKoko {
             }
 
             // C. Style
-            let styles = self.mix_styles(style_name, tokens.len())?;
+            let styles =
+                self.mix_styles(style_name, self.style_tokens(tokens.len(), chunk_count))?;
 
             // D. Padding
             let mut padded_tokens = vec![0];
@@ -199,18 +1070,75 @@ impl TTSKoko {
             let index_offset = 1 + silence_count;
             let tokens_batch = vec![padded_tokens];
 
-            // E. Infer
-            let (chunk_audio_array, chunk_durations_opt) = self.model.lock().unwrap().infer(
-                tokens_batch,
-                styles,
-                speed,
-                request_id,
-                instance_id,
-                Some(chunk_num),
-            )?;
-
-            let chunk_audio: Vec<f32> = chunk_audio_array.iter().cloned().collect();
+            // E. Infer, checking the output for NaN/Inf samples
+            let mut retried = false;
+            let (mut chunk_audio, chunk_durations_opt) = loop {
+                let (chunk_audio_array, chunk_durations_opt) = self.model.lock().unwrap().infer(
+                    &tokens_batch,
+                    &styles,
+                    speed,
+                    self.init_config.expressiveness,
+                    lang::kokoro_language_id(lan),
+                    request_id,
+                    instance_id,
+                    Some(chunk_num),
+                )?;
+                // Custom exports may return something other than the waveform
+                check_audio_shape(chunk_audio_array.shape())?;
+                let mut chunk_audio: Vec<f32> = chunk_audio_array.iter().cloned().collect();
+
+                let non_finite = chunk_audio.iter().filter(|s| !s.is_finite()).count();
+                if non_finite == 0 {
+                    break (chunk_audio, chunk_durations_opt);
+                }
+                match self.init_config.on_nan {
+                    NanPolicy::Error => {
+                        return Err(format!(
+                            "model produced {} non-finite samples in chunk {}",
+                            non_finite, chunk_num
+                        )
+                        .into());
+                    }
+                    NanPolicy::Retry if !retried => {
+                        tracing::warn!(
+                            "{} {}{} non-finite samples, retrying the chunk",
+                            debug_prefix,
+                            chunk_info,
+                            non_finite
+                        );
+                        retried = true;
+                    }
+                    _ => {
+                        tracing::warn!(
+                            "{} {}{} non-finite samples replaced with silence",
+                            debug_prefix,
+                            chunk_info,
+                            non_finite
+                        );
+                        for sample in chunk_audio.iter_mut().filter(|s| !s.is_finite()) {
+                            *sample = 0.0;
+                        }
+                        break (chunk_audio, chunk_durations_opt);
+                    }
+                }
+            };
 
+            if let Some(max_pause_ms) = self.init_config.max_internal_pause_ms {
+                chunk_audio = clamp_pauses(
+                    &chunk_audio,
+                    SMART_GAP_SILENCE_THRESHOLD,
+                    ms_to_samples(max_pause_ms, 24000),
+                    PAUSE_FRAME_LEN,
+                );
+            }
+            if emphasized {
+                for sample in &mut chunk_audio {
+                    *sample *= EMPHASIS_GAIN;
+                }
+            }
+            if let Some(de_esser) = &self.init_config.de_ess {
+                de_ess(&mut chunk_audio, 24000, de_esser);
+            }
             // F. Calculate Alignments
             if let Some(durations) = chunk_durations_opt {
                 let mut alignments = Vec::new();
@@ -234,16 +1162,6 @@ impl TTSKoko {
                     }
                 }
 
-                // Punctuation pause table in seconds (tune as needed). We scale by 1/speed so faster speech shortens pauses.
-                let punct_pause_s = |label: &str| -> f32 {
-                    match label {
-                        "." | "!" | "?" => 0.300, // 300 ms
-                        "," => 0.150,             // 150 ms
-                        ";" | ":" => 0.200,
-                        _ => 0.0,
-                    }
-                };
-
                 for (word, start, end) in word_map {
                     let adj_start = start + index_offset;
                     let adj_end = end + index_offset;
@@ -252,7 +1170,7 @@ impl TTSKoko {
                     let is_punct = word.len() == 1 && ".,!?:;!?".contains(word.as_str());
                     if is_punct {
                         // Scale pauses by 1/speed so timestamps match rendered audio when speech rate changes.
-                        let pause_s = punct_pause_s(&word) / speed_safe;
+                        let pause_s = punctuation_pause_secs(&word) / speed_safe;
                         let pause_frames = pause_s * frames_per_sec;
                         let start_sec = chunk_time_cursor_frames / frames_per_sec;
                         let end_sec = (chunk_time_cursor_frames + pause_frames) / frames_per_sec;
@@ -260,6 +1178,7 @@ impl TTSKoko {
                             word: word.clone(),
                             start_sec,
                             end_sec,
+                            phonemes: Vec::new(),
                         });
                         chunk_time_cursor_frames += pause_frames;
                         continue;
@@ -275,10 +1194,35 @@ impl TTSKoko {
 
                         let start_sec = chunk_time_cursor_frames / frames_per_sec;
                         let end_sec = (chunk_time_cursor_frames + word_frames) / frames_per_sec;
+
+                        // One token per phoneme, each lasting its own predicted duration
+                        let mut phoneme_cursor_frames = chunk_time_cursor_frames;
+                        let phonemes = if granularity == TimestampGranularity::Word {
+                            Vec::new()
+                        } else {
+                            tokens_batch[0][adj_start..adj_end]
+                                .iter()
+                                .zip(&durations[adj_start..adj_end])
+                                .map(|(&token, &frames)| {
+                                    let start_sec = phoneme_cursor_frames / frames_per_sec;
+                                    phoneme_cursor_frames += frames;
+                                    PhonemeAlignment {
+                                        phoneme: reverse_vocab
+                                            .get(&(token as usize))
+                                            .map(char::to_string)
+                                            .unwrap_or_default(),
+                                        start_sec,
+                                        end_sec: phoneme_cursor_frames / frames_per_sec,
+                                    }
+                                })
+                                .collect()
+                        };
+
                         alignments.push(WordAlignment {
                             word,
                             start_sec,
                             end_sec,
+                            phonemes,
                         });
                         chunk_time_cursor_frames += word_frames;
                     }
@@ -302,6 +1246,10 @@ impl TTSKoko {
                         for al in &mut alignments {
                             al.start_sec *= s_clamped;
                             al.end_sec *= s_clamped;
+                            for ph in &mut al.phonemes {
+                                ph.start_sec *= s_clamped;
+                                ph.end_sec *= s_clamped;
+                            }
                         }
                     }
 
@@ -323,17 +1271,70 @@ impl TTSKoko {
                     }
                 }
 
+                if granularity == TimestampGranularity::Frame {
+                    let snap = |t: &mut f32| *t = (*t * frames_per_sec).round() / frames_per_sec;
+                    for al in &mut alignments {
+                        snap(&mut al.start_sec);
+                        snap(&mut al.end_sec);
+                        for ph in &mut al.phonemes {
+                            snap(&mut ph.start_sec);
+                            snap(&mut ph.end_sec);
+                        }
+                    }
+                }
+
                 Ok(TtsOutput::Aligned(chunk_audio, alignments))
             } else {
                 Ok(TtsOutput::Audio(chunk_audio))
             }
         };
 
+        let max_samples = self
+            .init_config
+            .max_duration_secs
+            .map(|secs| (secs.max(0.0) * 24000.0) as usize);
+        let warn_truncated = |chunk_index: usize| {
+            tracing::warn!(
+                "{} Output reached the {:.1}s limit, skipping {} of {} chunks",
+                debug_prefix,
+                self.init_config.max_duration_secs.unwrap_or(0.0),
+                chunks.len() - chunk_index - 1,
+                chunks.len()
+            );
+        };
+
         match &mut mode {
             ExecutionMode::Stream(callback) => {
-                for (i, chunk) in chunks.iter().enumerate() {
-                    let output = process_one_chunk(chunk, start_chunk_num + i)?;
+                let mut emitted = 0;
+                for (i, (chunk, chunk_lan, chunk_style, emphasized)) in chunks.iter().enumerate() {
+                    if is_cancelled(i) {
+                        break;
+                    }
+                    let mut output = process_one_chunk(
+                        chunk,
+                        chunk_lan,
+                        chunk_style,
+                        *emphasized,
+                        start_chunk_num + i,
+                    )?;
+                    emitted += output.audio_len();
+                    let reached_max = match max_samples {
+                        Some(max) if emitted >= max => {
+                            let keep = output.audio_len() - (emitted - max);
+                            output.truncate(keep, 24000.0);
+                            true
+                        }
+                        _ => false,
+                    };
+                    // Streamed chunks are final as they are
+                    if let Some(threshold) = self.init_config.limiter_threshold {
+                        soft_limit(output.audio_mut(), threshold);
+                    }
                     callback(output)?;
+                    if reached_max {
+                        warn_truncated(i);
+                        break;
+                    }
                 }
                 Ok(None)
             }
@@ -341,30 +1342,91 @@ impl TTSKoko {
             ExecutionMode::Batch => {
                 let mut batch_audio = Vec::new();
                 let mut batch_alignments = Vec::new();
-                let mut global_time_offset = 0.0;
                 let sample_rate = 24000.0;
+                let crossfade = ms_to_samples(self.init_config.chunk_crossfade_ms, 24000);
+                let speed_safe = if speed > 1e-6 { speed } else { 1.0 };
+
+                // Append a chunk, returning where its first sample landed in the batch
+                // (negative when smart gaps trimmed its leading silence)
+                let append_chunk = |batch_audio: &mut Vec<f32>, audio: &[f32], text: &str| {
+                    if !self.init_config.smart_gaps {
+                        return crossfade_append(batch_audio, audio, crossfade) as isize;
+                    }
+
+                    let margin = ms_to_samples(SMART_GAP_MARGIN_MS, 24000);
+                    let loud = trim_silence(audio, SMART_GAP_SILENCE_THRESHOLD);
+                    if loud.is_empty() {
+                        return batch_audio.len() as isize;
+                    }
+                    let start = loud.start.saturating_sub(margin);
+                    let end = (loud.end + margin).min(audio.len());
+
+                    let placed = crossfade_append(batch_audio, &audio[start..end], crossfade);
+
+                    // Uniform pause after the chunk, by its final punctuation
+                    let last = text.trim_end().chars().last().map(String::from);
+                    let pause = match last.as_deref().map(punctuation_pause_secs) {
+                        Some(pause) if pause > 0.0 => pause,
+                        _ => SMART_GAP_DEFAULT_SECS,
+                    };
+                    let pause_samples = (pause / speed_safe * sample_rate) as usize;
+                    batch_audio.resize(batch_audio.len() + pause_samples, 0.0);
 
-                for (i, chunk) in chunks.iter().enumerate() {
-                    let output = process_one_chunk(chunk, start_chunk_num + i)?;
+                    placed as isize - start as isize
+                };
+
+                for (i, (chunk, chunk_lan, chunk_style, emphasized)) in chunks.iter().enumerate() {
+                    // Cancelled requests return the audio synthesized so far
+                    if is_cancelled(i) {
+                        break;
+                    }
+                    let output = process_one_chunk(
+                        chunk,
+                        chunk_lan,
+                        chunk_style,
+                        *emphasized,
+                        start_chunk_num + i,
+                    )?;
 
                     match output {
                         TtsOutput::Aligned(audio, alignments) => {
-                            let duration = audio.len() as f32 / sample_rate;
-                            batch_audio.extend_from_slice(&audio);
+                            // Chunks start earlier by the cross-faded overlap or trimmed
+                            // silence, shift timestamps to match
+                            let start = append_chunk(&mut batch_audio, &audio, chunk);
+                            let global_time_offset = start as f32 / sample_rate;
 
                             for mut align in alignments {
-                                align.start_sec += global_time_offset;
-                                align.end_sec += global_time_offset;
+                                align.start_sec = (align.start_sec + global_time_offset).max(0.0);
+                                align.end_sec = (align.end_sec + global_time_offset).max(0.0);
+                                for ph in &mut align.phonemes {
+                                    ph.start_sec = (ph.start_sec + global_time_offset).max(0.0);
+                                    ph.end_sec = (ph.end_sec + global_time_offset).max(0.0);
+                                }
                                 batch_alignments.push(align);
                             }
-                            global_time_offset += duration;
                         }
                         TtsOutput::Audio(audio) => {
-                            let duration = audio.len() as f32 / sample_rate;
-                            batch_audio.extend_from_slice(&audio);
-                            global_time_offset += duration;
+                            append_chunk(&mut batch_audio, &audio, chunk);
                         }
                     }
+
+                    if let Some(max) = max_samples.filter(|&max| batch_audio.len() >= max) {
+                        truncate_with_fade(&mut batch_audio, max);
+                        batch_alignments.retain(|w| w.start_sec < max as f32 / sample_rate);
+                        warn_truncated(i);
+                        break;
+                    }
+                }
+
+                if self.init_config.smart_gaps {
+                    // No pause after the last chunk
+                    let end = trim_silence(&batch_audio, SMART_GAP_SILENCE_THRESHOLD).end;
+                    let margin = ms_to_samples(SMART_GAP_MARGIN_MS, 24000);
+                    batch_audio.truncate((end + margin).min(batch_audio.len()));
+                }
+                // Last, as cross-fades and smart gaps change the levels
+                if let Some(threshold) = self.init_config.limiter_threshold {
+                    soft_limit(&mut batch_audio, threshold);
                 }
                 Ok(Some((batch_audio, batch_alignments)))
             }
@@ -376,20 +1438,16 @@ impl TTSKoko {
         &self,
         text: &str,
         lan: &str,
-    ) -> (Vec<i64>, Vec<(String, usize, usize)>) {
+    ) -> Result<(Vec<i64>, Vec<(String, usize, usize)>), Box<dyn Error>> {
         // We will produce tokens from the full, context-aware phonemes (best prosody)
         // and build an alignment map by estimating per-word token spans using
         // per-word phoneme tokenization. This keeps audio natural while providing
         // robust timestamps even when eSpeak merges words (e.g., "the model").
 
         // 1) Full-phrase phonemes and tokens (prosody source)
-        let full_phonemes = {
-            let _guard = ESPEAK_MUTEX.lock().unwrap();
-            text_to_phonemes(text, lan, None, true, false)
-                .unwrap_or_default()
-                .join("")
-        };
-        let all_tokens = tokenize(&full_phonemes);
+        let full_phonemes = self.phonemize(text, lan).unwrap_or_default();
+        let full_phonemes = self.apply_duration_profile(full_phonemes);
+        let all_tokens = self.tokenize_phonemes(&full_phonemes)?;
 
         // 2) Build a tokenization plan per original "word or punctuation" unit.
         //    We want punctuation timestamps too, so we split words and punctuation as separate items.
@@ -443,13 +1501,13 @@ impl TTSKoko {
                 per_item_token_counts.push(0);
                 per_item_is_punct.push(true);
             } else {
-                let ph = {
-                    let _guard = ESPEAK_MUTEX.lock().unwrap();
-                    text_to_phonemes(it, lan, None, true, false)
-                        .unwrap_or_default()
-                        .join("")
-                };
-                let cnt = tokenize(&ph).len();
+                let ph = self.phonemize(it, lan).unwrap_or_default();
+                let ph = self.apply_duration_profile(ph);
+                // Unknown phonemes were already reported by the full-phrase pass
+                let cnt =
+                    tokenize_with_vocab(&ph, self.vocab(), self.init_config.on_unknown_phoneme)
+                        .map(|(tokens, _)| tokens.len())
+                        .unwrap_or(0);
                 per_item_token_counts.push(cnt);
                 per_item_is_punct.push(false);
             }
@@ -508,80 +1566,203 @@ impl TTSKoko {
             }
         }
 
-        // If our mapping under-ran due to rounding issues, extend the last non-punct item to cover all tokens
-        if cursor < target_len {
-            if let Some(last_non_punct_pos) =
-                (0..word_map.len()).rev().find(|&i| !(per_item_is_punct[i]))
-            {
-                let (w, s, _e) = &word_map[last_non_punct_pos];
-                word_map[last_non_punct_pos] = (w.clone(), *s, target_len);
-            }
+        // If our mapping under-ran due to rounding issues, extend the last non-punct item to cover all tokens
+        if cursor < target_len {
+            if let Some(last_non_punct_pos) =
+                (0..word_map.len()).rev().find(|&i| !(per_item_is_punct[i]))
+            {
+                let (w, s, _e) = &word_map[last_non_punct_pos];
+                word_map[last_non_punct_pos] = (w.clone(), *s, target_len);
+            }
+        }
+
+        // If there are absolutely no tokens (empty text), return empty mapping
+        Ok((all_tokens, word_map))
+    }
+
+    /// Fast tokenization path for audio-only models (no timestamps)
+    /// Performs a single eSpeak phonemization for the full text and returns tokens with an empty word map.
+    fn tokenize_full_no_alignment(
+        &self,
+        text: &str,
+        lan: &str,
+    ) -> Result<(Vec<i64>, Vec<(String, usize, usize)>), Box<dyn Error>> {
+        let full_phonemes = self.phonemize(text, lan).unwrap_or_default();
+        let full_phonemes = self.apply_duration_profile(full_phonemes);
+        let all_tokens = self.tokenize_phonemes(&full_phonemes)?;
+        Ok((all_tokens, Vec::new()))
+    }
+
+    /// Number of tokens the model gets for `text`, for sizing chunks: the
+    /// duration profile, vocab and unknown-phoneme policy of inference, without
+    /// its warnings
+    fn count_tokens(&self, text: &str, lan: &str) -> usize {
+        let phonemes = self.apply_duration_profile(self.phonemize(text, lan).unwrap_or_default());
+        let policy = self.init_config.on_unknown_phoneme;
+        match tokenize_with_vocab(&phonemes, self.vocab(), policy) {
+            Ok((tokens, _)) => tokens.len(),
+            // Inference fails on these, count every phoneme to stay on the safe side
+            Err(_) => phonemes.chars().count(),
+        }
+    }
+
+    /// Phonemize `text` with eSpeak, splicing in the pronunciations of the
+    /// configured lexicon
+    fn phonemize(&self, text: &str, lan: &str) -> Result<String, String> {
+        TextPhonemizer::from_config(&self.init_config).phonemize(text, lan)
+    }
+
+    /// Token length to pick the style vector for in text of `chunk_count`
+    /// chunks, see `InitConfig::consistent_voice`
+    fn style_tokens(&self, tokens_len: usize, chunk_count: usize) -> usize {
+        if self.init_config.consistent_voice && chunk_count > 1 {
+            CONSISTENT_VOICE_STYLE_TOKENS
+        } else {
+            tokens_len
+        }
+    }
+
+    /// Intermediate acoustic features of `txt` instead of audio, for comparing
+    /// vocoders. Only works with ONNX graphs exported with such an extra output
+    /// (see `OrtKoko::feature_output`); the features of each chunk are
+    /// concatenated along their last (time) axis.
+    pub fn tts_features(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+    ) -> Result<Array2<f32>, Box<dyn Error>> {
+        let mut chunk_features = Vec::new();
+        let chunks = self.plan_chunks(txt, lan, style_name, speed)?;
+        let chunk_count = chunks.len();
+        for (chunk, chunk_lan, chunk_style, emphasized) in chunks {
+            let speed = if emphasized {
+                speed * EMPHASIS_SPEED
+            } else {
+                speed
+            };
+            let (tokens, _) = self.tokenize_full_no_alignment(&chunk, &chunk_lan)?;
+            let styles =
+                self.mix_styles(&chunk_style, self.style_tokens(tokens.len(), chunk_count))?;
+
+            let mut padded_tokens = vec![0];
+            padded_tokens.extend(tokens);
+            padded_tokens.push(0);
+
+            let (shape, data) = self.model.lock().unwrap().infer_features(
+                &[padded_tokens],
+                &styles,
+                speed,
+                self.init_config.expressiveness,
+                lang::kokoro_language_id(&chunk_lan),
+            )?;
+            // Drop the batch dimension, e.g. [1, 80, T] -> [80, T]
+            let dims: Vec<usize> = match shape.iter().position(|&d| d != 1) {
+                Some(first) if shape.len() - first <= 2 => shape[first..].to_vec(),
+                _ => shape.clone(),
+            };
+            let features = match dims[..] {
+                [channels, frames] => Array2::from_shape_vec((channels, frames), data)?,
+                [frames] => Array2::from_shape_vec((1, frames), data)?,
+                _ => {
+                    return Err(format!("expected 2-D features, got shape {:?}", shape).into());
+                }
+            };
+            chunk_features.push(features);
+        }
+
+        let views: Vec<_> = chunk_features.iter().map(|f| f.view()).collect();
+        if views.is_empty() {
+            return Err("input text is empty".into());
         }
+        Ok(ndarray::concatenate(Axis(1), &views)?)
+    }
 
-        // If there are absolutely no tokens (empty text), return empty mapping
-        (all_tokens, word_map)
+    /// The phoneme-to-id vocab of the model, see `InitConfig::vocab`
+    fn vocab(&self) -> &HashMap<char, usize> {
+        self.init_config.vocab.as_deref().unwrap_or(&VOCAB)
     }
 
-    /// Fast tokenization path for audio-only models (no timestamps)
-    /// Performs a single eSpeak phonemization for the full text and returns tokens with an empty word map.
-    fn tokenize_full_no_alignment(
+    /// Tokenize according to the configured unknown-phoneme policy, logging the
+    /// phonemes the model vocab doesn't know
+    fn tokenize_phonemes(&self, phonemes: &str) -> Result<Vec<i64>, Box<dyn Error>> {
+        let policy = self.init_config.on_unknown_phoneme;
+        let (tokens, unknown) = tokenize_with_vocab(phonemes, self.vocab(), policy)?;
+        if !unknown.is_empty() {
+            let listed: Vec<String> = unknown
+                .iter()
+                .map(|c| format!("'{}' (U+{:04X})", c, *c as u32))
+                .collect();
+            let action = match policy {
+                UnknownPhonemePolicy::Nearest => "mapped to the nearest known phoneme",
+                _ => "skipped",
+            };
+            tracing::warn!(
+                "Phonemes not in the model vocab {}: {}",
+                action,
+                listed.join(", ")
+            );
+        }
+        Ok(tokens)
+    }
+
+    /// Apply the configured duration profile (if any) to a phoneme string
+    fn apply_duration_profile(&self, phonemes: String) -> String {
+        match &self.init_config.duration_profile {
+            Some(profile) => profile.apply(&phonemes),
+            None => phonemes,
+        }
+    }
+
+    /// Split `text` into chunks of whole sentences where possible. With
+    /// `ends_sentence` false the text stops mid-sentence, so its last sentence
+    /// isn't auto-punctuated.
+    fn split_text_into_chunks(
         &self,
         text: &str,
+        max_tokens: usize,
         lan: &str,
-    ) -> (Vec<i64>, Vec<(String, usize, usize)>) {
-        let full_phonemes = {
-            let _guard = ESPEAK_MUTEX.lock().unwrap();
-            text_to_phonemes(text, lan, None, true, false)
-                .unwrap_or_default()
-                .join("")
-        };
-        let all_tokens = tokenize(&full_phonemes);
-        (all_tokens, Vec::new())
-    }
-
-    fn split_text_into_chunks(&self, text: &str, max_tokens: usize, lan: &str) -> Vec<String> {
+        ends_sentence: bool,
+    ) -> Vec<String> {
         let mut chunks = Vec::new();
 
-        // First split by sentences - using common sentence ending punctuation
-        let sentences: Vec<&str> = text
-            .split(|c| c == '.' || c == '?' || c == '!' || c == ';')
-            .filter(|s| !s.trim().is_empty())
-            .collect();
+        // First split by sentences, keeping their own punctuation
+        let sentences = lang::split_sentences(text, lan);
+        let last = sentences.len().saturating_sub(1);
+        // CJK text has no spaces between words or sentences
+        let cjk = lang::is_cjk_language(lan);
+        let separator = if cjk { "" } else { " " };
 
         let mut current_chunk = String::new();
 
-        for sentence in sentences {
-            // Clean up the sentence and add back punctuation
-            let sentence = format!("{}.", sentence.trim());
-
-            // Convert to phonemes to check token count
-            let sentence_phonemes = {
-                let _guard = ESPEAK_MUTEX.lock().unwrap();
-                text_to_phonemes(&sentence, lan, None, true, false)
-                    .unwrap_or_default()
-                    .join("")
+        for (i, sentence) in sentences.into_iter().enumerate() {
+            let sentence = if self.init_config.auto_punctuate && (ends_sentence || i < last) {
+                lang::terminate_sentence(&sentence)
+            } else {
+                sentence
             };
-            let token_count = tokenize(&sentence_phonemes).len();
+
+            let token_count = self.count_tokens(&sentence, lan);
 
             if token_count > max_tokens {
-                // If single sentence is too long, split by words
-                let words: Vec<&str> = sentence.split_whitespace().collect();
+                // If single sentence is too long, split by words, or by
+                // clauses for CJK
+                let words: Vec<String> = if cjk {
+                    lang::split_cjk_clauses(&sentence)
+                } else {
+                    sentence.split_whitespace().map(str::to_string).collect()
+                };
                 let mut word_chunk = String::new();
 
                 for word in words {
                     let test_chunk = if word_chunk.is_empty() {
                         word.to_string()
                     } else {
-                        format!("{} {}", word_chunk, word)
+                        format!("{}{}{}", word_chunk, separator, word)
                     };
 
-                    let test_phonemes = {
-                        let _guard = ESPEAK_MUTEX.lock().unwrap();
-                        text_to_phonemes(&test_chunk, lan, None, true, false)
-                            .unwrap_or_default()
-                            .join("")
-                    };
-                    let test_tokens = tokenize(&test_phonemes).len();
+                    let test_tokens = self.count_tokens(&test_chunk, lan);
 
                     if test_tokens > max_tokens {
                         if !word_chunk.is_empty() {
@@ -598,14 +1779,8 @@ impl TTSKoko {
                 }
             } else if !current_chunk.is_empty() {
                 // Try to append to current chunk
-                let test_text = format!("{} {}", current_chunk, sentence);
-                let test_phonemes = {
-                    let _guard = ESPEAK_MUTEX.lock().unwrap();
-                    text_to_phonemes(&test_text, lan, None, true, false)
-                        .unwrap_or_default()
-                        .join("")
-                };
-                let test_tokens = tokenize(&test_phonemes).len();
+                let test_text = format!("{}{}{}", current_chunk, separator, sentence);
+                let test_tokens = self.count_tokens(&test_text, lan);
 
                 if test_tokens > max_tokens {
                     // If combining would exceed limit, start new chunk
@@ -741,6 +1916,9 @@ impl TTSKoko {
         chunks
     }
 
+    /// Synthesize `txt` with the timing of each word, and of its phonemes
+    /// unless `granularity` is `Word`. Returns `None` when the model doesn't
+    /// output durations
     pub fn tts_timestamped_raw_audio(
         &self,
         txt: &str,
@@ -751,6 +1929,7 @@ impl TTSKoko {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
+        granularity: TimestampGranularity,
     ) -> Result<Option<(Vec<f32>, Vec<WordAlignment>)>, Box<dyn Error>> {
         self.process_internal(
             txt,
@@ -761,6 +1940,8 @@ impl TTSKoko {
             request_id,
             instance_id,
             chunk_number,
+            None,
+            granularity,
             ExecutionMode::Batch,
         )
     }
@@ -785,12 +1966,142 @@ impl TTSKoko {
             request_id,
             instance_id,
             chunk_number,
+            None,
+            TimestampGranularity::Word,
+            ExecutionMode::Batch,
+        )?;
+
+        Ok(audio.unwrap().0)
+    }
+
+    /// Like `tts_raw_audio`, but stops between chunks once `cancel` is cancelled
+    /// and returns the audio synthesized up to that point
+    pub fn tts_raw_audio_cancellable(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let audio = self.process_internal(
+            txt,
+            lan,
+            style_name,
+            speed,
+            initial_silence,
+            request_id,
+            instance_id,
+            chunk_number,
+            Some(cancel),
+            TimestampGranularity::Word,
             ExecutionMode::Batch,
         )?;
 
         Ok(audio.unwrap().0)
     }
 
+    /// Like `tts_raw_audio_streaming`, but stops yielding chunks once `cancel`
+    /// is cancelled
+    pub fn tts_raw_audio_streaming_cancellable<F>(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+        cancel: &CancellationToken,
+        mut chunk_callback: F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(Vec<f32>) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        let mut adapter = |output: TtsOutput| -> Result<(), Box<dyn std::error::Error>> {
+            chunk_callback(output.raw_output().0)
+        };
+
+        self.process_internal(
+            txt,
+            lan,
+            style_name,
+            speed,
+            initial_silence,
+            request_id,
+            instance_id,
+            chunk_number,
+            Some(cancel),
+            TimestampGranularity::Word,
+            ExecutionMode::Stream(&mut adapter),
+        )?;
+
+        Ok(())
+    }
+
+    /// Synthesize into a caller-owned buffer, e.g. a preallocated shared-memory
+    /// ring for low-latency IPC.
+    ///
+    /// Samples are copied into `buffer` as chunks are generated. Whenever the
+    /// buffer fills up, `on_full` is called with it to signal that more space is
+    /// needed; writing then restarts at the beginning of the buffer. Returns the
+    /// number of samples written to the start of `buffer` after the last
+    /// `on_full`, which the caller still has to consume.
+    pub fn tts_into<F>(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        buffer: &mut [f32],
+        mut on_full: F,
+    ) -> Result<usize, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&[f32]) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        if buffer.is_empty() {
+            return Err("tts_into needs a non-empty buffer".into());
+        }
+
+        let mut written = 0;
+        let mut adapter = |output: TtsOutput| -> Result<(), Box<dyn std::error::Error>> {
+            let (audio, _) = output.raw_output();
+            let mut samples = audio.as_slice();
+            while !samples.is_empty() {
+                let n = samples.len().min(buffer.len() - written);
+                buffer[written..written + n].copy_from_slice(&samples[..n]);
+                written += n;
+                samples = &samples[n..];
+                if written == buffer.len() {
+                    on_full(buffer)?;
+                    written = 0;
+                }
+            }
+            Ok(())
+        };
+
+        self.process_internal(
+            txt,
+            lan,
+            style_name,
+            speed,
+            None,
+            None,
+            None,
+            None,
+            None,
+            TimestampGranularity::Word,
+            ExecutionMode::Stream(&mut adapter),
+        )?;
+
+        Ok(written)
+    }
+
     /// Streaming version that yields audio chunks as they're generated
     pub fn tts_raw_audio_streaming<F>(
         &self,
@@ -820,6 +2131,8 @@ impl TTSKoko {
             request_id,
             instance_id,
             chunk_number,
+            None,
+            TimestampGranularity::Word,
             // Pass the ADAPTER, not the original callback
             ExecutionMode::Stream(&mut adapter),
         )?;
@@ -827,6 +2140,135 @@ impl TTSKoko {
         Ok(())
     }
 
+    /// Like `tts_raw_audio_streaming`, but synthesis runs on its own thread up
+    /// to `lookahead` chunks ahead of `chunk_callback`, in a bounded queue, so
+    /// a chunk that is slow to synthesize doesn't stall the delivery of the
+    /// ones before it. The first `lookahead` chunks are queued before the
+    /// first callback, trading initial latency for steadier output. A
+    /// `lookahead` of 0 synthesizes and delivers in turn like
+    /// `tts_raw_audio_streaming`
+    pub fn tts_raw_audio_streaming_lookahead<F>(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+        lookahead: usize,
+        mut chunk_callback: F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(Vec<f32>) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        if lookahead == 0 {
+            return self.tts_raw_audio_streaming(
+                txt,
+                lan,
+                style_name,
+                speed,
+                initial_silence,
+                None,
+                None,
+                None,
+                chunk_callback,
+            );
+        }
+
+        let (chunk_tx, chunk_rx) = std::sync::mpsc::sync_channel::<Vec<f32>>(lookahead);
+        std::thread::scope(|scope| -> Result<(), Box<dyn std::error::Error>> {
+            let producer = scope.spawn(move || {
+                // Sending fails once the consumer stopped, which ends synthesis
+                self.tts_raw_audio_streaming(
+                    txt,
+                    lan,
+                    style_name,
+                    speed,
+                    initial_silence,
+                    None,
+                    None,
+                    None,
+                    |chunk| {
+                        chunk_tx
+                            .send(chunk)
+                            .map_err(|_| "the chunk consumer stopped".into())
+                    },
+                )
+                .map_err(|e| e.to_string())
+            });
+
+            // Owns the receiver, so returning early unblocks the producer
+            let consume = move || -> Result<(), Box<dyn std::error::Error>> {
+                let mut queued = Vec::with_capacity(lookahead);
+                let mut prefilled = false;
+                for chunk in chunk_rx {
+                    queued.push(chunk);
+                    prefilled |= queued.len() >= lookahead;
+                    if prefilled {
+                        for chunk in queued.drain(..) {
+                            chunk_callback(chunk)?;
+                        }
+                    }
+                }
+                // Texts shorter than the look-ahead
+                for chunk in queued {
+                    chunk_callback(chunk)?;
+                }
+                Ok(())
+            };
+            let consumed = consume();
+            let produced = producer
+                .join()
+                .map_err(|_| "the synthesis thread panicked")?;
+            consumed?;
+            produced.map_err(Into::into)
+        })
+    }
+
+    /// Like `tts_raw_audio_streaming`, but yields the audio already encoded
+    /// as `format`, ready to forward to a client. The chunks concatenate into
+    /// one stream: the WAV header comes with the first chunk and a single MP3
+    /// encoder runs across chunks, so frames continue without gaps. Chunks the
+    /// encoder buffers completely are not passed on.
+    pub fn tts_stream_encoded<F>(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+        format: StreamFormat,
+        mut chunk_callback: F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(Vec<u8>) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        let mut encoder = StreamEncoder::new(format, self.sample_rate())?;
+        self.tts_raw_audio_streaming(
+            txt,
+            lan,
+            style_name,
+            speed,
+            initial_silence,
+            None,
+            None,
+            None,
+            |audio| {
+                let data = encoder.encode(&audio)?;
+                if data.is_empty() {
+                    Ok(())
+                } else {
+                    chunk_callback(data)
+                }
+            },
+        )?;
+
+        let tail = encoder.finish()?;
+        if !tail.is_empty() {
+            chunk_callback(tail)?;
+        }
+        Ok(())
+    }
+
     /// Streaming version that strictly requires a timestamped model.
     /// Yields audio chunks + alignment data via the callback as they are generated.
     pub fn tts_timestamped_raw_audio_streaming<F>(
@@ -839,6 +2281,7 @@ impl TTSKoko {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
+        granularity: TimestampGranularity,
         mut chunk_callback: F,
     ) -> Result<(), Box<dyn std::error::Error>>
     where
@@ -859,121 +2302,278 @@ impl TTSKoko {
             request_id,
             instance_id,
             chunk_number,
+            None,
+            granularity,
             ExecutionMode::Stream(&mut adapter),
         )?;
 
         Ok(())
     }
 
-    pub fn tts(
+    /// Sample rate of the audio this instance produces, in Hz
+    pub fn sample_rate(&self) -> u32 {
+        self.init_config.sample_rate
+    }
+
+    /// Synthesize to a WAV file, returning the level statistics of the audio
+    pub fn tts(&self, opts: TTSOpts) -> Result<AudioStats, Box<dyn std::error::Error>> {
+        let save_path = opts.save_path;
+        let file = std::io::BufWriter::new(File::create(save_path)?);
+        let stats = self.tts_to_writer(file, opts)?;
+
+        tracing::info!("Audio saved to {}", save_path);
+        Ok(stats)
+    }
+
+    /// Like `tts`, but writes the WAV file to `writer`, e.g. a
+    /// `Cursor<Vec<u8>>` for a complete WAV in memory. The sizes in the header
+    /// are patched once the audio is written, which is what needs `Seek`.
+    /// `opts.save_path` is not used.
+    pub fn tts_to_writer<W: std::io::Write + std::io::Seek>(
         &self,
+        writer: W,
         TTSOpts {
             txt,
             lan,
             style_name,
-            save_path,
+            save_path: _,
             mono,
             speed,
             initial_silence,
+            low_memory,
         }: TTSOpts,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let audio = self.tts_raw_audio(
-            &txt,
-            lan,
-            style_name,
+    ) -> Result<AudioStats, Box<dyn std::error::Error>> {
+        let spec = hound::WavSpec {
+            channels: if mono { 1 } else { 2 },
+            sample_rate: self.init_config.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::new(writer, spec)?;
+        let mut write_samples = |audio: &[f32]| -> Result<(), hound::Error> {
+            for &sample in audio {
+                for _ in 0..spec.channels {
+                    writer.write_sample(sample)?;
+                }
+            }
+            Ok(())
+        };
+
+        let stats = if low_memory {
+            // Only the current chunk is held; hound patches the sizes in the
+            // header when the writer is finalized
+            let mut stats = AudioStats::default();
+            let mut len = 0;
+            self.tts_raw_audio_streaming(
+                txt,
+                lan,
+                style_name,
+                speed,
+                initial_silence,
+                None,
+                None,
+                None,
+                |chunk| {
+                    write_samples(&chunk)?;
+                    stats = stats.combine(len, AudioStats::measure(&chunk), chunk.len());
+                    len += chunk.len();
+                    Ok(())
+                },
+            )?;
+            stats
+        } else {
+            let audio = self.tts_raw_audio(
+                txt,
+                lan,
+                style_name,
+                speed,
+                initial_silence,
+                None,
+                None,
+                None,
+            )?;
+            write_samples(&audio)?;
+            AudioStats::measure(&audio)
+        };
+        writer.finalize()?;
+        Ok(stats)
+    }
+
+    /// Synthesize `txt` one sentence at a time, keeping each sentence's audio
+    /// for `resynthesize`. Sentences are joined without cross-fades or smart
+    /// gaps, each keeping the pause the model renders after it
+    pub fn tts_sentences(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+    ) -> Result<TTSResult, Box<dyn std::error::Error>> {
+        let empty = TTSResult {
+            lan: lan.to_string(),
+            style_name: style_name.to_string(),
             speed,
-            initial_silence,
-            None,
-            None,
-            None,
-        )?;
+            sentences: Vec::new(),
+        };
+        self.resynthesize(&empty, txt)
+    }
 
-        // Save to file
-        if mono {
-            let spec = hound::WavSpec {
-                channels: 1,
-                sample_rate: self.init_config.sample_rate,
-                bits_per_sample: 32,
-                sample_format: hound::SampleFormat::Float,
-            };
+    /// `tts_sentences`, calling `on_sentence` with the text and audio of each
+    /// sentence as soon as it is synthesized, e.g. to highlight the sentence
+    /// being spoken in a read-along UI. An error from the callback stops
+    /// synthesis and is returned
+    pub fn tts_sentences_with<F>(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        mut on_sentence: F,
+    ) -> Result<TTSResult, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, &[f32]) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        let empty = TTSResult {
+            lan: lan.to_string(),
+            style_name: style_name.to_string(),
+            speed,
+            sentences: Vec::new(),
+        };
+        self.resynthesize_each(&empty, txt, &mut on_sentence)
+    }
 
-            let mut writer = hound::WavWriter::create(save_path, spec)?;
-            for &sample in &audio {
-                writer.write_sample(sample)?;
-            }
-            writer.finalize()?;
-        } else {
-            let spec = hound::WavSpec {
-                channels: 2,
-                sample_rate: self.init_config.sample_rate,
-                bits_per_sample: 32,
-                sample_format: hound::SampleFormat::Float,
-            };
+    /// Synthesize the edited text `txt` with the language, style and speed of
+    /// `previous`, reusing the audio of every sentence whose text is unchanged
+    /// and synthesizing only new or edited sentences. Sentences may also move
+    /// or repeat; matching is by their exact text
+    pub fn resynthesize(
+        &self,
+        previous: &TTSResult,
+        txt: &str,
+    ) -> Result<TTSResult, Box<dyn std::error::Error>> {
+        self.resynthesize_each(previous, txt, &mut |_, _| Ok(()))
+    }
 
-            let mut writer = hound::WavWriter::create(save_path, spec)?;
-            for &sample in &audio {
-                writer.write_sample(sample)?;
-                writer.write_sample(sample)?;
-            }
-            writer.finalize()?;
+    /// `resynthesize`, handing each sentence's audio (reused or new) to
+    /// `on_sentence` in order
+    fn resynthesize_each(
+        &self,
+        previous: &TTSResult,
+        txt: &str,
+        on_sentence: &mut dyn FnMut(&str, &[f32]) -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<TTSResult, Box<dyn std::error::Error>> {
+        let known: HashMap<&str, &[f32]> = previous
+            .sentences
+            .iter()
+            .map(|s| (s.text.as_str(), s.audio.as_slice()))
+            .collect();
+
+        let mut sentences = Vec::new();
+        let mut synthesized = 0;
+        for text in lang::split_sentences(txt, &previous.lan) {
+            let audio = match known.get(text.as_str()) {
+                Some(audio) => audio.to_vec(),
+                None => {
+                    synthesized += 1;
+                    self.tts_raw_audio(
+                        &text,
+                        &previous.lan,
+                        &previous.style_name,
+                        previous.speed,
+                        None,
+                        None,
+                        None,
+                        None,
+                    )?
+                }
+            };
+            on_sentence(&text, &audio)?;
+            sentences.push(SentenceAudio { text, audio });
         }
-        eprintln!("Audio saved to {}", save_path);
-        Ok(())
+        tracing::debug!(
+            "Synthesized {} of {} sentences, reused the rest",
+            synthesized,
+            sentences.len()
+        );
+
+        Ok(TTSResult {
+            sentences,
+            ..previous.clone()
+        })
     }
 
+    /// Look up or blend the style vector for `style_name`, see `parse_style`
     pub fn mix_styles(
         &self,
         style_name: &str,
         tokens_len: usize,
     ) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
-        if !style_name.contains("+") {
-            if let Some(style) = self.styles.get(style_name) {
-                let styles = vec![style[tokens_len][0].to_vec()];
-                Ok(styles)
-            } else {
-                Err(format!("can not found from styles_map: {}", style_name).into())
-            }
+        if !is_blend(style_name) {
+            let style = self.voice(style_name)?;
+            Ok(vec![style[tokens_len][0].to_vec()])
+        } else if self.blend_cache.is_enabled() {
+            let table = self
+                .blend_cache
+                .get_or_try_insert(&style_name.to_string(), || {
+                    self.blend_table(style_name).map(Arc::new)
+                })?;
+            Ok(vec![table[tokens_len][0].to_vec()])
         } else {
-            eprintln!("parsing style mix");
-            let styles: Vec<&str> = style_name.split('+').collect();
+            tracing::debug!("parsing style mix");
+            let parts = parse_style(style_name)?;
+            tracing::debug!("styles: {:?}", parts);
 
-            let mut style_names = Vec::new();
-            let mut style_portions = Vec::new();
+            let mut blended_style = vec![vec![0.0; 256]; 1];
 
-            for style in styles {
-                if let Some((name, portion)) = style.split_once('.') {
-                    if let Ok(portion) = portion.parse::<f32>() {
-                        style_names.push(name);
-                        style_portions.push(portion * 0.1);
-                    }
+            for (name, portion) in parts {
+                let style = self.voice(name)?;
+                let style_slice = &style[tokens_len][0]; // This is a [256] array
+                // Blend into the blended_style
+                for j in 0..256 {
+                    blended_style[0][j] += style_slice[j] * portion;
                 }
             }
-            eprintln!("styles: {:?}, portions: {:?}", style_names, style_portions);
-
-            let mut blended_style = vec![vec![0.0; 256]; 1];
+            tracing::trace!("blended_style: {:?}", blended_style);
+            Ok(blended_style)
+        }
+    }
 
-            for (name, portion) in style_names.iter().zip(style_portions.iter()) {
-                if let Some(style) = self.styles.get(*name) {
-                    let style_slice = &style[tokens_len][0]; // This is a [256] array
-                    // Blend into the blended_style
-                    for j in 0..256 {
-                        blended_style[0][j] += style_slice[j] * portion;
-                    }
+    /// Blend every row of the voices in `style_name`, for `blend_cache`
+    fn blend_table(
+        &self,
+        style_name: &str,
+    ) -> Result<Vec<[[f32; 256]; 1]>, Box<dyn std::error::Error>> {
+        tracing::debug!("blending style table for {}", style_name);
+        let parts = parse_style(style_name)?;
+        let mut table = vec![[[0.0; 256]; 1]; voices::VOICE_ROWS];
+        for (name, portion) in parts {
+            let style = self.voice(name)?;
+            for (row, style_row) in table.iter_mut().zip(style) {
+                for (value, v) in row[0].iter_mut().zip(&style_row[0]) {
+                    *value += v * portion;
                 }
             }
-            eprintln!("blended_style: {:?}", blended_style);
-            Ok(blended_style)
         }
+        Ok(table)
+    }
+
+    /// Hit counters of the blend cache, see `InitConfig::blend_cache_size`
+    pub fn blend_cache_stats(&self) -> BlendCacheStats {
+        self.blend_cache.stats()
     }
 
-    fn load_voices(voices_path: &str) -> HashMap<String, Vec<[[f32; 256]; 1]>> {
-        let mut npz = NpzReader::new(File::open(voices_path).unwrap()).unwrap();
+    /// Voices of `voices_path` merged with the voice packs of `cfg`
+    fn load_voices(
+        voices_path: &str,
+        cfg: &InitConfig,
+    ) -> Result<HashMap<String, Vec<[[f32; 256]; 1]>>, String> {
         let mut map = HashMap::new();
+        let paths: Vec<&str> = std::iter::once(voices_path)
+            .chain(cfg.voice_packs.iter().map(String::as_str))
+            .collect();
 
-        for voice in npz.names().unwrap() {
-            let voice_data: Result<Array3<f32>, _> = npz.by_name(&voice);
-            let voice_data = voice_data.unwrap();
-            let mut tensor = vec![[[0.0; 256]; 1]; 511];
+        for (voice, voice_data) in voices::read_voice_packs(&paths, cfg.voice_packs_override)? {
+            let mut tensor = vec![[[0.0; 256]; 1]; voices::VOICE_ROWS];
             for (i, inner_value) in voice_data.outer_iter().enumerate() {
                 for (j, inner_inner_value) in inner_value.outer_iter().enumerate() {
                     for (k, number) in inner_inner_value.iter().enumerate() {
@@ -1039,7 +2639,7 @@ impl TTSKoko {
             voices
         };
 
-        map
+        Ok(map)
     }
 
     // Returns a sorted list of available voice names
@@ -1048,6 +2648,20 @@ impl TTSKoko {
         voices.sort();
         voices
     }
+
+    /// Sorted voice names in a voices file, in the order voice indices refer
+    /// to, without loading the model or the voice data
+    pub fn list_voices(voices_path: &str) -> Result<Vec<String>, String> {
+        let file = File::open(voices_path)
+            .map_err(|e| format!("failed to open voices file {}: {}", voices_path, e))?;
+        let mut npz = NpzReader::new(file)
+            .map_err(|e| format!("failed to read voices file {}: {}", voices_path, e))?;
+        let mut voices = npz
+            .names()
+            .map_err(|e| format!("failed to read voices file {}: {}", voices_path, e))?;
+        voices.sort();
+        Ok(voices)
+    }
 }
 
 impl TTSKokoParallel {
@@ -1055,7 +2669,7 @@ impl TTSKokoParallel {
         model_path: &str,
         voices_path: &str,
         num_instances: usize,
-    ) -> Self {
+    ) -> Result<Self, Box<dyn Error>> {
         Self::from_config_with_instances(
             model_path,
             voices_path,
@@ -1070,18 +2684,23 @@ impl TTSKokoParallel {
         voices_path: &str,
         cfg: InitConfig,
         num_instances: usize,
-    ) -> Self {
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some(espeak_data_path) = &cfg.espeak_data_path {
+            set_espeak_data_path(espeak_data_path)?;
+        }
+
         if !Path::new(model_path).exists() {
             utils::fileio::download_file_from_url(cfg.model_url.as_str(), model_path)
                 .await
-                .expect("download model failed.");
+                .map_err(|e| format!("download model failed: {}", e))?;
         }
 
         if !Path::new(voices_path).exists() {
             utils::fileio::download_file_from_url(cfg.voices_url.as_str(), voices_path)
                 .await
-                .expect("download voices data file failed.");
+                .map_err(|e| format!("download voices data file failed: {}", e))?;
         }
+        verify_model_checksum(model_path, &cfg)?;
 
         // Create multiple ONNX model instances
         let mut models = Vec::new();
@@ -1092,21 +2711,26 @@ impl TTSKokoParallel {
                 i + 1,
                 num_instances
             );
-            let model = Arc::new(Mutex::new(
-                ort_koko::OrtKoko::new(model_path.to_string())
-                    .expect("Failed to create Kokoro TTS model"),
-            ));
+            let model = ort_koko::OrtKoko::new_with_options(
+                model_path.to_string(),
+                cfg.model_precision,
+                cfg.provider_fallback,
+                cfg.session_memory,
+            )
+            .map_err(|e| format!("failed to create Kokoro TTS model: {}", e))?;
+            let model = Arc::new(Mutex::new(model));
             models.push(model);
         }
 
-        let styles = TTSKoko::load_voices(voices_path);
+        let styles = TTSKoko::load_voices(voices_path, &cfg)?;
 
-        TTSKokoParallel {
+        Ok(TTSKokoParallel {
             model_path: model_path.to_string(),
             models,
             styles,
+            blend_cache: Arc::new(BlendCache::new(cfg.blend_cache_size)),
             init_config: cfg,
-        }
+        })
     }
 
     /// Get a specific model instance for a worker
@@ -1122,6 +2746,7 @@ impl TTSKokoParallel {
             model: model_instance,
             // TODO: This clones the HashMap. In a future PR, wrap styles in Arc<>!
             styles: self.styles.clone(),
+            blend_cache: Arc::clone(&self.blend_cache),
             init_config: self.init_config.clone(),
         }
     }
@@ -1137,6 +2762,7 @@ impl TTSKokoParallel {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
+        granularity: TimestampGranularity,
         model_instance: Arc<Mutex<ort_koko::OrtKoko>>,
     ) -> Result<Option<(Vec<f32>, Vec<WordAlignment>)>, Box<dyn Error>> {
         let wrapper = self.get_tts_wrapper(model_instance);
@@ -1149,6 +2775,7 @@ impl TTSKokoParallel {
             request_id,
             instance_id,
             chunk_number,
+            granularity,
         )
     }
 
@@ -1186,6 +2813,7 @@ impl TTSKokoParallel {
             model_path: self.model_path.clone(),
             model: Arc::clone(&self.models[0]), // Just for interface compatibility
             styles: self.styles.clone(),
+            blend_cache: Arc::clone(&self.blend_cache),
             init_config: self.init_config.clone(),
         };
         temp_tts.split_text_into_speech_chunks(text, max_words)
@@ -0,0 +1,557 @@
+use std::path::{Path, PathBuf};
+use whatlang::Lang;
+
+/// Language used when detection fails or is not confident enough
+pub const FALLBACK_LANGUAGE: &str = "en-us";
+
+/// Detections below this confidence are treated as unknown
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+/// Sentences with fewer words than this inherit the previous sentence's
+/// language, since detection on a couple of words is mostly noise
+pub const MIN_SEGMENT_WORDS: usize = 4;
+
+/// A language Kokoro has voices for, with its eSpeak identifier
+struct LanguageEntry {
+    lang: Lang,
+    espeak_code: &'static str,
+    // First letter of the Kokoro voice names for this language (e.g. `af_sky` -> 'a')
+    voice_prefixes: &'static [char],
+    default_voice: &'static str,
+    // Pangram-like sample covering most sounds of the language
+    probe_text: &'static str,
+}
+
+const LANGUAGES: &[LanguageEntry] = &[
+    LanguageEntry {
+        lang: Lang::Eng,
+        espeak_code: "en-us",
+        voice_prefixes: &['a', 'b'],
+        default_voice: "af_heart",
+        probe_text: "The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs! Sphinx of black quartz, judge my vow. Is this the thing you measured, or a joyful vision?",
+    },
+    LanguageEntry {
+        lang: Lang::Spa,
+        espeak_code: "es",
+        voice_prefixes: &['e'],
+        default_voice: "ef_dora",
+        probe_text: "El veloz murciélago hindú comía feliz cardillo y kiwi. ¿La cigüeña tocaba el saxofón detrás del palenque de paja? ¡Qué rico!",
+    },
+    LanguageEntry {
+        lang: Lang::Fra,
+        espeak_code: "fr-fr",
+        voice_prefixes: &['f'],
+        default_voice: "ff_siwis",
+        probe_text: "Portez ce vieux whisky au juge blond qui fume. Voix ambiguë d'un cœur qui, au zéphyr, préfère les jattes de kiwis. Un bon vin blanc.",
+    },
+    LanguageEntry {
+        lang: Lang::Ita,
+        espeak_code: "it",
+        voice_prefixes: &['i'],
+        default_voice: "if_sara",
+        probe_text: "Quel vituperabile xenofobo zelante assaggia il whisky ed esclama: alleluja! Ma che gnocchi gli ho fatto?",
+    },
+    LanguageEntry {
+        lang: Lang::Por,
+        espeak_code: "pt-br",
+        voice_prefixes: &['p'],
+        default_voice: "pf_dora",
+        probe_text: "Um pequeno jabuti xereta viu dez cegonhas felizes. À noite, o vovô vê o ímã cair no pé do pinguim queixoso, e a vovó põe açúcar no chá.",
+    },
+    LanguageEntry {
+        lang: Lang::Hin,
+        espeak_code: "hi",
+        voice_prefixes: &['h'],
+        default_voice: "hf_alpha",
+        probe_text: "ऋषियों को सताने वाले दुष्ट राक्षसों के राजा रावण का सर्वनाश करने वाले विष्णु भगवान श्रीराम अयोध्या के बड़े पुत्र थे।",
+    },
+    LanguageEntry {
+        lang: Lang::Jpn,
+        espeak_code: "ja",
+        voice_prefixes: &['j'],
+        default_voice: "jf_alpha",
+        probe_text: "いろはにほへと ちりぬるを わかよたれそ つねならむ。今日は東京で友達と美味しいご飯を食べました。",
+    },
+    LanguageEntry {
+        lang: Lang::Cmn,
+        espeak_code: "cmn",
+        voice_prefixes: &['z'],
+        default_voice: "zf_xiaoxiao",
+        probe_text: "天地玄黄，宇宙洪荒。日月盈昃，辰宿列张。我们今天去北京吃饭，你觉得怎么样？",
+    },
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedLanguage {
+    /// eSpeak language identifier, e.g. `es`
+    pub espeak_code: &'static str,
+    /// Kokoro voice used when the requested style doesn't fit the language
+    pub default_voice: &'static str,
+    /// Detector confidence in the range 0.0 to 1.0
+    pub confidence: f64,
+}
+
+/// Guess the language of `text`.
+///
+/// Returns `None` when the text is too ambiguous to classify or the detected
+/// language has no Kokoro voices. Callers should check `confidence` against
+/// `MIN_CONFIDENCE` before trusting the result.
+pub fn detect_language(text: &str) -> Option<DetectedLanguage> {
+    let info = whatlang::detect(text)?;
+    let entry = LANGUAGES.iter().find(|e| e.lang == info.lang())?;
+    Some(DetectedLanguage {
+        espeak_code: entry.espeak_code,
+        default_voice: entry.default_voice,
+        confidence: info.confidence(),
+    })
+}
+
+fn entry_for_espeak_code(lan: &str) -> Option<&'static LanguageEntry> {
+    let lan = lan.to_lowercase();
+    LANGUAGES.iter().find(|e| {
+        lan == e.espeak_code
+            || lan.split('-').next() == e.espeak_code.split('-').next()
+            || (e.espeak_code == "cmn" && lan.starts_with("zh"))
+    })
+}
+
+/// Sample text to phonemize in `lan` for checking which phonemes eSpeak
+/// produces for it, falling back to English for languages Kokoro has no
+/// voices for
+pub fn probe_text(lan: &str) -> &'static str {
+    entry_for_espeak_code(lan)
+        .unwrap_or(&LANGUAGES[0])
+        .probe_text
+}
+
+/// Check whether every voice in a style (single voice or `a.4+b:0.6` blend)
+/// belongs to the given eSpeak language.
+pub fn voice_matches_language(style: &str, lan: &str) -> bool {
+    let Some(entry) = entry_for_espeak_code(lan) else {
+        // Unknown languages can't be checked, so don't second-guess the user
+        return true;
+    };
+    style.split('+').all(|part| {
+        let name = part.split_once(['.', ':']).map(|(n, _)| n).unwrap_or(part);
+        name.chars()
+            .next()
+            .map(|c| entry.voice_prefixes.contains(&c.to_ascii_lowercase()))
+            .unwrap_or(false)
+    })
+}
+
+/// Pick the style to use for `lan`: keep `style` when its voices belong to the
+/// language, otherwise fall back to the language's default voice.
+pub fn style_for_language(style: &str, lan: &str) -> String {
+    if voice_matches_language(style, lan) {
+        return style.to_string();
+    }
+    match entry_for_espeak_code(lan) {
+        Some(entry) => entry.default_voice.to_string(),
+        None => style.to_string(),
+    }
+}
+
+/// eSpeak codes of the languages Kokoro has voices for
+pub fn kokoro_languages() -> Vec<&'static str> {
+    LANGUAGES.iter().map(|e| e.espeak_code).collect()
+}
+
+/// Kokoro's language codes, in the order multilingual exports with a language
+/// id input number them (the first letter of the voice names, `b` being
+/// British English)
+const KOKORO_LANGUAGE_CODES: &[char] = &['a', 'b', 'e', 'f', 'h', 'i', 'j', 'p', 'z'];
+
+/// Language id of the eSpeak language `lan` for models that take one, `None`
+/// for languages Kokoro has no voices for
+pub fn kokoro_language_id(lan: &str) -> Option<i64> {
+    let lan = lan.to_lowercase();
+    let code = if lan.starts_with("en-gb") || lan == "en-uk" {
+        'b'
+    } else {
+        *entry_for_espeak_code(&lan)?.voice_prefixes.first()?
+    };
+    KOKORO_LANGUAGE_CODES
+        .iter()
+        .position(|&c| c == code)
+        .map(|i| i as i64)
+}
+
+/// Common install locations of `espeak-ng-data`, checked after `ESPEAK_DATA_PATH`
+const ESPEAK_DATA_DIRS: &[&str] = &[
+    "/usr/share/espeak-ng-data",
+    "/usr/local/share/espeak-ng-data",
+    "/usr/lib/x86_64-linux-gnu/espeak-ng-data",
+    "/usr/lib/aarch64-linux-gnu/espeak-ng-data",
+    "/opt/homebrew/share/espeak-ng-data",
+];
+
+/// Locate the `espeak-ng-data` directory eSpeak NG will most likely use
+pub fn espeak_data_dir() -> Option<PathBuf> {
+    let from_env =
+        std::env::var_os("ESPEAK_DATA_PATH").map(|root| Path::new(&root).join("espeak-ng-data"));
+    from_env
+        .into_iter()
+        .chain(ESPEAK_DATA_DIRS.iter().map(PathBuf::from))
+        .find(|dir| dir.join("lang").is_dir())
+}
+
+/// Language codes declared by the voice files under `data_dir/lang`, sorted
+/// and without duplicates. Each file declares its codes on `language <code>` lines.
+pub fn espeak_languages(data_dir: &Path) -> Vec<String> {
+    fn visit(dir: &Path, codes: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                visit(&path, codes);
+            } else if let Ok(contents) = std::fs::read_to_string(&path) {
+                codes.extend(contents.lines().filter_map(|line| {
+                    let mut words = line.split_whitespace();
+                    match (words.next(), words.next()) {
+                        (Some("language"), Some(code)) => Some(code.to_lowercase()),
+                        _ => None,
+                    }
+                }));
+            }
+        }
+    }
+
+    let mut codes = Vec::new();
+    visit(&data_dir.join("lang"), &mut codes);
+    codes.sort();
+    codes.dedup();
+    codes
+}
+
+/// Whether `lan` is written without spaces between words and with CJK
+/// punctuation (Chinese and Japanese), so sentences end at `。！？` and long
+/// ones break at `、，` rather than at spaces
+pub(crate) fn is_cjk_language(lan: &str) -> bool {
+    let lan = lan.to_lowercase();
+    let primary = lan.split(['-', '_']).next().unwrap_or_default();
+    matches!(primary, "ja" | "jpx" | "zh" | "cmn" | "yue" | "hak")
+}
+
+/// Sentence-ending punctuation of CJK text, see `is_cjk_language`
+const CJK_TERMINATORS: [char; 4] = ['。', '！', '？', '；'];
+
+/// Clause-separating punctuation of CJK text
+const CJK_CLAUSE_MARKS: [char; 3] = ['、', '，', ','];
+
+/// Split `text` into sentences, keeping their terminating punctuation. CJK
+/// full stops also end sentences when `lan` is a CJK language
+pub(crate) fn split_sentences(text: &str, lan: &str) -> Vec<String> {
+    let cjk = is_cjk_language(lan);
+    let mut sentences: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    let flush = |current: &mut String, sentences: &mut Vec<String>| {
+        let trimmed = current.trim();
+        if trimmed.chars().any(|c| c.is_alphanumeric()) {
+            sentences.push(trimmed.to_string());
+        } else if let Some(last) = sentences.last_mut() {
+            // Stray punctuation such as the tail of "..." belongs to the previous sentence
+            last.push_str(trimmed);
+        }
+        current.clear();
+    };
+
+    for c in text.chars() {
+        current.push(c);
+        if matches!(c, '.' | '!' | '?' | ';') || (cjk && CJK_TERMINATORS.contains(&c)) {
+            flush(&mut current, &mut sentences);
+        }
+    }
+    flush(&mut current, &mut sentences);
+
+    sentences
+}
+
+/// Split a CJK `sentence` after each clause mark, keeping the marks, for
+/// sentences too long for one chunk
+pub(crate) fn split_cjk_clauses(sentence: &str) -> Vec<String> {
+    let mut clauses = Vec::new();
+    let mut current = String::new();
+    for c in sentence.chars() {
+        current.push(c);
+        if CJK_CLAUSE_MARKS.contains(&c) {
+            clauses.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.trim().is_empty() {
+        clauses.push(current);
+    }
+    clauses
+}
+
+/// `sentence` ending in a period unless it already ends in terminal
+/// punctuation, possibly followed by closing quotes or brackets
+pub(crate) fn terminate_sentence(sentence: &str) -> String {
+    let sentence = sentence.trim_end();
+    let last = sentence
+        .trim_end_matches(|c: char| matches!(c, '"' | '\'' | '”' | '’' | '»' | ')' | ']'))
+        .chars()
+        .last();
+    match last {
+        None => sentence.to_string(),
+        Some('.' | '!' | '?' | ';' | ':' | '…' | '。' | '！' | '？') => sentence.to_string(),
+        Some(_) => format!("{}.", sentence),
+    }
+}
+
+/// Split `text` into runs of sentences sharing the same language.
+///
+/// Each sentence is classified on its own; sentences shorter than
+/// `MIN_SEGMENT_WORDS` or without a confident detection inherit the language of
+/// the sentence before them (or `fallback_lan` at the start). Detections of the
+/// same language as `fallback_lan` keep `fallback_lan` so regional variants such
+/// as `en-gb` chosen by the user are preserved.
+///
+/// Returns `(espeak_language, text)` pairs in input order.
+pub fn segment_by_language(text: &str, fallback_lan: &str) -> Vec<(String, String)> {
+    let fallback_entry = entry_for_espeak_code(fallback_lan);
+    let mut segments: Vec<(String, String)> = Vec::new();
+
+    for sentence in split_sentences(text, fallback_lan) {
+        let detected = if sentence.split_whitespace().count() >= MIN_SEGMENT_WORDS {
+            detect_language(&sentence).filter(|d| d.confidence >= MIN_CONFIDENCE)
+        } else {
+            None
+        };
+
+        let lan = match (detected, segments.last()) {
+            (Some(d), _) if fallback_entry.map(|e| e.espeak_code) == Some(d.espeak_code) => {
+                fallback_lan.to_string()
+            }
+            (Some(d), _) => d.espeak_code.to_string(),
+            (None, Some((prev_lan, _))) => prev_lan.clone(),
+            (None, None) => fallback_lan.to_string(),
+        };
+
+        match segments.last_mut() {
+            Some((prev_lan, prev_text)) if *prev_lan == lan => {
+                prev_text.push(' ');
+                prev_text.push_str(&sentence);
+            }
+            _ => segments.push((lan, sentence)),
+        }
+    }
+
+    segments
+}
+
+/// The language code of the contents of a `<...>` pair, `es` or `/es`, with
+/// whether it is a closing tag. Codes are a 2 or 3 letter primary code with
+/// optional `-` subtags, like `en-gb`.
+fn language_tag(tag: &str) -> Option<(bool, &str)> {
+    let (closing, code) = match tag.strip_prefix('/') {
+        Some(code) => (true, code),
+        None => (false, tag),
+    };
+    let mut parts = code.split('-');
+    let primary = parts.next()?;
+    let valid = (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some((closing, code))
+}
+
+/// Split `text` at language tags like `<es>Hola</es>`, returning
+/// `(Some(language), text)` for tagged spans and `(None, text)` for the text
+/// around them, in input order. Only codes `is_language` accepts make tags;
+/// other markup such as `<br>` or `<em>` and a `<` that doesn't start a tag
+/// are kept as text. Every tag must be closed by its own closing tag and tags
+/// can't be nested.
+pub fn split_language_tags(
+    text: &str,
+    mut is_language: impl FnMut(&str) -> bool,
+) -> Result<Vec<(Option<String>, String)>, String> {
+    let mut spans = Vec::new();
+    let mut push = |lan: Option<String>, span: &mut String| {
+        if !span.trim().is_empty() {
+            spans.push((lan, span.trim().to_string()));
+        }
+        span.clear();
+    };
+
+    let mut open: Option<String> = None;
+    let mut current = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let tag = rest[start + 1..]
+            .find('>')
+            .and_then(|len| {
+                language_tag(&rest[start + 1..start + 1 + len])
+                    .map(|(closing, code)| (closing, code.to_lowercase(), start + len + 2))
+            })
+            .filter(|(_, code, _)| is_language(code));
+        let Some((closing, code, end)) = tag else {
+            current.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+
+        current.push_str(&rest[..start]);
+        match (open.take(), closing) {
+            (None, false) => {
+                push(None, &mut current);
+                open = Some(code);
+            }
+            (Some(lan), true) if lan == code => push(Some(lan), &mut current),
+            (Some(lan), false) => {
+                return Err(format!(
+                    "language tag <{}> inside <{}>, tags can't be nested",
+                    code, lan
+                ));
+            }
+            (Some(lan), true) => return Err(format!("</{}> closes <{}>", code, lan)),
+            (None, true) => return Err(format!("</{}> has no opening <{}>", code, code)),
+        }
+        rest = &rest[end..];
+    }
+    current.push_str(rest);
+    if let Some(lan) = open {
+        return Err(format!("language tag <{}> is never closed", lan));
+    }
+    push(None, &mut current);
+
+    Ok(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_language_tags() {
+        let split =
+            |text: &str| split_language_tags(text, |code| ["es", "fr", "en-gb"].contains(&code));
+        let spans = split("<es>Hola</es> and hello, <EN-GB>mate</en-gb>").unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                (Some("es".to_string()), "Hola".to_string()),
+                (None, "and hello,".to_string()),
+                (Some("en-gb".to_string()), "mate".to_string()),
+            ]
+        );
+        // Comparisons and other markup are not tags
+        assert_eq!(
+            split("1 < 2 and <3> b>").unwrap(),
+            vec![(None, "1 < 2 and <3> b>".to_string())]
+        );
+        // So are tags that aren't languages, closed or not
+        assert_eq!(
+            split("Line one<br>line two").unwrap(),
+            vec![(None, "Line one<br>line two".to_string())]
+        );
+        assert_eq!(
+            split("This is <em>really</em> it").unwrap(),
+            vec![(None, "This is <em>really</em> it".to_string())]
+        );
+
+        assert!(split("<es>Hola").is_err());
+        assert!(split("<es>Hola</fr>").is_err());
+        assert!(split("<es><fr>Salut</fr></es>").is_err());
+        assert!(split("Hola</es>").is_err());
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let detected =
+            detect_language("El rápido zorro marrón salta sobre el perro perezoso en el jardín.")
+                .unwrap();
+        assert_eq!(detected.espeak_code, "es");
+        assert_eq!(detected.default_voice, "ef_dora");
+
+        assert!(detect_language("").is_none());
+    }
+
+    #[test]
+    fn test_split_cjk_sentences() {
+        let text = "今日は晴れです。散歩に行きましょう！どこへ？";
+        assert_eq!(
+            split_sentences(text, "ja"),
+            vec!["今日は晴れです。", "散歩に行きましょう！", "どこへ？"]
+        );
+        assert_eq!(split_sentences("你好。再见。", "cmn-latn-pinyin").len(), 2);
+        // Latin languages keep their rules
+        assert_eq!(split_sentences(text, "en-us"), vec![text]);
+        assert_eq!(
+            split_sentences("One. Two? Three", "ja"),
+            vec!["One.", "Two?", "Three"]
+        );
+
+        assert_eq!(
+            split_cjk_clauses("雨が降って、風が吹いて，寒い"),
+            vec!["雨が降って、", "風が吹いて，", "寒い"]
+        );
+    }
+
+    #[test]
+    fn test_terminate_sentence() {
+        assert_eq!(terminate_sentence("Save changes"), "Save changes.");
+        assert_eq!(terminate_sentence("Are you sure?"), "Are you sure?");
+        assert_eq!(terminate_sentence("Stop!  "), "Stop!");
+        assert_eq!(terminate_sentence("He said \"wait.\""), "He said \"wait.\"");
+        assert_eq!(terminate_sentence(""), "");
+    }
+
+    #[test]
+    fn test_kokoro_language_id() {
+        assert_eq!(kokoro_language_id("en-us"), Some(0));
+        assert_eq!(kokoro_language_id("en-gb-x-rp"), Some(1));
+        assert_eq!(kokoro_language_id("fr-fr"), Some(3));
+        assert_eq!(kokoro_language_id("zh"), Some(8));
+        assert_eq!(kokoro_language_id("de"), None);
+    }
+
+    #[test]
+    fn test_voice_matches_language() {
+        assert!(voice_matches_language("af_sky", "en-us"));
+        assert!(voice_matches_language("bf_emma", "en-gb-x-rp"));
+        assert!(voice_matches_language("af_sarah.4+af_nicole.6", "en-us"));
+        assert!(!voice_matches_language("af_sarah.4+ef_dora.6", "es"));
+        assert!(voice_matches_language("zf_xiaoxiao", "zh"));
+
+        assert_eq!(style_for_language("af_sky", "es"), "ef_dora");
+        assert_eq!(style_for_language("ef_dora", "es"), "ef_dora");
+    }
+
+    #[test]
+    fn test_espeak_languages() {
+        let data_dir = std::env::temp_dir().join(format!("kokoros-lang-{}", std::process::id()));
+        let family = data_dir.join("lang").join("gmw");
+        std::fs::create_dir_all(&family).unwrap();
+        std::fs::write(
+            family.join("en-US"),
+            "name English (America)\nlanguage en-us 2\nlanguage en 3\n",
+        )
+        .unwrap();
+        std::fs::write(
+            data_dir.join("lang").join("eo"),
+            "name Esperanto\nlanguage eo\n",
+        )
+        .unwrap();
+
+        let codes = espeak_languages(&data_dir);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+        assert_eq!(codes, vec!["en", "en-us", "eo"]);
+    }
+
+    #[test]
+    fn test_segment_by_language() {
+        let text = "The weather is lovely today and we are going outside. \
+                    Hoy vamos a caminar por el parque con nuestros amigos. Sí. \
+                    Then we will come back home for dinner with the family.";
+        let segments = segment_by_language(text, "en-us");
+
+        let langs: Vec<&str> = segments.iter().map(|(l, _)| l.as_str()).collect();
+        assert_eq!(langs, vec!["en-us", "es", "en-us"]);
+        // The one-word sentence inherits Spanish instead of becoming its own segment
+        assert!(segments[1].1.ends_with("Sí."));
+    }
+}
@@ -0,0 +1,187 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+/// How a lexicon entry is pronounced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pronunciation {
+    /// IPA passed to the model as is, written `/.../` in the lexicon file
+    Phonemes(String),
+    /// Text phonemized by eSpeak in place of the matched word
+    Respelling(String),
+}
+
+/// A piece of input text after lexicon lookup
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexiconSegment {
+    Text(String),
+    Phonemes(String),
+}
+
+/// Fixed pronunciations for words and phrases, e.g. product or drug names.
+///
+/// Matching is case-insensitive and whole-word at edges that are word
+/// characters, so `C++` or `.NET` match too; longer entries win over shorter
+/// ones they contain.
+#[derive(Debug, Clone)]
+pub struct Lexicon {
+    entries: HashMap<String, Pronunciation>,
+    pattern: Regex,
+}
+
+impl Lexicon {
+    /// Build a lexicon from `(word or phrase, pronunciation)` pairs
+    pub fn new(entries: impl IntoIterator<Item = (String, Pronunciation)>) -> Result<Self, String> {
+        let entries: HashMap<String, Pronunciation> = entries
+            .into_iter()
+            .map(|(word, pronunciation)| (word.trim().to_lowercase(), pronunciation))
+            .filter(|(word, _)| !word.is_empty())
+            .collect();
+        if entries.is_empty() {
+            return Err("lexicon has no entries".to_string());
+        }
+
+        let mut words: Vec<&String> = entries.keys().collect();
+        // Longest first so phrases take precedence over the words in them
+        words.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then(a.cmp(b)));
+        let alternatives: Vec<String> = words.iter().map(|w| word_pattern(w)).collect();
+        let pattern = Regex::new(&format!(r"(?i)(?:{})", alternatives.join("|")))
+            .map_err(|e| format!("failed to build lexicon matcher: {}", e))?;
+
+        Ok(Self { entries, pattern })
+    }
+
+    /// Parse a TSV lexicon with one `word or phrase<TAB>pronunciation` entry per
+    /// line. Pronunciations written as `/.../` are IPA, anything else is a
+    /// respelling. Empty lines and lines starting with `#` are ignored.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (word, pronunciation) = line.split_once('\t').ok_or_else(|| {
+                format!("lexicon line {}: expected word<TAB>pronunciation", i + 1)
+            })?;
+            let pronunciation = pronunciation.trim();
+            let pronunciation = match pronunciation
+                .strip_prefix('/')
+                .and_then(|p| p.strip_suffix('/'))
+            {
+                Some(ipa) => Pronunciation::Phonemes(ipa.to_string()),
+                None => Pronunciation::Respelling(pronunciation.to_string()),
+            };
+            entries.push((word.to_string(), pronunciation));
+        }
+        Self::new(entries)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read lexicon {}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Split `text` into plain text, with respellings substituted, and the
+    /// phonemes of IPA entries
+    pub fn segment(&self, text: &str) -> Vec<LexiconSegment> {
+        let mut segments = Vec::new();
+        let mut pending = String::new();
+        let mut last = 0;
+
+        for m in self.pattern.find_iter(text) {
+            pending.push_str(&text[last..m.start()]);
+            last = m.end();
+            match self.entries.get(&m.as_str().to_lowercase()) {
+                // Case-insensitive matches whose lowercase form differs, e.g. the Kelvin sign
+                None => pending.push_str(m.as_str()),
+                Some(Pronunciation::Respelling(respelling)) => pending.push_str(respelling),
+                Some(Pronunciation::Phonemes(phonemes)) => {
+                    if !pending.is_empty() {
+                        segments.push(LexiconSegment::Text(std::mem::take(&mut pending)));
+                    }
+                    segments.push(LexiconSegment::Phonemes(phonemes.clone()));
+                }
+            }
+        }
+        pending.push_str(&text[last..]);
+        if !pending.is_empty() {
+            segments.push(LexiconSegment::Text(pending));
+        }
+
+        segments
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `word` escaped, with a word boundary at each edge that is a word character.
+/// There is no boundary after the `+` of `C++`, so those edges go without
+fn word_pattern(word: &str) -> String {
+    let boundary = |c: Option<char>| {
+        if c.is_some_and(is_word_char) {
+            r"\b"
+        } else {
+            ""
+        }
+    };
+    format!(
+        "{}{}{}",
+        boundary(word.chars().next()),
+        regex::escape(word),
+        boundary(word.chars().next_back())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexicon_segment() {
+        let lexicon = Lexicon::parse(
+            "# glossary\n\
+             Tylenol\t/tˈaɪlənɔːl/\n\
+             SQL\tsequel\n\
+             New York\t/nuː jˈɔːɹk/\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            lexicon.segment("Take tylenol in new york, then SQL."),
+            vec![
+                LexiconSegment::Text("Take ".into()),
+                LexiconSegment::Phonemes("tˈaɪlənɔːl".into()),
+                LexiconSegment::Text(" in ".into()),
+                LexiconSegment::Phonemes("nuː jˈɔːɹk".into()),
+                LexiconSegment::Text(", then sequel.".into()),
+            ]
+        );
+
+        // Whole words only
+        assert_eq!(
+            lexicon.segment("SQLite"),
+            vec![LexiconSegment::Text("SQLite".into())]
+        );
+
+        assert!(Lexicon::parse("no tab here").is_err());
+    }
+
+    #[test]
+    fn test_lexicon_non_word_edges() {
+        let lexicon = Lexicon::parse("C++\tsee plus plus\n.NET\tdot net\n").unwrap();
+        assert_eq!(
+            lexicon.segment("I write C++ and .NET code."),
+            vec![LexiconSegment::Text(
+                "I write see plus plus and dot net code.".into()
+            )]
+        );
+        // The word edge still needs a boundary
+        assert_eq!(
+            lexicon.segment("ABC++"),
+            vec![LexiconSegment::Text("ABC++".into())]
+        );
+    }
+}
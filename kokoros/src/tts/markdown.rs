@@ -0,0 +1,114 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref FENCE: Regex = Regex::new(r"^\s*(```|~~~)").unwrap();
+    static ref HEADING: Regex = Regex::new(r"^\s{0,3}#{1,6}\s+(.*?)\s*#*\s*$").unwrap();
+    static ref BLOCKQUOTE: Regex = Regex::new(r"^\s*(>\s?)+").unwrap();
+    static ref LIST_MARKER: Regex = Regex::new(r"^\s*([-*+]|\d+[.)])\s+(\[[ xX]\]\s+)?").unwrap();
+    static ref RULE: Regex = Regex::new(r"^\s*([-*_]\s*){3,}$").unwrap();
+    static ref TABLE_SEPARATOR: Regex =
+        Regex::new(r"^\s*\|?(\s*:?-+:?\s*\|)+\s*:?-*:?\s*$").unwrap();
+    static ref LINK_DEFINITION: Regex = Regex::new(r"^\s*\[[^\]]+\]:\s+\S+").unwrap();
+    static ref IMAGE: Regex = Regex::new(r"!\[([^\]]*)\]\([^)]*\)").unwrap();
+    static ref LINK: Regex = Regex::new(r"\[([^\]]+)\](\([^)]*\)|\[[^\]]*\])").unwrap();
+    static ref AUTOLINK: Regex = Regex::new(r"<((https?|mailto):[^>]+)>").unwrap();
+    static ref HTML_TAG: Regex = Regex::new(r"</?[A-Za-z][^>]*>").unwrap();
+    static ref INLINE_CODE: Regex = Regex::new(r"`+([^`]+)`+").unwrap();
+    static ref BOLD: Regex = Regex::new(r"(\*\*|__)([^*_]+)(\*\*|__)").unwrap();
+    static ref ITALIC_STAR: Regex = Regex::new(r"\*([^*\s][^*]*)\*").unwrap();
+    static ref ITALIC_UNDERSCORE: Regex = Regex::new(r"\b_([^_]+)_\b").unwrap();
+    static ref STRIKETHROUGH: Regex = Regex::new(r"~~([^~]+)~~").unwrap();
+    static ref MULTI_SPACE: Regex = Regex::new(r"[ \t]{2,}").unwrap();
+}
+
+/// Remove common markdown markup, keeping the text a reader would see.
+///
+/// Works line by line and keeps the number of lines unchanged, so line-based
+/// consumers (e.g. file mode output numbering) still line up with the source.
+/// Fenced code blocks are blanked unless `keep_code_blocks` is set, in which
+/// case their contents are kept as plain text.
+pub fn strip_markdown(text: &str, keep_code_blocks: bool) -> String {
+    let mut in_code_block = false;
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        if FENCE.is_match(line) {
+            in_code_block = !in_code_block;
+            lines.push(String::new());
+            continue;
+        }
+        if in_code_block {
+            lines.push(if keep_code_blocks {
+                line.trim().to_string()
+            } else {
+                String::new()
+            });
+            continue;
+        }
+        lines.push(strip_line(line));
+    }
+
+    lines.join("\n")
+}
+
+fn strip_line(line: &str) -> String {
+    if RULE.is_match(line) || TABLE_SEPARATOR.is_match(line) || LINK_DEFINITION.is_match(line) {
+        return String::new();
+    }
+
+    let line = HEADING.replace(line, "$1");
+    let line = BLOCKQUOTE.replace(&line, "");
+    let line = LIST_MARKER.replace(&line, "");
+    let line = IMAGE.replace_all(&line, "$1");
+    let line = LINK.replace_all(&line, "$1");
+    let line = AUTOLINK.replace_all(&line, "$1");
+    let line = HTML_TAG.replace_all(&line, "");
+    let line = INLINE_CODE.replace_all(&line, "$1");
+    let line = BOLD.replace_all(&line, "$2");
+    let line = ITALIC_STAR.replace_all(&line, "$1");
+    let line = ITALIC_UNDERSCORE.replace_all(&line, "$1");
+    let line = STRIKETHROUGH.replace_all(&line, "$1");
+
+    // Table cells read as a list
+    let line = line.trim().trim_matches('|').replace('|', ",");
+
+    MULTI_SPACE.replace_all(line.trim(), " ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markdown() {
+        let md = "# Getting **started**\n\
+                  \n\
+                  Read the [docs](https://example.com) and `cargo build`.\n\
+                  - *Fast*  and  ~~slow~~ __simple__\n\
+                  > Quoted ![logo](logo.png)\n\
+                  ```rust\n\
+                  fn main() {}\n\
+                  ```\n\
+                  ---\n\
+                  | a | b |\n\
+                  |---|---|";
+        let stripped = strip_markdown(md, false);
+        let lines: Vec<&str> = stripped.split('\n').collect();
+
+        assert_eq!(lines.len(), md.lines().count());
+        assert_eq!(lines[0], "Getting started");
+        assert_eq!(lines[2], "Read the docs and cargo build.");
+        assert_eq!(lines[3], "Fast and slow simple");
+        assert_eq!(lines[4], "Quoted logo");
+        assert_eq!(lines[6], "");
+        assert_eq!(lines[8], "");
+        assert_eq!(lines[9], "a , b");
+        assert_eq!(lines[10], "");
+
+        assert_eq!(
+            strip_markdown(md, true).lines().nth(6),
+            Some("fn main() {}")
+        );
+    }
+}
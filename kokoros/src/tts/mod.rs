@@ -1,5 +1,14 @@
+pub mod blend_cache;
+pub mod cancel;
+pub mod duration;
+pub mod emphasis;
+pub mod error;
 pub mod koko;
+pub mod lang;
+pub mod lexicon;
+pub mod markdown;
 pub mod normalize;
 pub mod phonemizer;
 pub mod tokenize;
 pub mod vocab;
+pub mod voices;
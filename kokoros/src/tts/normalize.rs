@@ -25,6 +25,11 @@ lazy_static! {
     static ref X_POSSESSIVE_RE: Regex = Regex::new(r"(?<=X')S\b").unwrap();
     static ref INITIALS_RE: Regex = Regex::new(r"(?:[A-Za-z]\.){2,} [a-z]").unwrap();
     static ref ACRONYM_RE: Regex = Regex::new(r"(?i)(?<=[A-Z])\.(?=[A-Z])").unwrap();
+    static ref SPACE_BEFORE_PUNCT_RE: Regex = Regex::new(r" +([.,!?;:])").unwrap();
+    static ref DIGITS_RE: Regex = Regex::new(
+        r"\+[0-9]+(?:[ .-][0-9]+)*|\([0-9]+\)[ -]?[0-9]+(?:[-.][0-9]+)*|[0-9]+(?:[-.,][0-9]+)*"
+    )
+    .unwrap();
 }
 
 pub fn normalize_text(text: &str) -> String {
@@ -69,3 +74,338 @@ pub fn normalize_text(text: &str) -> String {
 
     text.trim().to_string()
 }
+
+/// What to do with emoji and pictographic symbols (✓, →, ©, 👍) in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmojiPolicy {
+    /// Replace them with spoken names from a symbol table, dropping unknown ones
+    Speak,
+    /// Remove them
+    Strip,
+    /// Leave them for eSpeak to read or drop as it sees fit
+    #[default]
+    Keep,
+}
+
+impl std::str::FromStr for EmojiPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "speak" => Ok(EmojiPolicy::Speak),
+            "strip" => Ok(EmojiPolicy::Strip),
+            "keep" => Ok(EmojiPolicy::Keep),
+            other => Err(format!(
+                "unknown emoji policy '{}', expected speak, strip or keep",
+                other
+            )),
+        }
+    }
+}
+
+/// Spoken names of common symbols and emoji, used by `EmojiPolicy::Speak`
+pub const DEFAULT_SYMBOL_TABLE: &[(char, &str)] = &[
+    ('✓', "check mark"),
+    ('✔', "check mark"),
+    ('✅', "check mark"),
+    ('✗', "cross"),
+    ('✘', "cross"),
+    ('❌', "cross"),
+    ('→', "to"),
+    ('⇒', "implies"),
+    ('←', "left arrow"),
+    ('↑', "up arrow"),
+    ('↓', "down arrow"),
+    ('©', "copyright"),
+    ('®', "registered"),
+    ('™', "trademark"),
+    ('°', "degrees"),
+    ('±', "plus or minus"),
+    ('×', "times"),
+    ('÷', "divided by"),
+    ('≈', "approximately"),
+    ('≠', "not equal to"),
+    ('≤', "less than or equal to"),
+    ('≥', "greater than or equal to"),
+    ('∞', "infinity"),
+    ('★', "star"),
+    ('⭐', "star"),
+    ('♥', "heart"),
+    ('❤', "heart"),
+    ('⚠', "warning"),
+    ('✨', "sparkles"),
+    ('😀', "grinning face"),
+    ('😂', "face with tears of joy"),
+    ('🤣', "rolling on the floor laughing"),
+    ('😊', "smiling face"),
+    ('🙂', "slightly smiling face"),
+    ('😉', "winking face"),
+    ('😍', "smiling face with heart eyes"),
+    ('😎', "smiling face with sunglasses"),
+    ('😢', "crying face"),
+    ('😭', "loudly crying face"),
+    ('🤔', "thinking face"),
+    ('🥳', "partying face"),
+    ('👍', "thumbs up"),
+    ('👎', "thumbs down"),
+    ('👏', "clapping hands"),
+    ('👋', "waving hand"),
+    ('🙏', "folded hands"),
+    ('🎉', "party popper"),
+    ('🔥', "fire"),
+    ('💯', "hundred points"),
+    ('💡', "light bulb"),
+    ('🚀', "rocket"),
+];
+
+/// Emoji, pictographs, arrows and marks such as ©, which eSpeak reads
+/// inconsistently. Math symbols like × are not included since stripping them
+/// would merge the surrounding numbers.
+fn is_pictographic(c: char) -> bool {
+    matches!(c as u32,
+        0x00A9 | 0x00AE | 0x2122
+        | 0x2190..=0x21FF // arrows
+        | 0x2300..=0x23FF // miscellaneous technical
+        | 0x25A0..=0x25FF // geometric shapes
+        | 0x2600..=0x27BF // miscellaneous symbols, dingbats
+        | 0x2B00..=0x2BFF // miscellaneous symbols and arrows
+        | 0x1F000..=0x1FAFF // emoji, including flags and skin tones
+    )
+}
+
+/// Parts of emoji sequences with no meaning of their own: variation
+/// selectors, zero width joiners and the keycap mark
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(c, '\u{FE0E}' | '\u{FE0F}' | '\u{200D}' | '\u{20E3}')
+}
+
+/// Apply `policy` to the emoji and symbols of `text`, looking up spoken names
+/// in `table` (e.g. `DEFAULT_SYMBOL_TABLE`). Lines are kept intact.
+pub fn normalize_symbols(text: &str, policy: EmojiPolicy, table: &[(char, &str)]) -> String {
+    if policy == EmojiPolicy::Keep {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_emoji_modifier(c) {
+            continue;
+        }
+        let name = match policy {
+            EmojiPolicy::Speak => table.iter().find(|(s, _)| *s == c).map(|(_, n)| *n),
+            _ => None,
+        };
+        match name {
+            Some(name) => {
+                out.push(' ');
+                out.push_str(name);
+                out.push(' ');
+            }
+            None if is_pictographic(c) => out.push(' '),
+            None => out.push(c),
+        }
+    }
+
+    let mut normalized = out
+        .lines()
+        .map(|line| {
+            let line = MULTI_SPACE_RE.replace_all(line.trim(), " ");
+            SPACE_BEFORE_PUNCT_RE.replace_all(&line, "$1").to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if text.ends_with('\n') {
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// How to read runs of digits such as `5551234`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DigitReading {
+    /// As numbers, "five million five hundred fifty-one thousand..."
+    #[default]
+    Grouped,
+    /// Digit by digit, "five five five one two three four"
+    Individual,
+    /// Digit by digit for what looks like a phone number or code, as numbers
+    /// otherwise
+    Auto,
+}
+
+impl std::str::FromStr for DigitReading {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "grouped" => Ok(DigitReading::Grouped),
+            "individual" => Ok(DigitReading::Individual),
+            "auto" => Ok(DigitReading::Auto),
+            other => Err(format!(
+                "unknown digit reading '{}', expected grouped, individual or auto",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether the number `token` (a match of `DIGITS_RE`) reads as a phone
+/// number or code rather than a quantity: it has an international `+` or an
+/// area code in parentheses, 7 or more digits in one run or in groups joined
+/// by hyphens or dots, or a leading zero as in `007`. Thousands separators,
+/// decimals and dates like `2024-01-15` are quantities.
+fn is_code_like(token: &str) -> bool {
+    if token.starts_with('+') || token.starts_with('(') {
+        return true;
+    }
+    let groups: Vec<&str> = token.split(|c: char| !c.is_ascii_digit()).collect();
+    let digits: usize = groups.iter().map(|g| g.len()).sum();
+    let separators: Vec<char> = token.chars().filter(|c| !c.is_ascii_digit()).collect();
+
+    match groups.as_slice() {
+        [single] => single.len() >= 7 || (single.len() >= 2 && single.starts_with('0')),
+        _ if separators.contains(&',') => false,
+        [_, _] if separators == ['.'] => false,
+        [year, month, day] if separators == ['-', '-'] => {
+            !(year.len() == 4 && month.len() <= 2 && day.len() <= 2)
+        }
+        _ => digits >= 7,
+    }
+}
+
+/// Rewrite the digits of `token` one by one with spaces between them, its
+/// groups separated by commas for a short pause: `555-1234` becomes
+/// `5 5 5, 1 2 3 4`
+fn digits_one_by_one(token: &str) -> String {
+    let groups: Vec<String> = token
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|group| !group.is_empty())
+        .map(|group| {
+            group
+                .chars()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    let spoken = groups.join(", ");
+    if token.starts_with('+') {
+        format!("plus {}", spoken)
+    } else {
+        spoken
+    }
+}
+
+/// Apply `reading` to the numbers of `text`. `Individual` reads every number
+/// digit by digit, `Auto` only those `is_code_like` accepts, e.g. "Call
+/// 5551234" or "+1 (555) 123-4567" but not "1,500 people" or "3.5 km".
+pub fn normalize_digits(text: &str, reading: DigitReading) -> String {
+    if reading == DigitReading::Grouped {
+        return text.to_string();
+    }
+    DIGITS_RE
+        .replace_all(text, |caps: &regex::Captures| {
+            let token = &caps[0];
+            // The `+` of a sum like `3+4` is not an international prefix
+            let start = caps.get(0).map_or(0, |m| m.start());
+            let in_sum = text[..start]
+                .chars()
+                .last()
+                .is_some_and(char::is_alphanumeric);
+            if token.starts_with('+') && in_sum {
+                return token.to_string();
+            }
+            if reading == DigitReading::Individual || is_code_like(token) {
+                digits_one_by_one(token)
+            } else {
+                token.to_string()
+            }
+        })
+        .to_string()
+}
+
+/// Rewrite `text` to be read character by character, e.g. for confirmation
+/// codes: letters (uppercased, so they are read as letter names) and digits are
+/// separated by commas for a short gap, whitespace-separated groups by a full
+/// stop for a longer one. Other characters are dropped; lines are kept.
+pub fn spell_out(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let groups: Vec<String> = line
+                .split_whitespace()
+                .map(|group| {
+                    group
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .flat_map(char::to_uppercase)
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .filter(|group| !group.is_empty())
+                .collect();
+            if groups.is_empty() {
+                String::new()
+            } else {
+                format!("{}.", groups.join(". "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_symbols() {
+        let text = "Deploy ✅ done 🚀🚀!\nA → B © 2024 👨‍👩‍👧";
+        assert_eq!(
+            normalize_symbols(text, EmojiPolicy::Speak, DEFAULT_SYMBOL_TABLE),
+            "Deploy check mark done rocket rocket!\nA to B copyright 2024"
+        );
+        assert_eq!(
+            normalize_symbols(text, EmojiPolicy::Strip, DEFAULT_SYMBOL_TABLE),
+            "Deploy done!\nA B 2024"
+        );
+        assert_eq!(
+            normalize_symbols(text, EmojiPolicy::Keep, DEFAULT_SYMBOL_TABLE),
+            text
+        );
+        // Math symbols are only replaced when speaking
+        assert_eq!(
+            normalize_symbols("3×4", EmojiPolicy::Strip, DEFAULT_SYMBOL_TABLE),
+            "3×4"
+        );
+    }
+
+    #[test]
+    fn test_normalize_digits() {
+        let auto = |text| normalize_digits(text, DigitReading::Auto);
+        assert_eq!(auto("Call 5551234 now"), "Call 5 5 5 1 2 3 4 now");
+        assert_eq!(auto("Dial 555-123-4567."), "Dial 5 5 5, 1 2 3, 4 5 6 7.");
+        assert_eq!(auto("+44 20 7946 0958"), "plus 4 4, 2 0, 7 9 4 6, 0 9 5 8");
+        assert_eq!(auto("(555) 123-4567"), "5 5 5, 1 2 3, 4 5 6 7");
+        assert_eq!(auto("Agent 007"), "Agent 0 0 7");
+        assert_eq!(auto("3+4"), "3+4");
+        let quantities = "1,500,000 people, 3.5 km, 250000 steps, on 2024-01-15";
+        assert_eq!(auto(quantities), quantities);
+
+        assert_eq!(
+            normalize_digits("Room 42, 3.5", DigitReading::Individual),
+            "Room 4 2, 3, 5"
+        );
+        assert_eq!(
+            normalize_digits("Call 5551234", DigitReading::Grouped),
+            "Call 5551234"
+        );
+    }
+
+    #[test]
+    fn test_spell_out() {
+        assert_eq!(spell_out("a4b9"), "A, 4, B, 9.");
+        assert_eq!(spell_out("XK-12  q\n\n7"), "X, K, 1, 2. Q.\n\n7.");
+        assert_eq!(spell_out(" - "), "");
+    }
+}
@@ -76,3 +76,170 @@ mod tests2 {
         assert_eq!(tokens_to_phonemes(&empty_tokens), "");
     }
 }
+
+use crate::tts::vocab::VOCAB as KNOWN_PHONEMES;
+use std::collections::HashMap;
+
+/// What to do with phonemes that are not in the model vocab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownPhonemePolicy {
+    /// Drop them (the behavior of `tokenize`)
+    #[default]
+    Skip,
+    /// Replace them with the closest known phoneme(s), dropping those without one
+    Nearest,
+    /// Fail tokenization
+    Error,
+}
+
+impl std::str::FromStr for UnknownPhonemePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(UnknownPhonemePolicy::Skip),
+            "nearest" => Ok(UnknownPhonemePolicy::Nearest),
+            "error" => Ok(UnknownPhonemePolicy::Error),
+            other => Err(format!(
+                "unknown phoneme policy '{}', expected skip, nearest or error",
+                other
+            )),
+        }
+    }
+}
+
+/// Closest vocab spelling of a phoneme eSpeak can emit but the model lacks.
+/// Combining diacritics and tone letters map to nothing, keeping the base sound.
+fn nearest_phoneme(c: char) -> Option<&'static str> {
+    let replacement = match c {
+        '\u{0300}'..='\u{036F}' => "",
+        '˥' | '˦' | '˧' | '˨' | '˩' | '‿' => "",
+        'ã' | 'á' | 'à' | 'â' => "a",
+        'ẽ' | 'é' | 'è' | 'ê' => "e",
+        'ĩ' | 'í' | 'ì' | 'î' => "i",
+        'õ' | 'ó' | 'ò' | 'ô' => "o",
+        'ũ' | 'ú' | 'ù' | 'û' => "u",
+        'ỹ' => "y",
+        'ʦ' => "ts",
+        'ʣ' => "dz",
+        'ʨ' => "tɕ",
+        'ʥ' => "dʑ",
+        'ᵿ' | 'ᴜ' | 'ɷ' => "ʊ",
+        'ɩ' => "ɪ",
+        'ɿ' | 'ʅ' => "ɨ",
+        'ˀ' => "ʔ",
+        'ⁿ' => "n",
+        'ˡ' => "l",
+        'ʳ' => "ɹ",
+        'ᶣ' => "ɥ",
+        'ᵝ' => "β",
+        '-' => " ",
+        _ => return None,
+    };
+    Some(replacement)
+}
+
+/// Tokenize `phonemes` with explicit handling of characters missing from the
+/// vocab. Returns the tokens and the distinct unknown characters, in order of
+/// first appearance, so callers can report them.
+pub fn tokenize_with_policy(
+    phonemes: &str,
+    policy: UnknownPhonemePolicy,
+) -> Result<(Vec<i64>, Vec<char>), String> {
+    tokenize_with_vocab(phonemes, &KNOWN_PHONEMES, policy)
+}
+
+/// `tokenize_with_policy` against a custom phoneme-to-id vocab, e.g. one
+/// loaded with `vocab::load_vocab_file`
+pub fn tokenize_with_vocab(
+    phonemes: &str,
+    vocab: &HashMap<char, usize>,
+    policy: UnknownPhonemePolicy,
+) -> Result<(Vec<i64>, Vec<char>), String> {
+    let mut tokens = Vec::with_capacity(phonemes.len());
+    let mut unknown: Vec<char> = Vec::new();
+
+    for c in phonemes.chars() {
+        if let Some(&idx) = vocab.get(&c) {
+            tokens.push(idx as i64);
+            continue;
+        }
+        match policy {
+            UnknownPhonemePolicy::Error => {
+                return Err(format!(
+                    "phoneme '{}' (U+{:04X}) is not in the model vocab",
+                    c, c as u32
+                ));
+            }
+            UnknownPhonemePolicy::Nearest => {
+                if let Some(replacement) = nearest_phoneme(c) {
+                    tokens.extend(
+                        replacement
+                            .chars()
+                            .filter_map(|r| vocab.get(&r))
+                            .map(|&idx| idx as i64),
+                    );
+                }
+            }
+            UnknownPhonemePolicy::Skip => {}
+        }
+        if !unknown.contains(&c) {
+            unknown.push(c);
+        }
+    }
+
+    Ok((tokens, unknown))
+}
+
+/// Id-to-phoneme map of a phoneme-to-id vocab, for looking up many tokens
+pub fn reverse_vocab(vocab: &HashMap<char, usize>) -> HashMap<usize, char> {
+    vocab.iter().map(|(&c, &idx)| (idx, c)).collect()
+}
+
+/// `tokens_to_phonemes` against a custom phoneme-to-id vocab
+pub fn tokens_to_phonemes_with_vocab(tokens: &[i64], vocab: &HashMap<char, usize>) -> String {
+    let reverse = reverse_vocab(vocab);
+    tokens
+        .iter()
+        .filter_map(|&t| reverse.get(&(t as usize)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests3 {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_with_policy() {
+        // U+0303 is a combining tilde, ʦ has no vocab entry
+        let phonemes = "ã\u{0303}ʦ";
+
+        let (skipped, unknown) =
+            tokenize_with_policy(phonemes, UnknownPhonemePolicy::Skip).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(unknown, vec!['ã', '\u{0303}', 'ʦ']);
+
+        let (nearest, _) = tokenize_with_policy(phonemes, UnknownPhonemePolicy::Nearest).unwrap();
+        assert_eq!(nearest, tokenize("ats"));
+
+        assert!(tokenize_with_policy(phonemes, UnknownPhonemePolicy::Error).is_err());
+        assert_eq!(
+            tokenize_with_policy("həlˈoʊ", UnknownPhonemePolicy::Error)
+                .unwrap()
+                .0,
+            tokenize("həlˈoʊ")
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_vocab() {
+        let vocab: HashMap<char, usize> = [('a', 1), ('b', 2), ('t', 3), ('s', 4)].into();
+        let (tokens, unknown) =
+            tokenize_with_vocab("abʦ", &vocab, UnknownPhonemePolicy::Nearest).unwrap();
+        assert_eq!(tokens, vec![1, 2, 3, 4]);
+        assert_eq!(unknown, vec!['ʦ']);
+        assert_eq!(tokens_to_phonemes_with_vocab(&tokens, &vocab), "abts");
+
+        assert!(tokenize_with_vocab("abc", &vocab, UnknownPhonemePolicy::Error).is_err());
+    }
+}
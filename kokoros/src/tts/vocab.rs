@@ -32,7 +32,73 @@ pub fn print_sorted_reverse_vocab() {
     }
 }
 
+/// Load a phoneme-to-id vocab from a JSON file, either a `{"phoneme": id}`
+/// object or a Kokoro `config.json` with that object under `vocab`.
+///
+/// Every key must be a single character and every id a non-negative integer.
+pub fn load_vocab_file(path: &str) -> Result<HashMap<char, usize>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read vocab file {}: {}", path, e))?;
+    let json: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|e| format!("vocab file {} is not valid JSON: {}", path, e))?;
+
+    let entries = json
+        .get("vocab")
+        .unwrap_or(&json)
+        .as_object()
+        .ok_or_else(|| format!("vocab file {} must contain a phoneme-to-id object", path))?;
+
+    let mut vocab = HashMap::with_capacity(entries.len());
+    for (phoneme, id) in entries {
+        let mut chars = phoneme.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return Err(format!(
+                "vocab file {}: phoneme '{}' must be a single character",
+                path, phoneme
+            ));
+        };
+        let id = id.as_u64().ok_or_else(|| {
+            format!(
+                "vocab file {}: id of phoneme '{}' must be a non-negative integer, got {}",
+                path, phoneme, id
+            )
+        })?;
+        vocab.insert(c, id as usize);
+    }
+
+    if vocab.is_empty() {
+        return Err(format!("vocab file {} has no entries", path));
+    }
+    Ok(vocab)
+}
+
+/// Phonemes of `phonemes` that `vocab` has no id for, with how often each
+/// occurs, most frequent first
+pub fn missing_phonemes(phonemes: &str, vocab: &HashMap<char, usize>) -> Vec<(char, usize)> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in phonemes.chars().filter(|c| !vocab.contains_key(c)) {
+        *counts.entry(c).or_default() += 1;
+    }
+    let mut missing: Vec<(char, usize)> = counts.into_iter().collect();
+    missing.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    missing
+}
+
 lazy_static! {
     pub static ref VOCAB: HashMap<char, usize> = get_vocab();
     pub static ref REVERSE_VOCAB: HashMap<usize, char> = get_reverse_vocab();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_phonemes() {
+        assert!(missing_phonemes("həlˈoʊ wˈɜːld", &VOCAB).is_empty());
+        assert_eq!(
+            missing_phonemes("ʦaʦ\u{0303}", &VOCAB),
+            vec![('ʦ', 2), ('\u{0303}', 1)]
+        );
+    }
+}
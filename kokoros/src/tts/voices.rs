@@ -0,0 +1,229 @@
+use ndarray::Array3;
+use ndarray_npy::NpzReader;
+use std::collections::HashMap;
+use std::fs::File;
+
+/// Style vectors per input length held by a voice pack. Longer inputs use the
+/// last one, see `TTSKoko::mix_styles`
+pub const VOICE_ROWS: usize = 511;
+
+/// Width of a Kokoro v1.0 style vector
+pub const STYLE_DIM: usize = 256;
+
+/// Read every voice of a voices file (a numpy `.npz` archive of
+/// `[rows, 1, style_dim]` arrays), in archive order
+pub fn read_voices(voices_path: &str) -> Result<Vec<(String, Array3<f32>)>, String> {
+    let read_error =
+        |e: &dyn std::fmt::Display| format!("failed to read voices file {}: {}", voices_path, e);
+    let file = File::open(voices_path)
+        .map_err(|e| format!("failed to open voices file {}: {}", voices_path, e))?;
+    let mut npz = NpzReader::new(file).map_err(|e| read_error(&e))?;
+    let names = npz.names().map_err(|e| read_error(&e))?;
+
+    names
+        .into_iter()
+        .map(|name| {
+            let data: Array3<f32> = npz
+                .by_name(&name)
+                .map_err(|e| read_error(&format!("voice {}: {}", name, e)))?;
+            Ok((name, data))
+        })
+        .collect()
+}
+
+/// Read the voices of several voices files into one table, in file order.
+/// A voice name found in more than one file is an error, unless
+/// `later_overrides` lets the later file's voice replace it. Every voice must
+/// have the `[_, 1, style_dim]` layout of the first file's
+pub fn read_voice_packs(
+    paths: &[&str],
+    later_overrides: bool,
+) -> Result<Vec<(String, Array3<f32>)>, String> {
+    let mut merged: Vec<(String, Array3<f32>)> = Vec::new();
+    let mut sources: HashMap<String, (usize, &str)> = HashMap::new();
+    let mut layout: Option<(&[usize], &str)> = None;
+    let packs = paths
+        .iter()
+        .map(|path| read_voices(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (&path, voices) in paths.iter().zip(&packs) {
+        for (name, data) in voices {
+            let shape = data.shape();
+            let (first, first_path) = *layout.get_or_insert((shape, path));
+            if shape[1..] != first[1..] {
+                return Err(format!(
+                    "voice {} of {} has shape {:?}, incompatible with {:?} of {}",
+                    name, path, shape, first, first_path
+                ));
+            }
+            if shape[0] > VOICE_ROWS {
+                return Err(format!(
+                    "voice {} of {} has {} rows, expected at most {}",
+                    name, path, shape[0], VOICE_ROWS
+                ));
+            }
+
+            match sources.get(name) {
+                Some(&(i, earlier)) if later_overrides => {
+                    merged[i].1 = data.clone();
+                    sources.insert(name.clone(), (i, path));
+                    tracing::debug!("voice {} of {} overrides {}", name, path, earlier);
+                }
+                Some(&(_, earlier)) => {
+                    return Err(format!(
+                        "voice {} is in both {} and {}, rename one or pass --voices-override-order",
+                        name, earlier, path
+                    ));
+                }
+                None => {
+                    sources.insert(name.clone(), (merged.len(), path));
+                    merged.push((name.clone(), data.clone()));
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Findings about one voice of a voices file
+#[derive(Debug, Clone)]
+pub struct VoiceCheck {
+    pub name: String,
+    pub shape: Vec<usize>,
+    /// NaN or infinite values in the embeddings
+    pub non_finite: usize,
+    /// An earlier voice with identical embeddings
+    pub duplicate_of: Option<String>,
+    /// Problems that make the voice unusable or degrade it, empty for a good voice
+    pub problems: Vec<String>,
+}
+
+/// Check every voice of `voices_path` against a model taking `style_dim`
+/// wide style vectors, without running inference
+pub fn validate_voices(voices_path: &str, style_dim: usize) -> Result<Vec<VoiceCheck>, String> {
+    let voices = read_voices(voices_path)?;
+    let mut checks: Vec<VoiceCheck> = Vec::with_capacity(voices.len());
+    let mut seen_names: HashMap<&str, usize> = HashMap::new();
+    // Embeddings are compared by their bit patterns, so equal voices hash equally
+    let mut seen_data: HashMap<Vec<u32>, &str> = HashMap::new();
+
+    for (name, data) in &voices {
+        let shape = data.shape().to_vec();
+        let mut problems = Vec::new();
+
+        if let Some(count) = seen_names.get(name.as_str()) {
+            problems.push(format!("name appears {} times in the archive", count + 1));
+        }
+        *seen_names.entry(name).or_insert(0) += 1;
+
+        if shape[1] != 1 || shape[2] != style_dim {
+            problems.push(format!(
+                "expected [rows, 1, {}] embeddings, found {:?}",
+                style_dim, shape
+            ));
+        }
+        if shape[0] > VOICE_ROWS {
+            problems.push(format!(
+                "{} rows, more than the {} the loader supports",
+                shape[0], VOICE_ROWS
+            ));
+        } else if shape[0] < VOICE_ROWS - 1 {
+            problems.push(format!(
+                "only {} rows, longer inputs would use zero style vectors",
+                shape[0]
+            ));
+        }
+
+        let non_finite = data.iter().filter(|v| !v.is_finite()).count();
+        if non_finite > 0 {
+            problems.push(format!("{} NaN or infinite values", non_finite));
+        }
+
+        let bits: Vec<u32> = data.iter().map(|v| v.to_bits()).collect();
+        let duplicate_of = match seen_data.get(&bits) {
+            Some(original) => {
+                problems.push(format!("identical to {}", original));
+                Some(original.to_string())
+            }
+            None => {
+                seen_data.insert(bits, name);
+                None
+            }
+        };
+
+        checks.push(VoiceCheck {
+            name: name.clone(),
+            shape,
+            non_finite,
+            duplicate_of,
+            problems,
+        });
+    }
+
+    Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_npy::NpzWriter;
+
+    #[test]
+    fn test_validate_voices() {
+        let path = std::env::temp_dir().join(format!("kokoros-voices-{}.npz", std::process::id()));
+        let good = Array3::<f32>::from_elem((VOICE_ROWS - 1, 1, STYLE_DIM), 0.1);
+        let mut broken = good.clone();
+        broken[[3, 0, 7]] = f32::NAN;
+        let narrow = Array3::<f32>::zeros((VOICE_ROWS - 1, 1, 128));
+
+        let mut npz = NpzWriter::new(File::create(&path).unwrap());
+        npz.add_array("af_good", &good).unwrap();
+        npz.add_array("af_copy", &good).unwrap();
+        npz.add_array("af_broken", &broken).unwrap();
+        npz.add_array("af_narrow", &narrow).unwrap();
+        npz.finish().unwrap();
+
+        let checks = validate_voices(path.to_str().unwrap(), STYLE_DIM).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let by_name = |name: &str| checks.iter().find(|c| c.name == name).unwrap();
+        assert!(by_name("af_good").problems.is_empty());
+        assert_eq!(by_name("af_copy").duplicate_of.as_deref(), Some("af_good"));
+        assert_eq!(by_name("af_broken").non_finite, 1);
+        assert_eq!(by_name("af_narrow").problems.len(), 1);
+        assert!(validate_voices("/nonexistent/voices.bin", STYLE_DIM).is_err());
+    }
+
+    #[test]
+    fn test_read_voice_packs() {
+        let dir = std::env::temp_dir().join(format!("kokoros-packs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, voices: &[(&str, Array3<f32>)]| {
+            let path = dir.join(name).to_str().unwrap().to_string();
+            let mut npz = NpzWriter::new(File::create(&path).unwrap());
+            for (voice, data) in voices {
+                npz.add_array(*voice, data).unwrap();
+            }
+            npz.finish().unwrap();
+            path
+        };
+        let voice = |value| Array3::<f32>::from_elem((4, 1, STYLE_DIM), value);
+        let stock = write("stock.npz", &[("af_sky", voice(0.1))]);
+        let pack = write(
+            "pack.npz",
+            &[("xf_new", voice(0.2)), ("af_sky", voice(0.3))],
+        );
+        let narrow = write("narrow.npz", &[("xf_narrow", Array3::zeros((4, 1, 128)))]);
+
+        let duplicate = read_voice_packs(&[&stock, &pack], false).unwrap_err();
+        assert!(duplicate.contains("af_sky"), "{}", duplicate);
+        let merged = read_voice_packs(&[&stock, &pack], true).unwrap();
+        let names: Vec<&str> = merged.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["af_sky", "xf_new"]);
+        assert_eq!(merged[0].1[[0, 0, 0]], 0.3);
+        assert!(read_voice_packs(&[&stock, &narrow], true).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
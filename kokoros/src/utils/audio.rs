@@ -0,0 +1,630 @@
+/// Convert a duration in milliseconds to a sample count
+pub fn ms_to_samples(ms: u32, sample_rate: u32) -> usize {
+    (ms as u64 * sample_rate as u64 / 1000) as usize
+}
+
+/// Append `src` to `dst`, overlapping up to `overlap` samples of the tail of
+/// `dst` with the head of `src` using an equal-power cross-fade.
+///
+/// The overlap is shortened when either side is too short. Returns the index
+/// in `dst` at which `src` starts, which callers use to place timestamps.
+pub fn crossfade_append(dst: &mut Vec<f32>, src: &[f32], overlap: usize) -> usize {
+    let overlap = overlap.min(dst.len()).min(src.len());
+    let start = dst.len() - overlap;
+
+    for i in 0..overlap {
+        // Equal-power curves keep the perceived loudness constant for uncorrelated signals
+        let t = (i as f32 + 0.5) / overlap as f32 * std::f32::consts::FRAC_PI_2;
+        let (fade_in, fade_out) = t.sin_cos();
+        dst[start + i] = dst[start + i] * fade_out + src[i] * fade_in;
+    }
+    dst.extend_from_slice(&src[overlap..]);
+
+    start
+}
+
+/// Fade the last `len` samples of `samples` linearly to silence
+pub fn fade_out(samples: &mut [f32], len: usize) {
+    let len = len.min(samples.len());
+    let start = samples.len() - len;
+    for (i, s) in samples[start..].iter_mut().enumerate() {
+        *s *= (len - i) as f32 / (len + 1) as f32;
+    }
+}
+
+/// Default `soft_limit` threshold, -1 dBFS
+pub const DEFAULT_LIMITER_THRESHOLD: f32 = 0.891;
+
+/// Soft-limit `samples` in place so they never exceed full scale.
+///
+/// Samples below `threshold` are untouched; above it the excess is squashed
+/// with a tanh curve that approaches 1.0 asymptotically, instead of being hard
+/// clipped later by the integer conversion.
+pub fn soft_limit(samples: &mut [f32], threshold: f32) {
+    let threshold = threshold.clamp(0.0, 0.999);
+    let knee = 1.0 - threshold;
+    for s in samples.iter_mut() {
+        let magnitude = s.abs();
+        if magnitude > threshold {
+            let limited = threshold + knee * ((magnitude - threshold) / knee).tanh();
+            *s = limited.copysign(*s);
+        }
+    }
+}
+
+/// Settings of `de_ess`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeEsser {
+    /// Lower edge of the sibilance band, in Hz
+    pub freq_hz: f32,
+    /// Level of the band above which it is turned down, in dBFS
+    pub threshold_db: f32,
+}
+
+impl Default for DeEsser {
+    fn default() -> Self {
+        Self {
+            freq_hz: 5000.0,
+            threshold_db: -30.0,
+        }
+    }
+}
+
+/// Compression ratio of `de_ess` above its threshold
+const DE_ESS_RATIO: f32 = 4.0;
+
+/// Tame harsh sibilance in `samples` by turning down the band above
+/// `de_esser.freq_hz` while its level exceeds the threshold.
+///
+/// The band is split off with a second-order high-pass filter run forwards
+/// and backwards, so it has no phase shift and subtracting it leaves the rest
+/// of the spectrum intact, and is tracked by a peak envelope (1 ms attack,
+/// 60 ms release). Above the threshold the band is compressed 4:1; the rest
+/// of the spectrum passes unchanged, and so does everything while the band
+/// stays below the threshold.
+pub fn de_ess(samples: &mut [f32], sample_rate: u32, de_esser: &DeEsser) {
+    let rate = sample_rate as f32;
+    let freq = de_esser.freq_hz.clamp(20.0, rate * 0.45);
+
+    // RBJ cookbook high-pass, Q = 1/sqrt(2)
+    let w0 = std::f32::consts::TAU * freq / rate;
+    let alpha = w0.sin() / std::f32::consts::SQRT_2;
+    let cos_w0 = w0.cos();
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos_w0) / 2.0 / a0;
+    let b1 = -(1.0 + cos_w0) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos_w0 / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    let attack = 1.0 - (-1.0 / (0.001 * rate)).exp();
+    let release = 1.0 - (-1.0 / (0.060 * rate)).exp();
+    let threshold = db_to_gain(de_esser.threshold_db);
+
+    let high_pass = |input: &mut dyn Iterator<Item = f32>| -> Vec<f32> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        input
+            .map(|x| {
+                let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    };
+    let forward = high_pass(&mut samples.iter().copied());
+    let mut band = high_pass(&mut forward.into_iter().rev());
+    band.reverse();
+
+    let mut envelope = 0.0f32;
+    for (s, band) in samples.iter_mut().zip(band) {
+        let level = band.abs();
+        let coeff = if level > envelope { attack } else { release };
+        envelope += coeff * (level - envelope);
+
+        if envelope > threshold {
+            let gain = (threshold / envelope).powf(1.0 - 1.0 / DE_ESS_RATIO);
+            *s -= band * (1.0 - gain);
+        }
+    }
+}
+
+/// Range of `samples` between the first and last sample whose magnitude
+/// exceeds `threshold`, i.e. without leading and trailing silence. Empty for
+/// all-silent input.
+pub fn trim_silence(samples: &[f32], threshold: f32) -> std::ops::Range<usize> {
+    let is_loud = |s: &f32| s.abs() > threshold;
+    match samples.iter().position(is_loud) {
+        Some(start) => start..samples.iter().rposition(is_loud).unwrap() + 1,
+        None => 0..0,
+    }
+}
+
+/// Split `samples` into the spoken segments between pauses, with a simple
+/// energy-based voice activity detector.
+///
+/// `samples` is measured in frames of `frame_len` samples; a frame is silent
+/// when its RMS level is at or below `threshold`. Runs of at least
+/// `min_silence` silent samples separate segments and are cut in the middle,
+/// so each segment keeps part of the pause around it. Leading and trailing
+/// silence is dropped, and all-silent input has no segments.
+pub fn split_on_silence(
+    samples: &[f32],
+    threshold: f32,
+    min_silence: usize,
+    frame_len: usize,
+) -> Vec<std::ops::Range<usize>> {
+    let frame_len = frame_len.max(1);
+    let mut segments = Vec::new();
+    let mut segment_start: Option<usize> = None;
+    let mut loud_end = 0;
+
+    for (i, frame) in samples.chunks(frame_len).enumerate() {
+        let sum_squares: f32 = frame.iter().map(|s| s * s).sum();
+        if (sum_squares / frame.len() as f32).sqrt() <= threshold {
+            continue;
+        }
+        let frame_start = i * frame_len;
+        match segment_start {
+            None => segment_start = Some(frame_start),
+            Some(start) if frame_start - loud_end >= min_silence => {
+                let cut = (loud_end + frame_start) / 2;
+                segments.push(start..cut);
+                segment_start = Some(cut);
+            }
+            Some(_) => {}
+        }
+        loud_end = frame_start + frame.len();
+    }
+    if let Some(start) = segment_start {
+        segments.push(start..loud_end);
+    }
+    segments
+}
+
+/// `samples` with every pause between speech longer than `max_pause`
+/// samples shortened to `max_pause`, for tighter pacing without a faster
+/// speed. Frames and silence are detected like in `split_on_silence`; the
+/// two ends of a long pause are kept and its middle is cut out. Leading and
+/// trailing silence is left alone.
+pub fn clamp_pauses(
+    samples: &[f32],
+    threshold: f32,
+    max_pause: usize,
+    frame_len: usize,
+) -> Vec<f32> {
+    let frame_len = frame_len.max(1);
+    let mut clamped = Vec::with_capacity(samples.len());
+    // `samples[..copied]` is already in `clamped` or cut out
+    let mut copied = 0;
+    let mut loud_end: Option<usize> = None;
+
+    for (i, frame) in samples.chunks(frame_len).enumerate() {
+        let sum_squares: f32 = frame.iter().map(|s| s * s).sum();
+        if (sum_squares / frame.len() as f32).sqrt() <= threshold {
+            continue;
+        }
+        let frame_start = i * frame_len;
+        match loud_end {
+            Some(end) if frame_start - end > max_pause => {
+                let head = max_pause / 2;
+                clamped.extend_from_slice(&samples[copied..end + head]);
+                copied = frame_start - (max_pause - head);
+            }
+            _ => {}
+        }
+        loud_end = Some(frame_start + frame.len());
+    }
+    clamped.extend_from_slice(&samples[copied..]);
+    clamped
+}
+
+/// Linear amplitude factor of a gain in decibels
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Convert an f32 sample to 16-bit PCM, rounded to the nearest step.
+/// Out-of-range samples are clipped and NaN becomes silence
+pub fn sample_to_i16(sample: f32) -> i16 {
+    (sample * 32767.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+/// Convert f32 samples to headerless 16-bit little-endian PCM, two bytes per
+/// sample with the low byte first, see `sample_to_i16`
+pub fn to_pcm16(samples: &[f32]) -> Vec<u8> {
+    let mut pcm_data = Vec::with_capacity(samples.len() * 2);
+    for &sample in samples {
+        pcm_data.extend_from_slice(&sample_to_i16(sample).to_le_bytes());
+    }
+    pcm_data
+}
+
+/// Mix `background` into `samples` at `gain`, looping it when it is shorter
+/// and cutting it at the end of `samples`
+pub fn mix_background(samples: &mut [f32], background: &[f32], gain: f32) {
+    if background.is_empty() {
+        return;
+    }
+    for (s, b) in samples.iter_mut().zip(background.iter().cycle()) {
+        *s += b * gain;
+    }
+}
+
+/// Level statistics of synthesized audio, for flagging clipped or silent output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioStats {
+    /// Largest absolute sample value
+    pub peak: f32,
+    pub rms: f32,
+    /// Samples at or beyond full scale, which integer output formats clip
+    pub clipped_samples: usize,
+}
+
+impl AudioStats {
+    pub fn measure(samples: &[f32]) -> Self {
+        let mut peak = 0f32;
+        let mut sum_squares = 0f64;
+        let mut clipped_samples = 0;
+        for &s in samples {
+            let magnitude = s.abs();
+            peak = peak.max(magnitude);
+            sum_squares += (s as f64) * (s as f64);
+            if magnitude >= 1.0 {
+                clipped_samples += 1;
+            }
+        }
+        let rms = if samples.is_empty() {
+            0.0
+        } else {
+            (sum_squares / samples.len() as f64).sqrt() as f32
+        };
+        Self {
+            peak,
+            rms,
+            clipped_samples,
+        }
+    }
+
+    /// Stats of `len` samples measured as `self` followed by `other_len`
+    /// samples measured as `other`, for audio that is never held in one buffer
+    pub fn combine(self, len: usize, other: AudioStats, other_len: usize) -> Self {
+        let total = len + other_len;
+        let rms = if total == 0 {
+            0.0
+        } else {
+            let sum_squares = |rms: f32, n: usize| (rms as f64).powi(2) * n as f64;
+            ((sum_squares(self.rms, len) + sum_squares(other.rms, other_len)) / total as f64).sqrt()
+                as f32
+        };
+        Self {
+            peak: self.peak.max(other.peak),
+            rms,
+            clipped_samples: self.clipped_samples + other.clipped_samples,
+        }
+    }
+}
+
+impl std::fmt::Display for AudioStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dbfs = |level: f32| 20.0 * level.max(1e-10).log10();
+        write!(
+            f,
+            "peak {:.3} ({:.1} dBFS), rms {:.3} ({:.1} dBFS), {} clipped samples",
+            self.peak,
+            dbfs(self.peak),
+            self.rms,
+            dbfs(self.rms),
+            self.clipped_samples
+        )
+    }
+}
+
+/// Highest peak `batch_gain` allows, in dBFS
+pub const BATCH_PEAK_CEILING_DB: f32 = -1.0;
+
+/// One gain for a batch of clips, given the stats and length of each, that
+/// brings their overall RMS to `target_rms` without lifting the peak of the
+/// loudest clip past `BATCH_PEAK_CEILING_DB`. Applied to every clip alike,
+/// it keeps the levels of the clips relative to each other
+pub fn batch_gain(clips: &[(AudioStats, usize)], target_rms: f32) -> f32 {
+    let (overall, _) = clips.iter().fold(
+        (AudioStats::default(), 0),
+        |(stats, len), &(clip, clip_len)| (stats.combine(len, clip, clip_len), len + clip_len),
+    );
+    if overall.rms <= 0.0 || overall.peak <= 0.0 {
+        return 1.0;
+    }
+    (target_rms / overall.rms).min(db_to_gain(BATCH_PEAK_CEILING_DB) / overall.peak)
+}
+
+/// Speed/fidelity tiers of `resample_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResampleQuality {
+    /// Linear interpolation between neighbouring samples. Cheapest, but
+    /// without anti-aliasing, so downsampling folds high frequencies back
+    Fast,
+    /// Windowed sinc with an 8 sample half-width
+    Medium,
+    /// Windowed sinc with a 16 sample half-width
+    #[default]
+    High,
+}
+
+impl std::str::FromStr for ResampleQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "fast" => Ok(ResampleQuality::Fast),
+            "medium" => Ok(ResampleQuality::Medium),
+            "high" => Ok(ResampleQuality::High),
+            other => Err(format!(
+                "unknown resample quality '{}', expected fast, medium or high",
+                other
+            )),
+        }
+    }
+}
+
+/// Resample `samples` from `from_rate` to `to_rate` Hz at `ResampleQuality::High`
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    resample_with(samples, from_rate, to_rate, ResampleQuality::High)
+}
+
+/// Resample `samples` from `from_rate` to `to_rate` Hz.
+///
+/// The sinc tiers use Hann-windowed sinc interpolation; when downsampling the
+/// kernel is widened so it also acts as the anti-aliasing low-pass filter.
+pub fn resample_with(
+    samples: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() || from_rate == 0 || to_rate == 0 {
+        return samples.to_vec();
+    }
+
+    let ratio = to_rate as f64 / from_rate as f64;
+    let out_len = (samples.len() as f64 * ratio).round() as usize;
+    let half_taps = match quality {
+        ResampleQuality::Fast => return resample_linear(samples, ratio, out_len),
+        ResampleQuality::Medium => 8,
+        ResampleQuality::High => 16,
+    };
+    let cutoff = ratio.min(1.0);
+    let half_width = half_taps as f64 / cutoff;
+
+    (0..out_len)
+        .map(|i| {
+            let center = i as f64 / ratio;
+            let first = (center - half_width).ceil().max(0.0) as usize;
+            let last = ((center + half_width).floor() as usize).min(samples.len() - 1);
+
+            let mut acc = 0.0;
+            let mut weight_sum = 0.0;
+            for (j, &sample) in samples.iter().enumerate().take(last + 1).skip(first) {
+                let x = j as f64 - center;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let t = std::f64::consts::PI * x * cutoff;
+                    t.sin() / t
+                };
+                let window = 0.5 + 0.5 * (std::f64::consts::PI * x / half_width).cos();
+                let weight = sinc * window;
+                acc += sample as f64 * weight;
+                weight_sum += weight;
+            }
+            // Normalizing by the weight sum keeps DC gain at 1, including at the edges
+            if weight_sum.abs() > f64::EPSILON {
+                (acc / weight_sum) as f32
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// `out_len` samples linearly interpolated from `samples` at `ratio` output
+/// samples per input sample
+fn resample_linear(samples: &[f32], ratio: f64, out_len: usize) -> Vec<f32> {
+    let last = samples.len() - 1;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 / ratio;
+            let j = (pos.floor() as usize).min(last);
+            let frac = (pos - j as f64).clamp(0.0, 1.0) as f32;
+            let next = samples[(j + 1).min(last)];
+            samples[j] + (next - samples[j]) * frac
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_pcm16() {
+        let pcm = to_pcm16(&[0.0, 0.5, -1.0, 2.0, f32::NAN]);
+        // No header, two little-endian bytes per sample
+        assert_eq!(pcm.len(), 10);
+        assert_eq!(&pcm[..4], &[0x00, 0x00, 0x00, 0x40]);
+        assert_eq!(i16::from_le_bytes([pcm[4], pcm[5]]), -32767);
+        assert_eq!(i16::from_le_bytes([pcm[6], pcm[7]]), i16::MAX);
+        assert_eq!(&pcm[8..], &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_soft_limit() {
+        let mut samples = vec![0.5, -0.5, 0.95, 1.5, -3.0, 0.0];
+        soft_limit(&mut samples, 0.9);
+        assert_eq!(&samples[..2], &[0.5, -0.5]);
+        assert!(samples[2] > 0.9 && samples[2] < 0.95);
+        assert!(samples[3] > 0.95 && samples[3] <= 1.0);
+        assert!(samples[4] < -0.95 && samples[4] >= -1.0);
+        assert_eq!(samples[5], 0.0);
+    }
+
+    #[test]
+    fn test_de_ess() {
+        let tone = |freq: f32| -> Vec<f32> {
+            (0..24_000)
+                .map(|i| (i as f32 * freq * std::f32::consts::TAU / 24_000.0).sin() * 0.5)
+                .collect()
+        };
+        // Away from the edges, where the filters settle
+        let peak = |s: &[f32]| s[6_000..18_000].iter().fold(0f32, |m, &x| m.max(x.abs()));
+
+        let mut hiss = tone(8000.0);
+        de_ess(&mut hiss, 24_000, &DeEsser::default());
+        assert!(peak(&hiss) < 0.1, "{}", peak(&hiss));
+
+        // Below the band, and quiet sibilance, are left alone
+        let mut voice = tone(200.0);
+        de_ess(&mut voice, 24_000, &DeEsser::default());
+        assert!((peak(&voice) - 0.5).abs() < 0.01);
+        let mut quiet: Vec<f32> = tone(8000.0).iter().map(|s| s * 0.01).collect();
+        let before = quiet.clone();
+        de_ess(&mut quiet, 24_000, &DeEsser::default());
+        assert_eq!(quiet, before);
+    }
+
+    #[test]
+    fn test_split_on_silence() {
+        let mut samples = vec![0.0; 50];
+        samples.extend([0.5; 100]);
+        samples.extend([0.001; 300]);
+        samples.extend([-0.5; 100]);
+        samples.extend([0.0; 50]);
+
+        assert_eq!(
+            split_on_silence(&samples, 0.01, 200, 10),
+            vec![50..300, 300..550]
+        );
+        // Pauses shorter than the minimum stay inside one segment
+        assert_eq!(split_on_silence(&samples, 0.01, 400, 10), vec![50..550]);
+        assert!(split_on_silence(&[0.0; 100], 0.01, 200, 10).is_empty());
+
+        // The 300 sample pause is cut to 100, the leading and trailing
+        // silence stays
+        let clamped = clamp_pauses(&samples, 0.01, 100, 10);
+        assert_eq!(clamped.len(), 400);
+        assert_eq!(&clamped[..150], &samples[..150]);
+        assert_eq!(&clamped[250..], &samples[450..]);
+        assert_eq!(clamp_pauses(&samples, 0.01, 300, 10), samples);
+    }
+
+    #[test]
+    fn test_crossfade_append() {
+        let mut audio = vec![1.0; 10];
+        let start = crossfade_append(&mut audio, &[1.0; 10], 4);
+        assert_eq!(start, 6);
+        assert_eq!(audio.len(), 16);
+        // Constant signals stay close to constant through the fade
+        assert!(audio.iter().all(|&s| (0.99..=1.42).contains(&s)));
+
+        let mut audio = vec![0.5; 3];
+        assert_eq!(crossfade_append(&mut audio, &[0.5; 2], 0), 3);
+        assert_eq!(audio.len(), 5);
+
+        // The overlap never exceeds what is available
+        let mut audio = vec![0.0; 2];
+        assert_eq!(crossfade_append(&mut audio, &[1.0; 8], 5), 0);
+        assert_eq!(audio.len(), 8);
+    }
+
+    #[test]
+    fn test_fade_out() {
+        let mut samples = vec![1.0; 6];
+        fade_out(&mut samples, 3);
+        assert_eq!(samples, vec![1.0, 1.0, 1.0, 0.75, 0.5, 0.25]);
+        fade_out(&mut samples, 10);
+        assert!(samples[5] < 0.25 && samples[0] < 1.0);
+    }
+
+    #[test]
+    fn test_trim_silence() {
+        let samples = [0.0, 0.001, 0.2, -0.5, 0.0, 0.3, 0.002, 0.0];
+        assert_eq!(trim_silence(&samples, 0.01), 2..6);
+        assert_eq!(trim_silence(&[0.0; 4], 0.01), 0..0);
+        assert_eq!(trim_silence(&[], 0.01), 0..0);
+    }
+
+    #[test]
+    fn test_mix_background() {
+        let mut samples = vec![0.5; 5];
+        mix_background(&mut samples, &[0.2, -0.2], db_to_gain(-6.0206));
+        let expected = [0.6, 0.4, 0.6, 0.4, 0.6];
+        assert!(
+            samples
+                .iter()
+                .zip(expected)
+                .all(|(s, e)| (s - e).abs() < 1e-4)
+        );
+
+        mix_background(&mut samples, &[], 1.0);
+        assert_eq!(samples.len(), 5);
+    }
+
+    #[test]
+    fn test_audio_stats() {
+        let stats = AudioStats::measure(&[0.5, -0.5, 1.2, -1.0, 0.0]);
+        assert_eq!(stats.peak, 1.2);
+        assert_eq!(stats.clipped_samples, 2);
+        assert!((stats.rms - (2.94f32 / 5.0).sqrt()).abs() < 1e-6);
+
+        assert_eq!(AudioStats::measure(&[]), AudioStats::default());
+
+        let combined =
+            AudioStats::measure(&[0.5, -0.5]).combine(2, AudioStats::measure(&[1.2, -1.0, 0.0]), 3);
+        assert_eq!(combined.peak, stats.peak);
+        assert_eq!(combined.clipped_samples, stats.clipped_samples);
+        assert!((combined.rms - stats.rms).abs() < 1e-6);
+
+        let clips = [
+            (AudioStats::measure(&[0.1, -0.1]), 2),
+            (AudioStats::measure(&[0.2, -0.2]), 2),
+        ];
+        // Overall RMS is sqrt(0.025)
+        assert!((batch_gain(&clips, 0.1) - 0.1 / 0.025f32.sqrt()).abs() < 1e-5);
+        // The loudest peak caps the gain
+        let ceiling = db_to_gain(BATCH_PEAK_CEILING_DB) / 0.2;
+        assert!((batch_gain(&clips, 1.0) - ceiling).abs() < 1e-5);
+        assert_eq!(batch_gain(&[], 0.1), 1.0);
+    }
+
+    #[test]
+    fn test_resample() {
+        let tone: Vec<f32> = (0..24_000)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 24_000.0).sin() * 0.5)
+            .collect();
+
+        let down = resample(&tone, 24_000, 16_000);
+        assert_eq!(down.len(), 16_000);
+        let up = resample(&tone, 24_000, 48_000);
+        assert_eq!(up.len(), 48_000);
+
+        // The tone keeps its level away from the edges
+        let peak = |s: &[f32]| {
+            s[1000..s.len() - 1000]
+                .iter()
+                .fold(0f32, |m, &x| m.max(x.abs()))
+        };
+        assert!((peak(&down) - 0.5).abs() < 0.02);
+        assert!((peak(&up) - 0.5).abs() < 0.02);
+
+        assert_eq!(resample(&tone[..10], 24_000, 24_000), &tone[..10]);
+
+        for quality in [ResampleQuality::Fast, ResampleQuality::Medium] {
+            let up = resample_with(&tone, 24_000, 48_000, quality);
+            assert_eq!(up.len(), 48_000);
+            assert!((peak(&up) - 0.5).abs() < 0.02, "{:?}", quality);
+        }
+        let linear = resample_with(&[0.0, 1.0], 1, 2, ResampleQuality::Fast);
+        assert_eq!(linear, [0.0, 0.5, 1.0, 1.0]);
+        assert_eq!("Medium".parse(), Ok(ResampleQuality::Medium));
+        assert!("best".parse::<ResampleQuality>().is_err());
+    }
+}
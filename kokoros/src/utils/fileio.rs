@@ -1,8 +1,21 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use serde_json::Value;
-use std::{io::Read, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{BufWriter, Read},
+    path::{Path, PathBuf},
+};
 use tokio::{fs::File, io::AsyncWriteExt};
 
+fn download_progress_bar(total_size: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total_size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb
+}
+
 pub async fn download_file_from_url(
     url: &str,
     path: &str,
@@ -16,13 +29,9 @@ pub async fn download_file_from_url(
     if resp.status().is_success() {
         let total_size = resp.content_length().unwrap_or(0);
 
-        eprintln!("Downloading {} - total size: {}", path, total_size);
+        tracing::info!("Downloading {} - total size: {}", path, total_size);
 
-        let pb = ProgressBar::new(total_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .unwrap()
-            .progress_chars("#>-"));
+        let pb = download_progress_bar(total_size);
 
         let mut file = File::create(path).await?;
         let mut downloaded = 0;
@@ -40,6 +49,175 @@ pub async fn download_file_from_url(
     }
 }
 
+/// Blocking variant of `download_file_from_url`, for callers without an async
+/// runtime. Must not be called from within one.
+pub fn download_file_from_url_blocking(
+    url: &str,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let resp = reqwest::blocking::get(url)?;
+
+    if resp.status().is_success() {
+        let total_size = resp.content_length().unwrap_or(0);
+
+        tracing::info!("Downloading {} - total size: {}", path, total_size);
+
+        let pb = download_progress_bar(total_size);
+
+        let mut file = BufWriter::new(std::fs::File::create(path)?);
+        std::io::copy(&mut pb.wrap_read(resp), &mut file)?;
+
+        pb.finish_with_message("Download completed");
+        Ok(())
+    } else {
+        Err(format!("Failed to download file: {}", resp.status()).into())
+    }
+}
+
+/// Whether `path` is an http(s) URL rather than a local file
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Cache directory for downloaded files: `$XDG_CACHE_HOME/kokoros`, or
+/// `~/.cache/kokoros`
+pub fn default_cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("kokoros")
+}
+
+/// Where `url` is cached in `cache_dir`: its file name prefixed with a hash
+/// of the whole URL, so same-named files from different sources don't collide
+pub fn cached_url_path(url: &str, cache_dir: &Path) -> PathBuf {
+    let hash: String = Sha256::digest(url.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("model.onnx");
+    cache_dir.join(format!("{}-{}", hash, name))
+}
+
+/// Lowercase hex SHA-256 of the file at `path`, read in blocks so large
+/// models aren't loaded into memory
+pub fn sha256_file(path: &str) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Check the file at `path` against an expected hex SHA-256 (any case),
+/// returning the computed hash
+pub fn verify_sha256(path: &str, expected: &str) -> Result<String, String> {
+    let actual = sha256_file(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(format!(
+            "checksum mismatch for {}: expected sha256 {}, got {}. The file may be corrupted or truncated",
+            path,
+            expected.trim().to_lowercase(),
+            actual
+        ));
+    }
+    Ok(actual)
+}
+
+/// Download the model at `url` into `cache_dir` unless it is already cached,
+/// and return its local path.
+///
+/// Unlike `download_file_from_url`, the response is checked before it is
+/// trusted: error statuses, text responses (e.g. an HTML error page) and
+/// truncated or empty bodies fail instead of leaving a broken model behind.
+pub async fn fetch_cached_model(
+    url: &str,
+    cache_dir: &Path,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let path = cached_url_path(url, cache_dir);
+    let path_str = path.to_string_lossy().to_string();
+    if path.exists() {
+        tracing::debug!("Using cached model {} for {}", path_str, url);
+        return Ok(path_str);
+    }
+    std::fs::create_dir_all(cache_dir)?;
+
+    let mut resp = reqwest::get(url).await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(format!("model not found at {} (HTTP 404)", url).into());
+    }
+    if !status.is_success() {
+        return Err(format!("failed to download model {}: HTTP {}", url, status).into());
+    }
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase();
+    if content_type.starts_with("text/") || content_type.contains("json") {
+        return Err(format!("{} returned {} instead of a model file", url, content_type).into());
+    }
+
+    let total_size = resp.content_length();
+    tracing::info!(
+        "Downloading {} to {} - total size: {}",
+        url,
+        path_str,
+        total_size.unwrap_or(0)
+    );
+    let pb = download_progress_bar(total_size.unwrap_or(0));
+
+    // Download next to the final path and rename, so an interrupted download
+    // is never mistaken for a cached model
+    let partial = path.with_extension("part");
+    let mut file = File::create(&partial).await?;
+    let mut downloaded: u64 = 0;
+    while let Some(chunk) = resp.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        pb.set_position(downloaded);
+    }
+    file.flush().await?;
+    pb.finish_with_message("Download completed");
+
+    let size_error = match total_size {
+        _ if downloaded == 0 => Some(format!("{} returned an empty body", url)),
+        Some(expected) if expected != downloaded => Some(format!(
+            "download of {} was truncated: got {} of {} bytes",
+            url, downloaded, expected
+        )),
+        _ => None,
+    };
+    if let Some(e) = size_error {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e.into());
+    }
+    std::fs::rename(&partial, &path)?;
+    Ok(path_str)
+}
+
 pub fn load_json_file(path: &str) -> Result<Value, String> {
     let file = std::fs::File::open(path);
     if file.is_err() {
@@ -54,3 +232,160 @@ pub fn load_json_file(path: &str) -> Result<Value, String> {
 
     Ok(json_value)
 }
+
+/// Character encoding of text input files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// UTF-8, falling back to Latin-1 (with a warning) when the bytes aren't valid UTF-8
+    #[default]
+    Auto,
+    Utf8,
+    Latin1,
+}
+
+impl std::str::FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "auto" => Ok(TextEncoding::Auto),
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(TextEncoding::Latin1),
+            _ => Err(format!(
+                "unknown encoding '{}', expected auto, utf-8 or latin-1",
+                s
+            )),
+        }
+    }
+}
+
+/// Decode raw file contents using `encoding`.
+///
+/// Strict UTF-8 decoding reports the byte offset of the first invalid sequence.
+pub fn decode_text(bytes: &[u8], encoding: TextEncoding) -> Result<String, String> {
+    let latin1 = |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect::<String>();
+
+    match encoding {
+        TextEncoding::Latin1 => Ok(latin1(bytes)),
+        TextEncoding::Utf8 => std::str::from_utf8(bytes)
+            .map(|s| s.to_string())
+            .map_err(|e| {
+                format!(
+                    "invalid UTF-8 at byte offset {} (byte 0x{:02x}); try --input-encoding latin-1",
+                    e.valid_up_to(),
+                    bytes[e.valid_up_to()]
+                )
+            }),
+        TextEncoding::Auto => match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(e) => {
+                tracing::warn!(
+                    "Input is not valid UTF-8 (first invalid byte at offset {}), decoding as Latin-1",
+                    e.valid_up_to()
+                );
+                Ok(latin1(bytes))
+            }
+        },
+    }
+}
+
+/// Turn Windows (`\r\n`) and old Mac (`\r`) line endings into `\n`, so no
+/// stray `\r` reaches phonemization
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// `text` without a leading byte order mark. Windows editors often save one
+/// at the start of UTF-8 files, and phonemized it adds an odd leading sound
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+/// Read a text file, decoding it with `encoding`. A leading byte order mark
+/// is dropped and line endings are normalized to `\n`
+pub fn read_text_file(path: &str, encoding: TextEncoding) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    decode_text(&bytes, encoding)
+        .map(|text| normalize_line_endings(strip_bom(&text)))
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_text() {
+        // "café" encoded as Latin-1
+        let latin1_bytes = b"caf\xe9";
+
+        assert_eq!(
+            decode_text("café".as_bytes(), TextEncoding::Utf8).unwrap(),
+            "café"
+        );
+        assert_eq!(
+            decode_text(latin1_bytes, TextEncoding::Latin1).unwrap(),
+            "café"
+        );
+        assert_eq!(
+            decode_text(latin1_bytes, TextEncoding::Auto).unwrap(),
+            "café"
+        );
+
+        let err = decode_text(latin1_bytes, TextEncoding::Utf8).unwrap_err();
+        assert!(err.contains("byte offset 3"));
+    }
+
+    #[test]
+    fn test_read_text_file_crlf() {
+        let path = std::env::temp_dir().join(format!("kokoros-crlf-{}.txt", std::process::id()));
+        std::fs::write(&path, b"first line\r\nsecond line\rthird line\r\n").unwrap();
+        let text = read_text_file(path.to_str().unwrap(), TextEncoding::Auto).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!text.contains('\r'));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines, ["first line", "second line", "third line"]);
+    }
+
+    #[test]
+    fn test_read_text_file_bom() {
+        let path = std::env::temp_dir().join(format!("kokoros-bom-{}.txt", std::process::id()));
+        std::fs::write(&path, b"\xef\xbb\xbfHello world\r\n").unwrap();
+        let text = read_text_file(path.to_str().unwrap(), TextEncoding::Utf8).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(text, "Hello world\n");
+        assert_eq!(strip_bom("no mark"), "no mark");
+        // Only a leading mark is removed
+        assert_eq!(strip_bom("a\u{feff}b"), "a\u{feff}b");
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let path = std::env::temp_dir().join(format!("kokoros-sha-{}.bin", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        let path = path.to_str().unwrap();
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        assert_eq!(sha256_file(path).unwrap(), abc);
+        assert!(verify_sha256(path, &abc.to_uppercase()).is_ok());
+        let err = verify_sha256(path, &"0".repeat(64)).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert!(err.contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn test_cached_url_path() {
+        let dir = Path::new("/cache");
+        let a = cached_url_path("https://a.example/m/kokoro.onnx?download=1", dir);
+        let b = cached_url_path("https://b.example/kokoro.onnx", dir);
+        assert!(a.to_string_lossy().ends_with("-kokoro.onnx"));
+        assert_ne!(a, b);
+        assert_eq!(
+            a,
+            cached_url_path("https://a.example/m/kokoro.onnx?download=1", dir)
+        );
+        assert!(is_url("http://host/x.onnx") && !is_url("checkpoints/x.onnx"));
+    }
+}
@@ -1,5 +1,7 @@
+pub mod audio;
 pub mod debug;
 pub mod fileio;
 pub mod mp3;
 // pub mod opus;
+pub mod stream_encoder;
 pub mod wav;
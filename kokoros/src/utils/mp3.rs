@@ -1,39 +1,28 @@
-use mp3lame_encoder::{Builder, FlushNoGap, Id3Tag, MonoPcm};
+use crate::utils::audio::sample_to_i16;
+use mp3lame_encoder::{Builder, Encoder, FlushNoGap, Id3Tag, MonoPcm};
 
-pub fn pcm_to_mp3(pcm_data: &[f32], sample_rate: u32) -> Result<Vec<u8>, std::io::Error> {
+fn lame_error(context: &str, e: impl std::fmt::Debug) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, format!("{}: {:?}", context, e))
+}
+
+fn build_encoder(sample_rate: u32) -> Result<Encoder, std::io::Error> {
     let mut mp3_encoder = Builder::new().ok_or(std::io::Error::new(
         std::io::ErrorKind::Other,
         format!("Encoder init failed"),
     ))?;
 
-    mp3_encoder.set_num_channels(1).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Set channels failed: {:?}", e),
-        )
-    })?;
-    mp3_encoder.set_sample_rate(sample_rate).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Set sample rate failed: {:?}", e),
-        )
-    })?;
+    mp3_encoder
+        .set_num_channels(1)
+        .map_err(|e| lame_error("Set channels failed", e))?;
+    mp3_encoder
+        .set_sample_rate(sample_rate)
+        .map_err(|e| lame_error("Set sample rate failed", e))?;
     mp3_encoder
         .set_brate(mp3lame_encoder::Bitrate::Kbps192)
-        .map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Set bitrate failed: {:?}", e),
-            )
-        })?;
+        .map_err(|e| lame_error("Set bitrate failed", e))?;
     mp3_encoder
         .set_quality(mp3lame_encoder::Quality::Best)
-        .map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Set quality failed: {:?}", e),
-            )
-        })?;
+        .map_err(|e| lame_error("Set quality failed", e))?;
 
     let _ = mp3_encoder.set_id3_tag(Id3Tag {
         title: b"Generated Audio",
@@ -44,43 +33,77 @@ pub fn pcm_to_mp3(pcm_data: &[f32], sample_rate: u32) -> Result<Vec<u8>, std::io
         comment: b"Generated by TTS",
     });
 
-    let mut mp3_encoder = mp3_encoder.build().map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Build encoder failed: {:?}", e),
-        )
-    })?;
+    mp3_encoder
+        .build()
+        .map_err(|e| lame_error("Build encoder failed", e))
+}
 
-    let pcm_i16: Vec<i16> = pcm_data
-        .iter()
-        .map(|&x| (x * i16::MAX as f32) as i16)
-        .collect();
+fn encode_into(
+    mp3_encoder: &mut Encoder,
+    pcm_data: &[f32],
+    mp3_out_buffer: &mut Vec<u8>,
+) -> Result<(), std::io::Error> {
+    let pcm_i16: Vec<i16> = pcm_data.iter().copied().map(sample_to_i16).collect();
     let pcm = MonoPcm(&pcm_i16);
 
-    let mut mp3_out_buffer = Vec::new();
     mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(pcm.0.len()));
-
     let encoded_size = mp3_encoder
         .encode(pcm, mp3_out_buffer.spare_capacity_mut())
-        .map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Encoding failed: {:?}", e),
-            )
-        })?;
+        .map_err(|e| lame_error("Encoding failed", e))?;
 
     unsafe {
         mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
     }
+    Ok(())
+}
 
+fn flush_into(mp3_encoder: Encoder, mp3_out_buffer: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    let mut mp3_encoder = mp3_encoder;
+    // Room for the frames LAME still buffers
+    mp3_out_buffer.reserve(7200);
     let flush_size = mp3_encoder
         .flush::<FlushNoGap>(mp3_out_buffer.spare_capacity_mut())
-        .map_err(|e| {
-            std::io::Error::new(std::io::ErrorKind::Other, format!("Flush failed: {:?}", e))
-        })?;
+        .map_err(|e| lame_error("Flush failed", e))?;
     unsafe {
         mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(flush_size));
     }
+    Ok(())
+}
 
+pub fn pcm_to_mp3(pcm_data: &[f32], sample_rate: u32) -> Result<Vec<u8>, std::io::Error> {
+    let mut mp3_encoder = build_encoder(sample_rate)?;
+    let mut mp3_out_buffer = Vec::new();
+    encode_into(&mut mp3_encoder, pcm_data, &mut mp3_out_buffer)?;
+    flush_into(mp3_encoder, &mut mp3_out_buffer)?;
     Ok(mp3_out_buffer)
 }
+
+/// MP3 encoder fed chunk by chunk. One LAME encoder spans all chunks, so the
+/// frames it returns concatenate into a single gapless stream, the same as
+/// `pcm_to_mp3` of the whole audio.
+pub struct Mp3StreamEncoder {
+    encoder: Encoder,
+}
+
+impl Mp3StreamEncoder {
+    pub fn new(sample_rate: u32) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            encoder: build_encoder(sample_rate)?,
+        })
+    }
+
+    /// Encode the next chunk of mono samples. LAME holds back the samples of
+    /// an incomplete frame, so the result may be empty for short chunks.
+    pub fn encode(&mut self, pcm_data: &[f32]) -> Result<Vec<u8>, std::io::Error> {
+        let mut mp3_out_buffer = Vec::new();
+        encode_into(&mut self.encoder, pcm_data, &mut mp3_out_buffer)?;
+        Ok(mp3_out_buffer)
+    }
+
+    /// The frames of the samples still buffered, ending the stream
+    pub fn finish(self) -> Result<Vec<u8>, std::io::Error> {
+        let mut mp3_out_buffer = Vec::new();
+        flush_into(self.encoder, &mut mp3_out_buffer)?;
+        Ok(mp3_out_buffer)
+    }
+}
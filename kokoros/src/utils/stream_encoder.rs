@@ -0,0 +1,118 @@
+use crate::utils::audio::to_pcm16;
+use crate::utils::mp3::Mp3StreamEncoder;
+use crate::utils::wav::{WavHeader, write_audio_chunk};
+use std::io;
+use std::str::FromStr;
+
+/// Encodings `StreamEncoder` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// A 32-bit float mono WAV header with placeholder sizes before the first
+    /// chunk, then bare data segments
+    Wav,
+    Mp3,
+    /// Headerless signed 16-bit little-endian mono samples
+    Pcm,
+}
+
+impl FromStr for StreamFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wav" => Ok(StreamFormat::Wav),
+            "mp3" => Ok(StreamFormat::Mp3),
+            "pcm" => Ok(StreamFormat::Pcm),
+            other => Err(format!(
+                "unknown stream format '{}', expected wav, mp3 or pcm",
+                other
+            )),
+        }
+    }
+}
+
+enum State {
+    Wav { header_written: bool },
+    Mp3(Mp3StreamEncoder),
+    Pcm,
+}
+
+/// Turns audio chunks into byte chunks of one continuous stream in a
+/// `StreamFormat`, keeping the encoder state between chunks. Concatenating
+/// everything `encode` and `finish` return gives a complete file.
+pub struct StreamEncoder {
+    sample_rate: u32,
+    state: State,
+}
+
+impl StreamEncoder {
+    pub fn new(format: StreamFormat, sample_rate: u32) -> io::Result<Self> {
+        let state = match format {
+            StreamFormat::Wav => State::Wav {
+                header_written: false,
+            },
+            StreamFormat::Mp3 => State::Mp3(Mp3StreamEncoder::new(sample_rate)?),
+            StreamFormat::Pcm => State::Pcm,
+        };
+        Ok(Self { sample_rate, state })
+    }
+
+    /// Bytes for the next chunk of mono samples. May be empty when the
+    /// encoder buffers the chunk, as MP3 does for partial frames.
+    pub fn encode(&mut self, samples: &[f32]) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        match &mut self.state {
+            State::Wav { header_written } => {
+                if !*header_written {
+                    WavHeader::new(1, self.sample_rate, 32).write_header(&mut data)?;
+                    *header_written = true;
+                }
+                write_audio_chunk(&mut data, samples)?;
+            }
+            State::Mp3(encoder) => data = encoder.encode(samples)?,
+            State::Pcm => data = to_pcm16(samples),
+        }
+        Ok(data)
+    }
+
+    /// Bytes that end the stream: the MP3 frames still buffered, or the WAV
+    /// header of a stream that got no audio
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self.state {
+            State::Wav {
+                header_written: false,
+            } => {
+                let mut data = Vec::new();
+                WavHeader::new(1, self.sample_rate, 32).write_header(&mut data)?;
+                Ok(data)
+            }
+            State::Mp3(encoder) => encoder.finish(),
+            State::Wav { .. } | State::Pcm => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_encoder() {
+        assert_eq!(" WAV".parse(), Ok(StreamFormat::Wav));
+        assert!("ogg".parse::<StreamFormat>().is_err());
+
+        let mut wav = StreamEncoder::new(StreamFormat::Wav, 24000).unwrap();
+        let first = wav.encode(&[0.5]).unwrap();
+        let second = wav.encode(&[-0.5]).unwrap();
+        assert_eq!(&first[..4], b"RIFF");
+        assert_eq!(first.len(), 44 + 4);
+        assert_eq!(second, (-0.5f32).to_le_bytes());
+        assert!(wav.finish().unwrap().is_empty());
+
+        let empty = StreamEncoder::new(StreamFormat::Wav, 24000).unwrap();
+        assert_eq!(empty.finish().unwrap().len(), 44);
+
+        let mut pcm = StreamEncoder::new(StreamFormat::Pcm, 24000).unwrap();
+        assert_eq!(pcm.encode(&[1.0, -2.0]).unwrap(), [0xff, 0x7f, 0x00, 0x80]);
+    }
+}
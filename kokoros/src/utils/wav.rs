@@ -1,4 +1,9 @@
-use std::io::{self, Write};
+use crate::utils::audio::sample_to_i16;
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Bit depths `write_audio_samples` can encode: 16 and 24-bit integer PCM and
+/// 32-bit float
+pub const SUPPORTED_BITS_PER_SAMPLE: [u16; 3] = [16, 24, 32];
 
 pub struct WavHeader {
     pub channels: u16,
@@ -15,6 +20,20 @@ impl WavHeader {
         }
     }
 
+    /// WAV format tag: IEEE float for 32-bit samples, integer PCM otherwise
+    pub fn format_tag(&self) -> u16 {
+        if self.bits_per_sample == 32 { 3 } else { 1 }
+    }
+
+    /// Bytes per frame (one sample for every channel)
+    pub fn block_align(&self) -> u16 {
+        self.channels * self.bits_per_sample / 8
+    }
+
+    pub fn byte_rate(&self) -> u32 {
+        self.sample_rate * u32::from(self.block_align())
+    }
+
     pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         // RIFF header
         writer.write_all(b"RIFF")?;
@@ -24,14 +43,11 @@ impl WavHeader {
         // Format chunk
         writer.write_all(b"fmt ")?;
         writer.write_all(&(16u32).to_le_bytes())?; // Format chunk size
-        writer.write_all(&(3u16).to_le_bytes())?; // Format = 3 (IEEE float)
+        writer.write_all(&self.format_tag().to_le_bytes())?;
         writer.write_all(&self.channels.to_le_bytes())?;
         writer.write_all(&self.sample_rate.to_le_bytes())?;
-        let byte_rate =
-            self.sample_rate * u32::from(self.channels) * u32::from(self.bits_per_sample) / 8;
-        writer.write_all(&byte_rate.to_le_bytes())?;
-        let block_align = self.channels * self.bits_per_sample / 8;
-        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&self.byte_rate().to_le_bytes())?;
+        writer.write_all(&self.block_align().to_le_bytes())?;
         writer.write_all(&self.bits_per_sample.to_le_bytes())?;
 
         // Data chunk header
@@ -40,11 +56,170 @@ impl WavHeader {
 
         Ok(())
     }
+
+    /// Fill in the sizes `write_header` leaves as placeholders, once
+    /// `data_size` bytes of audio follow the header at the start of `writer`.
+    /// Streams too large for the 32-bit RIFF sizes keep the placeholders. The
+    /// writer is left at its end.
+    pub fn patch_sizes<W: Write + Seek>(writer: &mut W, data_size: u64) -> io::Result<()> {
+        let sizes = u32::try_from(data_size)
+            .ok()
+            .and_then(|data_size| Some((data_size.checked_add(36)?, data_size)));
+        if let Some((riff_size, data_size)) = sizes {
+            writer.seek(SeekFrom::Start(4))?;
+            writer.write_all(&riff_size.to_le_bytes())?;
+            writer.seek(SeekFrom::Start(40))?;
+            writer.write_all(&data_size.to_le_bytes())?;
+        }
+        writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
 }
 
 pub fn write_audio_chunk<W: Write>(writer: &mut W, samples: &[f32]) -> io::Result<()> {
-    for sample in samples {
-        writer.write_all(&sample.to_le_bytes())?;
+    write_audio_samples(writer, samples, 32)
+}
+
+/// Write `samples` as `bits_per_sample` little-endian samples, one of
+/// `SUPPORTED_BITS_PER_SAMPLE`. Integer formats clamp to full scale.
+pub fn write_audio_samples<W: Write>(
+    writer: &mut W,
+    samples: &[f32],
+    bits_per_sample: u16,
+) -> io::Result<()> {
+    match bits_per_sample {
+        16 => {
+            for &sample in samples {
+                writer.write_all(&sample_to_i16(sample).to_le_bytes())?;
+            }
+        }
+        24 => {
+            for sample in samples {
+                let pcm = (sample * 8_388_607.0).clamp(-8_388_608.0, 8_388_607.0) as i32;
+                // The low three bytes of the little-endian i32 are the packed i24
+                writer.write_all(&pcm.to_le_bytes()[..3])?;
+            }
+        }
+        32 => {
+            for sample in samples {
+                writer.write_all(&sample.to_le_bytes())?;
+            }
+        }
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported WAV bit depth {}", other),
+            ));
+        }
     }
     Ok(())
 }
+
+/// Read a WAV file as mono f32 samples, averaging its channels. Returns the
+/// samples and the sample rate.
+pub fn read_wav_mono(path: &str) -> Result<(Vec<f32>, u32), String> {
+    let audio = read_wav(path)?;
+    let channels = audio.channels.max(1) as usize;
+    let mono = audio
+        .samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, audio.sample_rate))
+}
+
+/// Samples and format of a WAV file read by `read_wav`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavAudio {
+    /// Interleaved samples, integer PCM scaled to [-1.0, 1.0]
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    /// Float rather than integer samples
+    pub float: bool,
+}
+
+impl WavAudio {
+    /// Frames of `channels` samples each
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Layout such as `24000 Hz, 2 channels, 16-bit PCM`, for error messages
+    pub fn describe_format(&self) -> String {
+        format!(
+            "{} Hz, {} channel{}, {}-bit {}",
+            self.sample_rate,
+            self.channels,
+            if self.channels == 1 { "" } else { "s" },
+            self.bits_per_sample,
+            if self.float { "float" } else { "PCM" }
+        )
+    }
+}
+
+/// Read the WAV file at `path` keeping its channels and format
+pub fn read_wav(path: &str) -> Result<WavAudio, String> {
+    let reader =
+        hound::WavReader::open(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let spec = reader.spec();
+    let samples: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect()
+        }
+    };
+    let samples = samples.map_err(|e| format!("failed to decode {}: {}", path, e))?;
+
+    Ok(WavAudio {
+        samples,
+        channels: spec.channels,
+        sample_rate: spec.sample_rate,
+        bits_per_sample: spec.bits_per_sample,
+        float: spec.sample_format == hound::SampleFormat::Float,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(samples: &[f32], bits: u16) -> Vec<u8> {
+        let mut cursor = io::Cursor::new(Vec::new());
+        WavHeader::new(1, 24_000, bits)
+            .write_header(&mut cursor)
+            .unwrap();
+        write_audio_samples(&mut cursor, samples, bits).unwrap();
+        let data_size = cursor.get_ref().len() as u64 - 44;
+        WavHeader::patch_sizes(&mut cursor, data_size).unwrap();
+        // Left at the end, so more audio would be appended
+        assert_eq!(cursor.position(), 44 + data_size);
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_integer_wav_round_trip() {
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 2.0];
+
+        let reader = hound::WavReader::new(io::Cursor::new(encode(&samples, 24))).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Int);
+        let decoded: Vec<i32> = reader.into_samples().map(Result::unwrap).collect();
+        assert_eq!(
+            decoded,
+            vec![0, 4_194_303, -4_194_303, 8_388_607, -8_388_607, 8_388_607]
+        );
+
+        let reader = hound::WavReader::new(io::Cursor::new(encode(&samples, 16))).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        let decoded: Vec<i16> = reader.into_samples().map(Result::unwrap).collect();
+        assert_eq!(decoded, vec![0, 16384, -16384, 32767, -32767, 32767]);
+
+        assert!(write_audio_samples(&mut Vec::new(), &samples, 8).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Golden audio comparison: synthesizes fixed inputs and compares them with
+//! the WAV files in `tests/goldens` within a tolerance, so numeric drift
+//! across platforms and execution providers passes but regressions don't.
+//!
+//! Needs `checkpoints/kokoro-v1.0.onnx` and `data/voices-v1.0.bin` at the
+//! workspace root (or `KOKOROS_TEST_MODEL` and `KOKOROS_TEST_VOICES`), and is
+//! skipped without them. `KOKOROS_UPDATE_GOLDENS=1` rewrites the goldens from
+//! the current output instead of comparing.
+//!
+//! The comparison is ignored by default until goldens are committed, since the
+//! vocoder's noise has no seed control yet; run it with `--ignored`.
+
+use kokoros::tts::koko::{InitConfig, TTSKoko};
+use kokoros::utils::wav::read_wav_mono;
+use std::path::{Path, PathBuf};
+
+/// Name of the golden file, text, voice and speed of every case
+const CASES: &[(&str, &str, &str, f32)] = &[
+    (
+        "en_us_sky",
+        "The quick brown fox jumps over the lazy dog.",
+        "af_sky",
+        1.0,
+    ),
+    (
+        "en_gb_emma_slow",
+        "Numbers like 42 and dates like May 5th are read aloud.",
+        "bf_emma",
+        0.8,
+    ),
+];
+
+/// Largest accepted RMS of the per-sample difference, overridden by
+/// `KOKOROS_GOLDEN_TOLERANCE`. The vocoder's noise source and float drift
+/// stay well below it, a changed pronunciation or broken audio does not
+const DEFAULT_RMS_TOLERANCE: f64 = 0.02;
+
+/// Largest accepted length difference, as a fraction of the golden's length
+const LENGTH_TOLERANCE: f64 = 0.01;
+
+fn workspace_path(env: &str, default: &str) -> PathBuf {
+    std::env::var_os(env).map(PathBuf::from).unwrap_or_else(|| {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join(default)
+    })
+}
+
+/// Check `actual` against `golden`: lengths within `LENGTH_TOLERANCE` and the
+/// RMS of the difference over their common part within `rms_tolerance`
+fn compare(actual: &[f32], golden: &[f32], rms_tolerance: f64) -> Result<f64, String> {
+    let (longer, shorter) = (
+        actual.len().max(golden.len()),
+        actual.len().min(golden.len()),
+    );
+    if shorter == 0 {
+        return Err(format!(
+            "{} samples against {} in the golden",
+            actual.len(),
+            golden.len()
+        ));
+    }
+    if (longer - shorter) as f64 > golden.len() as f64 * LENGTH_TOLERANCE {
+        return Err(format!(
+            "length {} samples differs from the golden's {} by more than {}%",
+            actual.len(),
+            golden.len(),
+            LENGTH_TOLERANCE * 100.0
+        ));
+    }
+
+    let sum_squares: f64 = actual
+        .iter()
+        .zip(golden)
+        .map(|(&a, &g)| (a as f64 - g as f64).powi(2))
+        .sum();
+    let rms = (sum_squares / shorter as f64).sqrt();
+    if rms > rms_tolerance {
+        return Err(format!(
+            "RMS error {:.5} exceeds the tolerance {}",
+            rms, rms_tolerance
+        ));
+    }
+    Ok(rms)
+}
+
+fn write_golden(path: &Path, samples: &[f32], sample_rate: u32) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+#[test]
+#[ignore = "no goldens are committed yet and the vocoder noise isn't seeded"]
+fn test_golden_audio() {
+    let model = workspace_path("KOKOROS_TEST_MODEL", "checkpoints/kokoro-v1.0.onnx");
+    let voices = workspace_path("KOKOROS_TEST_VOICES", "data/voices-v1.0.bin");
+    if !model.exists() || !voices.exists() {
+        eprintln!(
+            "skipping golden audio tests: {} or {} not found",
+            model.display(),
+            voices.display()
+        );
+        return;
+    }
+    let update = std::env::var("KOKOROS_UPDATE_GOLDENS").is_ok_and(|v| v == "1");
+    let tolerance = std::env::var("KOKOROS_GOLDEN_TOLERANCE")
+        .ok()
+        .map(|v| {
+            v.parse()
+                .expect("KOKOROS_GOLDEN_TOLERANCE must be a number")
+        })
+        .unwrap_or(DEFAULT_RMS_TOLERANCE);
+
+    let tts = TTSKoko::from_config_blocking(
+        model.to_str().unwrap(),
+        voices.to_str().unwrap(),
+        InitConfig::default(),
+    )
+    .unwrap_or_else(|e| panic!("failed to load the model: {}", e));
+    let goldens = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/goldens");
+    let mut failures = Vec::new();
+    for &(name, text, voice, speed) in CASES {
+        let lan = if voice.starts_with('b') {
+            "en-gb"
+        } else {
+            "en-us"
+        };
+        let audio = tts
+            .tts_raw_audio(text, lan, voice, speed, None, None, None, None)
+            .unwrap_or_else(|e| panic!("{}: synthesis failed: {}", name, e));
+        let path = goldens.join(format!("{}.wav", name));
+
+        if update {
+            write_golden(&path, &audio, tts.sample_rate());
+            eprintln!("updated {}", path.display());
+            continue;
+        }
+        let (golden, rate) = match read_wav_mono(path.to_str().unwrap()) {
+            Ok(golden) => golden,
+            Err(e) => {
+                failures.push(format!(
+                    "{}: {}, run with KOKOROS_UPDATE_GOLDENS=1 to create it",
+                    name, e
+                ));
+                continue;
+            }
+        };
+        assert_eq!(rate, tts.sample_rate(), "{}: sample rate", name);
+        match compare(&audio, &golden, tolerance) {
+            Ok(rms) => eprintln!("{}: RMS error {:.5}", name, rms),
+            Err(e) => failures.push(format!("{}: {}", name, e)),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_compare() {
+    let golden: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+    let drifted: Vec<f32> = golden.iter().map(|s| s + 0.001).collect();
+    assert!(compare(&drifted, &golden, DEFAULT_RMS_TOLERANCE).is_ok());
+    assert!(compare(&golden[..995], &golden, DEFAULT_RMS_TOLERANCE).is_ok());
+
+    let silent = vec![0.0; 1000];
+    assert!(compare(&silent, &golden, DEFAULT_RMS_TOLERANCE).is_err());
+    assert!(compare(&golden[..900], &golden, DEFAULT_RMS_TOLERANCE).is_err());
+    assert!(compare(&[], &golden, DEFAULT_RMS_TOLERANCE).is_err());
+}